@@ -0,0 +1,69 @@
+use bevy::prelude::*;
+use bevy_vello::{VelloPlugin, prelude::*};
+
+fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins)
+        .add_plugins(VelloPlugin::default())
+        .add_systems(Startup, setup)
+        .add_systems(Update, animation)
+        .run();
+}
+
+fn setup(mut commands: Commands) {
+    commands.spawn((Camera2d, VelloView));
+
+    // A striped backdrop to blend with
+    let mut backdrop = VelloScene2d::new();
+    for i in 0..8 {
+        let color = if i % 2 == 0 {
+            peniko::Color::new([0.9, 0.7, 0.2, 1.0])
+        } else {
+            peniko::Color::new([0.2, 0.4, 0.8, 1.0])
+        };
+        backdrop.fill(
+            peniko::Fill::NonZero,
+            kurbo::Affine::IDENTITY,
+            color,
+            None,
+            &kurbo::Rect::new(
+                -400.0 + i as f64 * 100.0,
+                -200.0,
+                -300.0 + i as f64 * 100.0,
+                200.0,
+            ),
+        );
+    }
+    commands.spawn(backdrop);
+
+    // The same circle, blended four ways
+    for (i, blend_mode) in [
+        VelloBlendMode::NORMAL,
+        VelloBlendMode::MULTIPLY,
+        VelloBlendMode::SCREEN,
+        VelloBlendMode::ADD,
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        commands.spawn((
+            VelloScene2d::new(),
+            blend_mode,
+            Transform::from_xyz(-300.0 + i as f32 * 200.0, 0.0, 1.0),
+        ));
+    }
+}
+
+fn animation(mut scenes: Query<&mut VelloScene2d, With<VelloBlendMode>>, time: Res<Time>) {
+    let radius = 70.0 + time.elapsed_secs_f64().sin() * 20.0;
+    for mut scene in scenes.iter_mut() {
+        scene.reset();
+        scene.fill(
+            peniko::Fill::NonZero,
+            kurbo::Affine::IDENTITY,
+            peniko::Color::new([0.8, 0.3, 0.5, 1.0]),
+            None,
+            &kurbo::Circle::new((0.0, 0.0), radius),
+        );
+    }
+}
@@ -0,0 +1,115 @@
+use bevy::prelude::*;
+use bevy_vello::{VelloPlugin, prelude::*};
+
+fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins)
+        .add_plugins(VelloPlugin::default())
+        .add_systems(Startup, setup)
+        .add_systems(Update, (scroll, move_spotlight))
+        .run();
+}
+
+#[derive(Component)]
+struct ScrollingRow(f32);
+
+#[derive(Component)]
+struct Spotlight;
+
+fn setup(mut commands: Commands) {
+    commands.spawn((Camera2d, VelloView));
+
+    // A panel whose rows scroll past its rounded edges
+    let mut panel = VelloScene2d::new();
+    panel.fill(
+        peniko::Fill::NonZero,
+        kurbo::Affine::IDENTITY,
+        peniko::Color::new([0.15, 0.15, 0.2, 1.0]),
+        None,
+        &kurbo::RoundedRect::new(-150.0, -200.0, 150.0, 200.0, 24.0),
+    );
+    commands
+        .spawn((
+            panel,
+            VelloClip::shape(kurbo::RoundedRect::new(-150.0, -200.0, 150.0, 200.0, 24.0)),
+            Transform::from_xyz(-250.0, 0.0, 0.0),
+        ))
+        .with_children(|parent| {
+            for i in 0..8 {
+                let mut row = VelloScene2d::new();
+                row.fill(
+                    peniko::Fill::NonZero,
+                    kurbo::Affine::IDENTITY,
+                    peniko::Color::new([0.3 + i as f32 * 0.08, 0.5, 0.8, 1.0]),
+                    None,
+                    &kurbo::RoundedRect::new(-130.0, -30.0, 130.0, 30.0, 8.0),
+                );
+                let offset = i as f32 * 80.0;
+                parent.spawn((
+                    row,
+                    ScrollingRow(offset),
+                    Transform::from_xyz(0.0, 0.0, 1.0),
+                ));
+            }
+        });
+
+    // A striped backdrop, revealed by a moving spotlight
+    let mut backdrop = VelloScene2d::new();
+    for i in 0..6 {
+        let color = if i % 2 == 0 {
+            peniko::Color::new([0.9, 0.7, 0.2, 1.0])
+        } else {
+            peniko::Color::new([0.2, 0.4, 0.8, 1.0])
+        };
+        backdrop.fill(
+            peniko::Fill::NonZero,
+            kurbo::Affine::IDENTITY,
+            color,
+            None,
+            &kurbo::Rect::new(
+                -150.0 + i as f64 * 50.0,
+                -200.0,
+                -100.0 + i as f64 * 50.0,
+                200.0,
+            ),
+        );
+    }
+    let mut light = VelloScene2d::new();
+    light.fill(
+        peniko::Fill::NonZero,
+        kurbo::Affine::IDENTITY,
+        &peniko::Gradient::new_radial((0.0, 0.0), 100.0)
+            .with_stops([peniko::Color::WHITE, peniko::Color::BLACK]),
+        None,
+        &kurbo::Circle::new((0.0, 0.0), 100.0),
+    );
+    // The spotlight only serves as a mask, so it is hidden
+    let spotlight = commands
+        .spawn((
+            light,
+            Spotlight,
+            Visibility::Hidden,
+            Transform::from_xyz(250.0, 0.0, 0.0),
+        ))
+        .id();
+    commands.spawn((
+        backdrop,
+        VelloClip::luminance_mask(spotlight),
+        Transform::from_xyz(250.0, 0.0, 0.0),
+    ));
+}
+
+fn scroll(mut rows: Query<(&mut Transform, &ScrollingRow)>, time: Res<Time>) {
+    for (mut transform, row) in rows.iter_mut() {
+        let y = (row.0 + time.elapsed_secs() * 40.0) % 640.0;
+        transform.translation.y = y - 320.0;
+    }
+}
+
+fn move_spotlight(mut spotlight: Query<&mut Transform, With<Spotlight>>, time: Res<Time>) {
+    let t = time.elapsed_secs();
+    for mut transform in spotlight.iter_mut() {
+        transform.translation.x = 250.0 + t.cos() * 80.0;
+        transform.translation.y = (t * 1.3).sin() * 120.0;
+    }
+}
@@ -1,30 +1,21 @@
 use bevy::{
+    camera::{primitives::Aabb, visibility::RenderLayers},
     camera_controller::free_camera::{FreeCamera, FreeCameraPlugin},
     prelude::*,
-    render::{
-        Render, RenderApp, RenderSystems,
-        extract_component::{ExtractComponent, ExtractComponentPlugin},
-        render_asset::RenderAssets,
-        render_resource::{
-            Extent3d, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages,
-        },
-        renderer::{RenderDevice, RenderQueue},
-        texture::GpuImage,
-    },
 };
-use bevy_vello::{VelloPlugin, prelude::*, render::VelloRenderer};
-
-#[derive(Component, Clone, ExtractComponent)]
-pub struct VelloTarget(Handle<Image>);
+use bevy_vello::{VelloPlugin, prelude::*};
 
 // Marks the main pass cube, to which the texture is applied.
 #[derive(Component)]
 struct MainPassCube;
 
-fn main() {
-    let mut app = App::new();
+// Marks the scene while the pointer is over it, through the cube.
+#[derive(Component)]
+struct Hovered;
 
-    app.add_plugins(DefaultPlugins)
+fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins)
         .add_plugins(FreeCameraPlugin)
         .add_plugins(VelloPlugin {
             use_cpu: false,
@@ -32,20 +23,8 @@ fn main() {
             ..default()
         })
         .add_systems(Startup, setup)
-        .add_systems(Update, cube_rotator_system)
-        .add_plugins(ExtractComponentPlugin::<VelloTarget>::default());
-
-    let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
-        return;
-    };
-    render_app.add_systems(
-        Render,
-        render_texture
-            .in_set(RenderSystems::Render)
-            .run_if(resource_exists::<RenderDevice>),
-    );
-
-    app.run();
+        .add_systems(Update, (cube_rotator_system, animation))
+        .run();
 }
 
 fn setup(
@@ -54,32 +33,8 @@ fn setup(
     mut materials: ResMut<Assets<StandardMaterial>>,
     mut images: ResMut<Assets<Image>>,
 ) {
-    let size = Extent3d {
-        width: 512,
-        height: 512,
-        ..default()
-    };
     // This is the texture that will be rendered to.
-    let mut image = Image {
-        texture_descriptor: TextureDescriptor {
-            label: None,
-            size,
-            dimension: TextureDimension::D2,
-            format: TextureFormat::Rgba8Unorm,
-            mip_level_count: 1,
-            sample_count: 1,
-            usage: TextureUsages::TEXTURE_BINDING
-                | TextureUsages::COPY_DST
-                | TextureUsages::STORAGE_BINDING,
-            view_formats: &[],
-        },
-        ..default()
-    };
-
-    // fill image.data with zeroes
-    image.resize(size);
-
-    let image_handle = images.add(image);
+    let image_handle = images.add(VelloTextureTarget::new_image(512, 512));
 
     // This material has the texture that has been rendered.
     let material_handle = materials.add(StandardMaterial {
@@ -88,6 +43,22 @@ fn setup(
         unlit: false,
         ..default()
     });
+    // The main pass camera.
+    commands
+        .spawn(PointLight::default())
+        .insert(Transform::from_translation(Vec3::new(0.0, 0.0, 10.0)));
+
+    commands
+        .spawn((Camera3d::default(), FreeCamera::default()))
+        .insert(Transform::from_xyz(0.0, 0.0, 15.0).looking_at(Vec3::ZERO, Vec3::Y));
+
+    // Render the vector graphics on their own layer into the cube texture.
+    let texture_target = commands
+        .spawn((
+            VelloTextureTarget::new(image_handle),
+            RenderLayers::layer(1),
+        ))
+        .id();
     // Main pass cube, with material containing the rendered first pass texture.
     commands.spawn((
         Mesh3d(meshes.add(Cuboid::new(4.0, 4.0, 4.0))),
@@ -95,29 +66,34 @@ fn setup(
         Transform::from_xyz(0.0, 0.0, 1.5)
             .with_rotation(Quat::from_rotation_x(-std::f32::consts::PI / 5.0)),
         MainPassCube,
+        // Pointers over the cube pick the scene in its texture.
+        VelloPickingSurface::new(texture_target),
     ));
-    // The main pass camera.
     commands
-        .spawn(PointLight::default())
-        .insert(Transform::from_translation(Vec3::new(0.0, 0.0, 10.0)));
+        .spawn((
+            VelloScene2d::new(),
+            Aabb::from_min_max(Vec3::new(-256.0, -256.0, 0.0), Vec3::new(256.0, 256.0, 0.0)),
+            RenderLayers::layer(1),
+        ))
+        .observe(|over: On<Pointer<Over>>, mut commands: Commands| {
+            commands.entity(over.entity).insert(Hovered);
+        })
+        .observe(|out: On<Pointer<Out>>, mut commands: Commands| {
+            commands.entity(out.entity).remove::<Hovered>();
+        })
+        .observe(|press: On<Pointer<Press>>, hits: Res<VelloPickingHits>| {
+            if let Some(hit) = hits.get(press.pointer_id, press.entity) {
+                println!("Pressed the scene at {}", hit.position);
+            }
+        });
+}
 
-    commands
-        .spawn((Camera3d::default(), FreeCamera::default()))
-        .insert(Transform::from_xyz(0.0, 0.0, 15.0).looking_at(Vec3::ZERO, Vec3::Y));
+fn animation(scene: Single<(&mut VelloScene2d, Has<Hovered>)>, time: Res<Time>) {
+    let (mut scene, hovered) = scene.into_inner();
 
-    commands.spawn(VelloTarget(image_handle));
-}
+    // Reset scene every frame
+    scene.reset();
 
-fn render_texture(
-    renderer: Res<VelloRenderer>,
-    render_settings: Res<VelloRenderSettings>,
-    target: Single<&VelloTarget>,
-    device: Res<RenderDevice>,
-    gpu_images: Res<RenderAssets<GpuImage>>,
-    queue: Res<RenderQueue>,
-    time: Res<Time>,
-) {
-    let mut scene = vello::Scene::default();
     // Animate the scene
     let sin_time = time.elapsed_secs().sin().mul_add(0.5, 0.5);
     let c = Vec3::lerp(
@@ -127,30 +103,22 @@ fn render_texture(
     );
     scene.fill(
         peniko::Fill::NonZero,
-        kurbo::Affine::translate((128.0, 128.0)),
+        kurbo::Affine::default(),
+        if hovered {
+            peniko::Color::from_rgb8(255, 220, 120)
+        } else {
+            peniko::Color::WHITE
+        },
+        None,
+        &kurbo::Rect::new(-256.0, -256.0, 256.0, 256.0),
+    );
+    scene.fill(
+        peniko::Fill::NonZero,
+        kurbo::Affine::default(),
         peniko::Color::new([c.x, c.y, c.z, 1.]),
         None,
-        &kurbo::RoundedRect::new(0.0, 0.0, 256.0, 256.0, (sin_time as f64) * 128.0),
+        &kurbo::RoundedRect::new(-128.0, -128.0, 128.0, 128.0, (sin_time as f64) * 128.0),
     );
-
-    let gpu_image = gpu_images.get(target.0.id()).unwrap();
-    let params = vello::RenderParams {
-        base_color: vello::peniko::Color::WHITE,
-        width: gpu_image.size.width,
-        height: gpu_image.size.height,
-        antialiasing_method: render_settings.antialiasing,
-    };
-    renderer
-        .lock()
-        .unwrap()
-        .render_to_texture(
-            device.wgpu_device(),
-            &queue,
-            &scene,
-            &gpu_image.texture_view,
-            &params,
-        )
-        .unwrap();
 }
 
 /// Rotates the outer cube (main pass)
@@ -0,0 +1,98 @@
+use std::f64::consts::TAU;
+
+use bevy::prelude::*;
+use bevy_vello::{VelloPlugin, prelude::*};
+
+fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins)
+        .add_plugins(VelloPlugin::default())
+        .register_vello_renderable::<PieChart>()
+        .add_systems(Startup, setup)
+        .add_systems(Update, animation)
+        .run();
+}
+
+/// A chart component drawn by Vello, like the built-in scene, SVG and Lottie components.
+#[derive(Component, Clone)]
+struct PieChart {
+    values: Vec<f64>,
+    center: kurbo::Point,
+    radius: f64,
+    alpha: f32,
+}
+
+impl VelloRenderable for PieChart {
+    fn encode(&self, scene: &mut vello::Scene, affine: kurbo::Affine) {
+        let total: f64 = self.values.iter().sum();
+        if total <= 0.0 {
+            return;
+        }
+        let mut start = -TAU / 4.0;
+        for (i, value) in self.values.iter().enumerate() {
+            let sweep = value / total * TAU;
+            let hue = i as f32 / self.values.len() as f32 * 360.0;
+            let color = Color::hsl(hue, 0.7, 0.55).to_srgba();
+            scene.fill(
+                peniko::Fill::NonZero,
+                affine,
+                peniko::Color::new([color.red, color.green, color.blue, 1.0]),
+                None,
+                &kurbo::CircleSegment::new(self.center, self.radius, 0.0, start, sweep),
+            );
+            start += sweep;
+        }
+    }
+
+    fn alpha(&self) -> f32 {
+        self.alpha
+    }
+
+    fn bounds(&self) -> Option<kurbo::Rect> {
+        Some(kurbo::Rect::from_center_size(
+            self.center,
+            (self.radius * 2.0, self.radius * 2.0),
+        ))
+    }
+}
+
+fn setup(mut commands: Commands) {
+    commands.spawn((Camera2d, VelloView));
+
+    // A world chart, sorted by z with other Vello items.
+    commands.spawn((
+        PieChart {
+            values: vec![3.0, 2.0, 1.0, 1.0],
+            center: kurbo::Point::ZERO,
+            radius: 150.0,
+            alpha: 1.0,
+        },
+        Transform::from_xyz(-150.0, 0.0, 0.0),
+    ));
+
+    // A UI chart, filling its node. UI items are drawn from the node's top-left corner.
+    commands.spawn((
+        PieChart {
+            values: vec![1.0, 1.0, 2.0],
+            center: kurbo::Point::new(80.0, 80.0),
+            radius: 80.0,
+            alpha: 0.8,
+        },
+        Node {
+            position_type: PositionType::Absolute,
+            right: px(40),
+            top: px(40),
+            width: px(160),
+            height: px(160),
+            ..default()
+        },
+    ));
+}
+
+fn animation(mut charts: Query<&mut PieChart, Without<Node>>, time: Res<Time>) {
+    for mut chart in charts.iter_mut() {
+        let t = time.elapsed_secs_f64();
+        chart.values[0] = 3.0 + t.sin() * 2.0;
+        chart.alpha = (t.cos() as f32).mul_add(0.4, 0.6);
+    }
+}
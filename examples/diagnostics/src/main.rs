@@ -4,9 +4,15 @@ use bevy_vello::{VelloPlugin, prelude::*};
 fn main() {
     let mut app = App::new();
     app.add_plugins(DefaultPlugins)
-        .add_plugins(VelloPlugin::default())
+        .add_plugins(VelloPlugin {
+            // Don't block startup on shader compilation, and reuse compiled pipelines on the next
+            // launch where supported.
+            background_init: true,
+            pipeline_cache_dir: Some(std::env::temp_dir().join("bevy_vello_diagnostics")),
+            ..default()
+        })
         .add_systems(Startup, setup)
-        .add_systems(Update, simple_animation)
+        .add_systems(Update, (simple_animation, log_renderer_ready))
         .add_systems(Update, update_scene_count_ui);
 
     app.run();
@@ -42,7 +48,19 @@ fn setup(mut commands: Commands) {
 #[derive(Component)]
 struct DiagnosticsText;
 
-fn simple_animation(mut query: Query<(&mut Transform, &mut VelloScene2d)>, time: Res<Time>) {
+fn simple_animation(
+    mut query: Query<(&mut Transform, &mut VelloScene2d)>,
+    time: Res<Time>,
+    keys: Res<ButtonInput<KeyCode>>,
+    mut paused: Local<bool>,
+) {
+    // Pause to let Vello skip rendering unchanged frames
+    if keys.just_pressed(KeyCode::Space) {
+        *paused = !*paused;
+    }
+    if *paused {
+        return;
+    }
     let sin_time = time.elapsed_secs().sin().mul_add(0.5, 0.5);
 
     for (mut transform, mut scene) in query.iter_mut() {
@@ -101,12 +119,25 @@ fn update_scene_count_ui(
         .map(|m| m.value)
         .unwrap_or(0.0);
 
+    let skipped_frames_count = diagnostics
+        .get(&bevy_vello::render::diagnostics::SKIPPED_FRAMES_COUNT)
+        .and_then(|d| d.measurement())
+        .map(|m| m.value)
+        .unwrap_or(0.0);
+
     text.0 = format!(
-        r#"Diagnostics
+        r#"Diagnostics (press space to pause)
     Total scenes: {scene_count}
     Total paths: {path_count}
     Total path segments: {path_segs_count}
     Total clips: {clips_count}
-    Total open clips: {open_clips_count}"#
+    Total open clips: {open_clips_count}
+    Total skipped frames: {skipped_frames_count}"#
     );
 }
+
+fn log_renderer_ready(mut ready: MessageReader<VelloRendererReady>, time: Res<Time>) {
+    for _ in ready.read() {
+        info!("Vello renderer ready after {:.2}s", time.elapsed_secs_f64());
+    }
+}
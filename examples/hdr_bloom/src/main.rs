@@ -0,0 +1,82 @@
+use bevy::{
+    core_pipeline::tonemapping::Tonemapping, post_process::bloom::Bloom, prelude::*,
+    render::view::Hdr,
+};
+use bevy_vello::{VelloPlugin, prelude::*};
+
+fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins)
+        .add_plugins(VelloPlugin::default())
+        .add_systems(Startup, setup)
+        .add_systems(Update, (animation, adjust_intensity))
+        .run();
+}
+
+fn setup(mut commands: Commands) {
+    commands.spawn((
+        Camera2d,
+        Camera {
+            clear_color: ClearColorConfig::Custom(Color::BLACK),
+            ..default()
+        },
+        Hdr,
+        Tonemapping::TonyMcMapface,
+        Bloom::NATURAL,
+        VelloView,
+        // Render Vello content in linear light, brighter than white so it blooms.
+        VelloViewSettings {
+            canvas_format: VelloCanvasFormat::Rgba16Float,
+            intensity: 4.0,
+            ..default()
+        },
+    ));
+    commands.spawn(VelloScene2d::new());
+    commands.spawn((
+        Text::new("Up/Down: adjust intensity"),
+        Node {
+            position_type: PositionType::Absolute,
+            top: px(12),
+            left: px(12),
+            ..default()
+        },
+    ));
+}
+
+fn animation(mut scene: Single<&mut VelloScene2d>, time: Res<Time>) {
+    scene.reset();
+
+    let sin_time = time.elapsed_secs().sin().mul_add(0.5, 0.5) as f64;
+    let stroke = kurbo::Stroke::new(6.0);
+    scene.stroke(
+        &stroke,
+        kurbo::Affine::default(),
+        peniko::Color::new([0.2, 0.8, 1.0, 1.0]),
+        None,
+        &kurbo::Circle::new((0.0, 0.0), 100.0 + sin_time * 40.0),
+    );
+    scene.stroke(
+        &stroke,
+        kurbo::Affine::rotate(time.elapsed_secs_f64()),
+        peniko::Color::new([1.0, 0.3, 0.6, 1.0]),
+        None,
+        &kurbo::RoundedRect::new(-60.0, -60.0, 60.0, 60.0, 12.0),
+    );
+}
+
+fn adjust_intensity(
+    mut settings: Single<&mut VelloViewSettings>,
+    keys: Res<ButtonInput<KeyCode>>,
+    time: Res<Time>,
+) {
+    let mut change = 0.0;
+    if keys.pressed(KeyCode::ArrowUp) {
+        change += 1.0;
+    }
+    if keys.pressed(KeyCode::ArrowDown) {
+        change -= 1.0;
+    }
+    if change != 0.0 {
+        settings.intensity = (settings.intensity + change * 4.0 * time.delta_secs()).max(0.0);
+    }
+}
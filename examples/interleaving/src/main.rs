@@ -0,0 +1,60 @@
+use bevy::prelude::*;
+use bevy_vello::{VelloPlugin, prelude::*};
+
+fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins)
+        .add_plugins(VelloPlugin {
+            // Split Vello content at the z of the sprite, so it can sit between two scenes.
+            canvas_z_layers: vec![5.0],
+            ..default()
+        })
+        .add_systems(Startup, setup)
+        .add_systems(Update, animation)
+        .run();
+}
+
+#[derive(Component)]
+struct Bounce(f32);
+
+fn setup(mut commands: Commands) {
+    commands.spawn((Camera2d, VelloView));
+
+    // Drawn below the sprite.
+    commands.spawn((
+        VelloScene2d::new(),
+        Transform::from_xyz(-60.0, 0.0, 3.0),
+        Bounce(0.0),
+    ));
+    // A Bevy sprite between the two Vello scenes.
+    commands.spawn((
+        Sprite::from_color(Color::WHITE, Vec2::splat(150.0)),
+        Transform::from_xyz(0.0, 0.0, 5.0),
+    ));
+    // Drawn above the sprite.
+    commands.spawn((
+        VelloScene2d::new(),
+        Transform::from_xyz(60.0, 0.0, 7.0),
+        Bounce(std::f32::consts::PI),
+    ));
+}
+
+fn animation(mut scenes: Query<(&mut Transform, &mut VelloScene2d, &Bounce)>, time: Res<Time>) {
+    for (mut transform, mut scene, bounce) in scenes.iter_mut() {
+        let sin_time = (time.elapsed_secs() + bounce.0).sin();
+        transform.translation.y = sin_time * 100.0;
+
+        scene.reset();
+        scene.fill(
+            peniko::Fill::NonZero,
+            kurbo::Affine::default(),
+            if transform.translation.z < 5.0 {
+                peniko::Color::new([0.2, 0.4, 1.0, 1.0])
+            } else {
+                peniko::Color::new([1.0, 0.4, 0.2, 1.0])
+            },
+            None,
+            &kurbo::RoundedRect::new(-60.0, -60.0, 60.0, 60.0, 20.0),
+        );
+    }
+}
@@ -15,6 +15,7 @@ fn main() {
     .add_systems(Startup, setup_camera)
     .add_systems(Startup, enable_debug)
     .add_systems(Startup, load_lottie)
+    .add_systems(Startup, load_ui_lottie)
     .add_systems(Update, print_metadata);
     embedded_asset!(app, "assets/calendar.json");
     app.run();
@@ -38,6 +39,8 @@ fn load_lottie(mut commands: Commands, asset_server: ResMut<AssetServer>) {
                 ..Default::default()
             },
             Transform::from_scale(Vec3::splat(20.0)),
+            // Only hit over the calendar itself, not its transparent corners
+            VelloPickingMode::Shape,
         ))
         .observe(on_pointer_press)
         .observe(on_pointer_release)
@@ -45,13 +48,61 @@ fn load_lottie(mut commands: Commands, asset_server: ResMut<AssetServer>) {
         .observe(on_pointer_leave);
 }
 
+fn load_ui_lottie(mut commands: Commands, asset_server: ResMut<AssetServer>) {
+    commands
+        .spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                right: Val::Px(20.0),
+                bottom: Val::Px(20.0),
+                // Wider than the calendar, which is letterboxed inside
+                width: Val::Px(320.0),
+                height: Val::Px(160.0),
+                ..default()
+            },
+            UiVelloLottie(asset_server.load("embedded://picking/assets/calendar.json")),
+            PlaybackOptions {
+                speed: 0.0,
+                ..Default::default()
+            },
+            VelloPickingMode::Shape,
+        ))
+        .observe(on_ui_pointer_press);
+}
+
+fn on_ui_pointer_press(trigger: On<Pointer<Press>>, hits: Res<VelloPickingHits>) {
+    if let Some(hit) = hits.get(trigger.pointer_id, trigger.entity) {
+        println!("Pressed at {} in the UI Lottie", hit.position);
+    }
+}
+
 fn on_pointer_enter(trigger: On<Pointer<Over>>, mut theme: Single<&mut Theme>) {
     println!("Mouse entered: {}", trigger.event());
     *(theme.as_mut()) = Theme::new().add("calendar", css::ORANGE.into());
 }
 
-fn on_pointer_press(trigger: On<Pointer<Press>>, mut theme: Single<&mut Theme>) {
+fn on_pointer_press(
+    trigger: On<Pointer<Press>>,
+    hits: Res<VelloPickingHits>,
+    lottie: Query<(&VelloLottie2d, &Playhead)>,
+    lotties: Res<Assets<VelloLottie>>,
+    mut theme: Single<&mut Theme>,
+) {
     println!("Mouse pressed: {}", trigger.event());
+    if let Some(hit) = hits.get(trigger.pointer_id, trigger.entity) {
+        println!("Pressed at {} in the Lottie", hit.position);
+        let layer = lottie
+            .get(trigger.entity)
+            .ok()
+            .and_then(|(lottie, playhead)| {
+                lotties
+                    .get(&lottie.0)?
+                    .layer_at(playhead.frame(), hit.position)
+            });
+        if let Some(layer) = layer {
+            println!("Pressed the {layer:?} layer");
+        }
+    }
     *(theme.as_mut()) = Theme::new().add("calendar", css::RED.into());
 }
 
@@ -0,0 +1,101 @@
+//! Shows how to render Vello content through multiple cameras, each with its own canvas.
+
+use bevy::{camera::Viewport, prelude::*, window::WindowResized};
+use bevy_vello::{VelloPlugin, prelude::*};
+
+fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins)
+        .add_plugins(VelloPlugin::default())
+        .add_systems(Startup, (setup_cameras, setup_vector_graphics))
+        .add_systems(Update, (set_camera_viewports, animation))
+        .run();
+}
+
+/// Marks which half of the window a camera renders to.
+#[derive(Component)]
+struct CameraPosition(u32);
+
+fn setup_cameras(mut commands: Commands) {
+    commands.spawn((
+        Camera2d,
+        Camera {
+            order: 0,
+            ..default()
+        },
+        CameraPosition(0),
+        VelloView,
+    ));
+    commands.spawn((
+        Camera2d,
+        Camera {
+            order: 1,
+            ..default()
+        },
+        // Zoomed out, like a minimap.
+        Projection::Orthographic(OrthographicProjection {
+            scale: 2.0,
+            ..OrthographicProjection::default_2d()
+        }),
+        CameraPosition(1),
+        VelloView,
+        // This view has its own background and antialiasing.
+        VelloViewSettings {
+            base_color: Color::srgb(0.1, 0.1, 0.15),
+            antialiasing: Some(vello::AaConfig::Msaa16),
+        },
+    ));
+}
+
+fn set_camera_viewports(
+    window: Single<&Window>,
+    mut resize_events: MessageReader<WindowResized>,
+    mut cameras: Query<(&CameraPosition, &mut Camera)>,
+) {
+    // We need to dynamically resize the camera's viewports whenever the window size changes.
+    for _ in resize_events.read() {
+        let size = UVec2::new(
+            window.resolution.physical_width() / 2,
+            window.resolution.physical_height(),
+        );
+        for (position, mut camera) in cameras.iter_mut() {
+            camera.viewport = Some(Viewport {
+                physical_position: UVec2::new(position.0 * size.x, 0),
+                physical_size: size,
+                ..default()
+            });
+        }
+    }
+}
+
+fn setup_vector_graphics(mut commands: Commands) {
+    commands.spawn(VelloScene2d::new());
+}
+
+fn animation(scene: Single<(&mut Transform, &mut VelloScene2d)>, time: Res<Time>) {
+    let sin_time = time.elapsed_secs().sin().mul_add(0.5, 0.5);
+    let (mut transform, mut scene) = scene.into_inner();
+
+    // Reset scene every frame
+    scene.reset();
+
+    // Animate color green to blue
+    let c = Vec3::lerp(
+        Vec3::new(-1.0, 1.0, -1.0),
+        Vec3::new(-1.0, 1.0, 1.0),
+        sin_time + 0.5,
+    );
+
+    // Animate the corner radius
+    scene.fill(
+        peniko::Fill::NonZero,
+        kurbo::Affine::default(),
+        peniko::Color::new([c.x, c.y, c.z, 1.]),
+        None,
+        &kurbo::RoundedRect::new(-50.0, -50.0, 50.0, 50.0, (sin_time as f64) * 50.0),
+    );
+
+    transform.scale = Vec3::lerp(Vec3::ONE * 0.5, Vec3::ONE * 1.0, sin_time);
+    transform.translation = Vec3::lerp(Vec3::X * -100.0, Vec3::X * 100.0, sin_time);
+    transform.rotation = Quat::from_rotation_z(-std::f32::consts::TAU * sin_time);
+}
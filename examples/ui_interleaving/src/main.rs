@@ -0,0 +1,86 @@
+use bevy::{color::palettes::css, prelude::*};
+use bevy_vello::{VelloPlugin, prelude::*};
+
+fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins)
+        .add_plugins(VelloPlugin {
+            // Draw Vello UI in Bevy's UI pass, so native nodes can sit between Vello nodes.
+            interleave_ui: true,
+            ..default()
+        })
+        .add_systems(Startup, setup)
+        .add_systems(Update, draw_cards)
+        .run();
+}
+
+#[derive(Component)]
+struct Card(peniko::Color);
+
+fn setup(mut commands: Commands) {
+    commands.spawn((Camera2d, VelloView));
+
+    // Siblings are stacked in spawn order: a Vello card, a native panel, then another Vello card.
+    commands
+        .spawn(Node {
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            ..default()
+        })
+        .with_children(|parent| {
+            parent.spawn((
+                card_node(-120.0, -60.0),
+                UiVelloScene::new(),
+                Card(peniko::Color::from_rgba8(220, 60, 60, 255)),
+            ));
+            parent
+                .spawn((
+                    Node {
+                        border_radius: BorderRadius::all(Val::Px(16.0)),
+                        ..card_node(0.0, 0.0)
+                    },
+                    BackgroundColor(css::DARK_SLATE_GRAY.into()),
+                ))
+                .with_child((
+                    Text::new("Native Bevy UI, above the red card and below the blue one"),
+                    TextFont::from_font_size(24.0),
+                    Node {
+                        margin: UiRect::all(Val::Px(20.0)),
+                        ..default()
+                    },
+                ));
+            parent.spawn((
+                card_node(120.0, 60.0),
+                UiVelloScene::new(),
+                Card(peniko::Color::from_rgba8(60, 110, 220, 255)),
+            ));
+        });
+}
+
+fn card_node(x: f32, y: f32) -> Node {
+    Node {
+        position_type: PositionType::Absolute,
+        left: Val::Px(300.0 + x),
+        top: Val::Px(200.0 + y),
+        width: Val::Px(320.0),
+        height: Val::Px(200.0),
+        ..default()
+    }
+}
+
+fn draw_cards(mut cards: Query<(&ComputedNode, &Card, &mut UiVelloScene)>, time: Res<Time>) {
+    for (node, card, mut scene) in cards.iter_mut() {
+        // We draw with logical pixels. We need the logical size of this node.
+        let size = node.size() * node.inverse_scale_factor();
+        let wobble = (time.elapsed_secs_f64() * 2.0).sin() * 8.0;
+
+        scene.reset();
+        scene.fill(
+            peniko::Fill::NonZero,
+            kurbo::Affine::translate((0.0, wobble)),
+            card.0,
+            None,
+            &kurbo::RoundedRect::new(0.0, 0.0, size.x as f64, size.y as f64, 24.0),
+        );
+    }
+}
@@ -0,0 +1,34 @@
+// Converts Vello's output into a float canvas.
+//
+// Vello renders straight (not premultiplied) alpha with sRGB-encoded color into an `Rgba8Unorm`
+// texture. Float canvases hold straight alpha with linear-light color instead, scaled by the view
+// intensity so content can exceed 1.0 and feed HDR post-processing such as bloom.
+
+@group(0) @binding(0)
+var source: texture_2d<f32>;
+@group(0) @binding(1)
+var<uniform> intensity: vec4<f32>;
+
+@vertex
+fn vertex(@builtin(vertex_index) vertex_index: u32) -> @builtin(position) vec4<f32> {
+    // A single triangle covering the whole target.
+    let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+fn linear_from_srgba(srgba: vec4<f32>) -> vec4<f32> {
+    return vec4(
+        select(
+            srgba.rgb / 12.92,
+            pow((srgba.rgb + .055) / 1.055, vec3(2.4)),
+            srgba.rgb > vec3(0.04045)
+        ),
+        srgba.a,
+    );
+}
+
+@fragment
+fn fragment(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let color = linear_from_srgba(textureLoad(source, vec2<i32>(position.xy), 0));
+    return vec4(color.rgb * intensity.x, color.a);
+}
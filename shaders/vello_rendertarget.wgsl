@@ -20,6 +20,8 @@ fn vertex(in: Vertex) -> VertexOutput {
     return out;
 }
 
+// Vello writes sRGB-encoded color, while the view target expects linear light. Canvases in a
+// float format already hold linear light, see `vello_linear_convert.wgsl`.
 fn linear_from_srgba(srgba: vec4<f32>) -> vec4<f32> {
     return vec4(
         select(
@@ -35,6 +37,10 @@ fn linear_from_srgba(srgba: vec4<f32>) -> vec4<f32> {
 fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
     let uv = frag_coord_to_uv(in.position.xy, view.viewport);
     let color = textureSample(texture, texture_sampler, uv);
+#ifdef LINEAR_CANVAS
+    return color;
+#else
     let color_converted = linear_from_srgba(color);
     return color_converted;
+#endif
 }
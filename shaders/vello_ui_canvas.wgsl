@@ -0,0 +1,38 @@
+// Draws a canvas of Vello UI items over its camera's viewport, in Bevy's UI pass.
+
+@group(0) @binding(0)
+var texture: texture_2d<f32>;
+@group(0) @binding(1)
+var texture_sampler: sampler;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+@vertex
+fn vertex(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    // A single triangle covering the whole viewport, with the canvas origin at the top left.
+    let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+    var out: VertexOutput;
+    out.position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
+
+// Vello writes sRGB-encoded color, while the view target expects linear light.
+fn linear_from_srgba(srgba: vec4<f32>) -> vec4<f32> {
+    return vec4(
+        select(
+            srgba.rgb / 12.92,
+            pow((srgba.rgb + .055) / 1.055, vec3(2.4)),
+            srgba.rgb > vec3(0.04045)
+        ),
+        srgba.a,
+    );
+}
+
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+    return linear_from_srgba(textureSample(texture, texture_sampler, in.uv));
+}
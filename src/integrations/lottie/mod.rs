@@ -57,6 +57,83 @@ impl LottieAssetVariant for VelloLottie2d {
     }
 }
 
+#[cfg(feature = "picking")]
+impl crate::picking::VelloPickingShape for VelloLottie2d {
+    type Data = &'static Playhead;
+    type Param = Res<'static, Assets<VelloLottie>>;
+
+    fn contains(
+        &self,
+        playhead: &&Playhead,
+        lotties: &Res<Assets<VelloLottie>>,
+        point: vello::kurbo::Point,
+    ) -> bool {
+        lotties
+            .get(&self.0)
+            .is_some_and(|lottie| frame_contains(lottie, playhead.frame(), point))
+    }
+}
+
+/// Returns whether `frame` paints at `point`, in the composition's coordinates.
+#[cfg(feature = "picking")]
+fn frame_contains(lottie: &VelloLottie, frame: f64, point: vello::kurbo::Point) -> bool {
+    let mut scene = vello::Scene::new();
+    render::append_lottie(
+        &mut scene,
+        lottie,
+        frame,
+        1.0,
+        crate::render::VelloBlendMode::NORMAL,
+        vello::kurbo::Affine::IDENTITY,
+    );
+    crate::picking::scene_contains(&scene, point)
+}
+
+#[cfg(feature = "picking")]
+impl VelloLottie {
+    /// The name of the topmost layer painting at `position` at `frame`, in the composition's
+    /// coordinates like [`VelloHit::position`](crate::prelude::VelloHit::position).
+    ///
+    /// Layers are rendered one at a time until one is hit, so look layers up when they are
+    /// needed, e.g. on a click, rather than every frame.
+    pub fn layer_at(&self, frame: f64, position: Vec2) -> Option<String> {
+        let point = vello::kurbo::Point::new(position.x.into(), position.y.into());
+        if !frame_contains(self, frame, point) {
+            return None;
+        }
+
+        // Hide every layer, keeping mattes that mask them. Hidden layers still move the layers
+        // parented to them.
+        let mut isolated = VelloLottie {
+            composition: std::sync::Arc::new(self.composition.as_ref().clone()),
+            alpha: self.alpha,
+        };
+        let contents: Vec<_> = std::sync::Arc::get_mut(&mut isolated.composition)?
+            .layers
+            .iter_mut()
+            .map(|layer| {
+                (!layer.is_mask)
+                    .then(|| std::mem::replace(&mut layer.content, velato::model::Content::None))
+            })
+            .collect();
+
+        // Show each layer on its own, from the top down
+        for (index, content) in contents.into_iter().enumerate() {
+            let Some(content) = content else {
+                continue;
+            };
+            std::sync::Arc::get_mut(&mut isolated.composition)?.layers[index].content = content;
+            let hit = frame_contains(&isolated, frame, point);
+            let layer = &mut std::sync::Arc::get_mut(&mut isolated.composition)?.layers[index];
+            if hit {
+                return Some(layer.name.clone());
+            }
+            layer.content = velato::model::Content::None;
+        }
+        None
+    }
+}
+
 /// A renderable Lottie that may be used in Bevy UI.
 ///
 /// ### Object fit
@@ -76,6 +153,43 @@ impl LottieAssetVariant for VelloLottie2d {
 #[component(on_add = super::lottie::player::hooks::on_add_lottie::<UiVelloLottie>)]
 pub struct UiVelloLottie(pub Handle<VelloLottie>);
 
+#[cfg(feature = "picking")]
+impl crate::picking::VelloUiPickingShape for UiVelloLottie {
+    type Data = &'static Playhead;
+    type Param = Res<'static, Assets<VelloLottie>>;
+
+    fn content_position(
+        &self,
+        _playhead: &&Playhead,
+        lotties: &Res<Assets<VelloLottie>>,
+        node: &ComputedNode,
+        point: Vec2,
+    ) -> Option<Vec2> {
+        let lottie = lotties.get(&self.0)?;
+        let size = Vec2::new(
+            lottie.composition.width as f32,
+            lottie.composition.height as f32,
+        );
+        crate::picking::fit_content_position(node, size, point)
+    }
+
+    fn contains(
+        &self,
+        playhead: &&Playhead,
+        lotties: &Res<Assets<VelloLottie>>,
+        point: vello::kurbo::Point,
+    ) -> bool {
+        lotties.get(&self.0).is_some_and(|lottie| {
+            let size = Vec2::new(
+                lottie.composition.width as f32,
+                lottie.composition.height as f32,
+            );
+            crate::picking::in_content(size, point)
+                && frame_contains(lottie, playhead.frame(), point)
+        })
+    }
+}
+
 impl LottieAssetVariant for UiVelloLottie {
     fn asset_id(&self) -> AssetId<VelloLottie> {
         self.id()
@@ -111,3 +225,69 @@ pub enum VelloLottieAnchor {
     /// Bounds start from the render position and advance down and to the left.
     TopRight,
 }
+
+#[cfg(all(test, feature = "picking"))]
+mod tests {
+    use super::*;
+
+    /// Two 40x40 buttons, centered at (25, 25) and (75, 25).
+    const ARROWS: &str = r#"{
+  "v": "5.7.4", "fr": 30, "ip": 0, "op": 30, "w": 100, "h": 50, "nm": "arrows", "ddd": 0,
+  "assets": [],
+  "layers": [
+    {
+      "ddd": 0, "ind": 1, "ty": 4, "nm": "right_arrow", "sr": 1, "ao": 0,
+      "ip": 0, "op": 30, "st": 0, "bm": 0,
+      "ks": {
+        "o": { "a": 0, "k": 100 },
+        "r": { "a": 0, "k": 0 },
+        "p": { "a": 0, "k": [75, 25, 0] },
+        "a": { "a": 0, "k": [0, 0, 0] },
+        "s": { "a": 0, "k": [100, 100, 100] }
+      },
+      "shapes": [{
+        "ty": "gr", "nm": "button",
+        "it": [
+          { "ty": "rc", "nm": "rect", "d": 1, "s": { "a": 0, "k": [40, 40] }, "p": { "a": 0, "k": [0, 0] }, "r": { "a": 0, "k": 0 } },
+          { "ty": "fl", "nm": "fill", "c": { "a": 0, "k": [1, 0, 0, 1] }, "o": { "a": 0, "k": 100 }, "r": 1 },
+          { "ty": "tr", "p": { "a": 0, "k": [0, 0] }, "a": { "a": 0, "k": [0, 0] }, "s": { "a": 0, "k": [100, 100] }, "r": { "a": 0, "k": 0 }, "o": { "a": 0, "k": 100 }, "sk": { "a": 0, "k": 0 }, "sa": { "a": 0, "k": 0 } }
+        ]
+      }]
+    },
+    {
+      "ddd": 0, "ind": 2, "ty": 4, "nm": "left_arrow", "sr": 1, "ao": 0,
+      "ip": 0, "op": 30, "st": 0, "bm": 0,
+      "ks": {
+        "o": { "a": 0, "k": 100 },
+        "r": { "a": 0, "k": 0 },
+        "p": { "a": 0, "k": [25, 25, 0] },
+        "a": { "a": 0, "k": [0, 0, 0] },
+        "s": { "a": 0, "k": [100, 100, 100] }
+      },
+      "shapes": [{
+        "ty": "gr", "nm": "button",
+        "it": [
+          { "ty": "rc", "nm": "rect", "d": 1, "s": { "a": 0, "k": [40, 40] }, "p": { "a": 0, "k": [0, 0] }, "r": { "a": 0, "k": 0 } },
+          { "ty": "fl", "nm": "fill", "c": { "a": 0, "k": [1, 0, 0, 1] }, "o": { "a": 0, "k": 100 }, "r": 1 },
+          { "ty": "tr", "p": { "a": 0, "k": [0, 0] }, "a": { "a": 0, "k": [0, 0] }, "s": { "a": 0, "k": [100, 100] }, "r": { "a": 0, "k": 0 }, "o": { "a": 0, "k": 100 }, "sk": { "a": 0, "k": 0 }, "sa": { "a": 0, "k": 0 } }
+        ]
+      }]
+    }
+  ]
+}"#;
+
+    #[test]
+    fn layer_at_names_the_layer_painting_at_a_position() {
+        let lottie = load_lottie_from_str(ARROWS).unwrap();
+        assert_eq!(
+            lottie.layer_at(0.0, Vec2::new(25.0, 25.0)).as_deref(),
+            Some("left_arrow")
+        );
+        assert_eq!(
+            lottie.layer_at(0.0, Vec2::new(90.0, 40.0)).as_deref(),
+            Some("right_arrow")
+        );
+        // Between the buttons
+        assert_eq!(lottie.layer_at(0.0, Vec2::new(50.0, 25.0)), None);
+    }
+}
@@ -105,36 +105,59 @@ fn observe_pointer_exit<A: LottieAssetVariant>(
 #[cfg(feature = "picking")]
 fn observe_pointer_click<A: LottieAssetVariant>(
     trigger: On<Pointer<Click>>,
-    mut lottie: Query<(&mut LottiePlayer<A>, &A)>,
+    mut lottie: Query<(&mut LottiePlayer<A>, &A, &Playhead)>,
     lotties: Res<Assets<VelloLottie>>,
+    hits: Res<VelloPickingHits>,
 ) -> Result {
     debug!(entity = ?trigger.entity, event= ?trigger.event(), "Lottie event: Pointer Click");
-    let (mut player, lottie) = lottie.get_mut(trigger.entity)?;
+    let (mut player, lottie, playhead) = lottie.get_mut(trigger.entity)?;
 
     if player.stopped || player.states.len() <= 1 {
         return Ok(());
     }
-    if lotties.get(lottie.asset_id()).is_none() {
+    let Some(asset) = lotties.get(lottie.asset_id()) else {
         // Asset has not loaded yet and is therefore not visible. It would be odd to run transitions on assets that aren't visible.
         return Ok(());
     };
 
-    if let Some(next_state) =
-        player
-            .state()
-            .transitions
-            .iter()
-            .find_map(|transition| match transition {
-                PlayerTransition::OnMouseClick { state } => Some(*state),
-                _ => None,
-            })
-    {
+    // Only look the clicked layer up if a transition needs it
+    let transitions = &player.state().transitions;
+    let needs_layer = transitions
+        .iter()
+        .any(|transition| matches!(transition, PlayerTransition::OnLayerClick { .. }));
+    let clicked_layer = hits
+        .get(trigger.pointer_id, trigger.entity)
+        .filter(|_| needs_layer)
+        .and_then(|hit| asset.layer_at(playhead.frame(), hit.position));
+    if let Some(next_state) = click_transition(transitions, clicked_layer.as_deref()) {
         player.next_state.replace(next_state);
     }
 
     Ok(())
 }
 
+/// The state a click transitions to. Clicks on a layer with an
+/// [`OnLayerClick`](PlayerTransition::OnLayerClick) transition take it over any
+/// [`OnMouseClick`](PlayerTransition::OnMouseClick) transition.
+#[cfg(feature = "picking")]
+fn click_transition(
+    transitions: &[PlayerTransition],
+    clicked_layer: Option<&str>,
+) -> Option<&'static str> {
+    let layer_transition = clicked_layer.and_then(|clicked| {
+        transitions.iter().find_map(|transition| match transition {
+            PlayerTransition::OnLayerClick { layer, state } if *layer == clicked => Some(*state),
+            _ => None,
+        })
+    });
+    layer_transition.or_else(|| {
+        transitions.iter().find_map(|transition| match transition {
+            PlayerTransition::OnMouseClick { state } => Some(*state),
+            _ => None,
+        })
+    })
+}
+
 fn observe_on_show<A: LottieAssetVariant>(
     trigger: On<LottieOnShowEvent>,
     mut lottie: Query<(&mut LottiePlayer<A>, &mut A)>,
@@ -197,3 +220,29 @@ fn observe_on_complete<A: LottieAssetVariant>(
 
     Ok(())
 }
+
+#[cfg(all(test, feature = "picking"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn layer_clicks_take_their_transition_over_any_click() {
+        let transitions = [
+            PlayerTransition::OnMouseClick { state: "clicked" },
+            PlayerTransition::OnLayerClick {
+                layer: "left_arrow",
+                state: "left",
+            },
+        ];
+        assert_eq!(
+            click_transition(&transitions, Some("left_arrow")),
+            Some("left")
+        );
+        assert_eq!(
+            click_transition(&transitions, Some("right_arrow")),
+            Some("clicked")
+        );
+        assert_eq!(click_transition(&transitions, None), Some("clicked"));
+        assert_eq!(click_transition(&transitions[1..], None), None);
+    }
+}
@@ -217,6 +217,12 @@ pub enum PlayerTransition {
     OnMouseEnter { state: &'static str },
     /// Transition to the given state when the mouse clicks inside the image bounding box.
     OnMouseClick { state: &'static str },
+    /// Transition to the given state when the mouse clicks the named layer, where it paints at the
+    /// current frame. Takes precedence over [`OnMouseClick`](Self::OnMouseClick).
+    OnLayerClick {
+        layer: &'static str,
+        state: &'static str,
+    },
     /// Transition to the given state when the mouse exits the image bounding box.
     OnMouseLeave { state: &'static str },
     /// Transition to the given state on first render of this state.
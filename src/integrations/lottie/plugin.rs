@@ -9,7 +9,7 @@ use super::{
 };
 use crate::{
     integrations::lottie::{UiVelloLottie, VelloLottie2d},
-    render::{VelatoRenderer, extract::VelloExtractStep},
+    render::extract::VelloExtractStep,
 };
 
 pub struct LottieIntegrationPlugin;
@@ -17,7 +17,10 @@ pub struct LottieIntegrationPlugin;
 impl Plugin for LottieIntegrationPlugin {
     fn build(&self, app: &mut App) {
         #[cfg(feature = "picking")]
-        app.add_plugins(crate::picking::WorldPickingPlugin::<VelloLottie2d>::default());
+        app.add_plugins((
+            crate::picking::WorldPickingPlugin::<VelloLottie2d>::default(),
+            crate::picking::UiPickingPlugin::<UiVelloLottie>::default(),
+        ));
 
         app.init_asset_loader::<VelloLottieLoader>()
             .init_asset::<VelloLottie>()
@@ -64,18 +67,21 @@ impl Plugin for LottieIntegrationPlugin {
         };
 
         render_app
-            .init_resource::<VelatoRenderer>()
+            .init_resource::<render::RecoloredLotties>()
             .add_systems(
                 ExtractSchedule,
                 (
-                    render::extract_world_lottie_assets,
-                    render::extract_ui_lottie_assets,
-                )
-                    .in_set(VelloExtractStep::ExtractAssets),
+                    (
+                        render::extract_world_lottie_assets,
+                        render::extract_ui_lottie_assets,
+                    )
+                        .in_set(VelloExtractStep::ExtractAssets),
+                    render::retain_used_recolored_lotties.after(VelloExtractStep::ExtractAssets),
+                ),
             )
             .add_systems(
                 Render,
-                (render::prepare_asset_affines).in_set(RenderSystems::Prepare),
+                (render::prepare_asset_affines).in_set(RenderSystems::Queue),
             );
     }
 }
@@ -1,68 +1,109 @@
+use std::{cell::RefCell, sync::Arc};
+
+use velato::Composition;
+
 use bevy::{
     camera::visibility::RenderLayers,
+    ecs::entity::EntityHashMap,
+    platform::collections::HashMap,
     prelude::*,
-    render::{
-        Extract, camera::ExtractedCamera, sync_world::TemporaryRenderEntity, view::ExtractedView,
-    },
+    render::{Extract, sync_world::TemporaryRenderEntity},
 };
 use vello::kurbo::Affine;
 
 use super::{Playhead, Theme, VelloLottieAnchor, asset::VelloLottie};
 use crate::integrations::lottie::{UiVelloLottie, VelloLottie2d};
-use crate::render::{VelloEntityCountData, VelloView, prepare::PreparedAffine};
+use crate::render::{
+    VelloBlendMode, VelloEntityCountData, VelloFrameChanges, WorldSortKey,
+    encode::push_item_layer,
+    extract::{ExtractedVelloView, UiClipShapes, VelloHierarchy, VelloUiClips, VelloViewLayers},
+    prepare::{PreparedAffines, ui_node_views},
+};
 
 #[derive(Component, Clone)]
 pub struct ExtractedVelloLottie2d {
+    /// The asset, with its composition recolored by the entity's [`Theme`].
     pub asset: VelloLottie,
     pub asset_anchor: VelloLottieAnchor,
     pub transform: GlobalTransform,
+    pub render_layers: RenderLayers,
+    pub blend_mode: VelloBlendMode,
+    pub group: Option<Entity>,
+    pub clip: Option<Entity>,
+    pub sort_key: WorldSortKey,
     pub alpha: f32,
-    pub theme: Option<Theme>,
     pub playhead: f64,
 }
 
 #[derive(Component, Clone)]
 pub struct ExtractedUiVelloLottie {
+    /// The asset, with its composition recolored by the entity's [`Theme`].
     pub asset: VelloLottie,
     pub ui_transform: UiGlobalTransform,
     pub alpha: f32,
-    pub theme: Option<Theme>,
     pub playhead: f64,
     pub ui_node: ComputedNode,
+    pub target_camera: Option<Entity>,
+    pub render_layers: RenderLayers,
+    pub blend_mode: VelloBlendMode,
+    pub group: Option<Entity>,
     pub clip: Option<Rect>,
+    pub clip_shapes: UiClipShapes,
 }
 
+#[allow(clippy::too_many_arguments, reason = "Many system parameters")]
 pub fn extract_world_lottie_assets(
     mut commands: Commands,
-    query_views: Query<
-        (&ExtractedCamera, Option<&RenderLayers>),
-        (With<Camera2d>, With<VelloView>),
-    >,
+    views: VelloViewLayers,
+    hierarchy: VelloHierarchy,
     query_vectors: Extract<
         Query<
             (
+                Entity,
                 &VelloLottie2d,
                 &VelloLottieAnchor,
                 &GlobalTransform,
                 &Playhead,
-                Option<&Theme>,
+                Option<Ref<Theme>>,
                 Option<&RenderLayers>,
                 &ViewVisibility,
                 &InheritedVisibility,
+                Option<&VelloBlendMode>,
             ),
             Without<Node>,
         >,
     >,
+    changed_items: Extract<
+        Query<
+            (),
+            (
+                With<VelloLottie2d>,
+                Without<Node>,
+                Or<(
+                    Changed<VelloLottie2d>,
+                    Changed<VelloLottieAnchor>,
+                    Changed<Playhead>,
+                    Changed<Theme>,
+                    Changed<GlobalTransform>,
+                    Changed<ViewVisibility>,
+                    Changed<InheritedVisibility>,
+                    Changed<RenderLayers>,
+                    Changed<VelloBlendMode>,
+                )>,
+            ),
+        >,
+    >,
     assets: Extract<Res<Assets<VelloLottie>>>,
+    mut asset_events: Extract<MessageReader<AssetEvent<VelloLottie>>>,
     mut frame_data: ResMut<VelloEntityCountData>,
+    mut frame_changes: ResMut<VelloFrameChanges>,
+    mut themes: Local<SharedThemes>,
+    mut recolored: ResMut<RecoloredLotties>,
 ) {
     let mut n_lotties = 0;
 
-    // Sort cameras by rendering order
-    let mut views: Vec<_> = query_views.iter().collect();
-    views.sort_unstable_by_key(|(camera, _)| camera.order);
-
     for (
+        entity,
         asset_handle,
         asset_anchor,
         transform,
@@ -71,10 +112,12 @@ pub fn extract_world_lottie_assets(
         render_layers,
         view_visibility,
         inherited_visibility,
+        blend_mode,
     ) in query_vectors.iter()
     {
-        // Skip if visibility conditions are not met
-        if !view_visibility.get() || !inherited_visibility.get() {
+        // Skip if visibility conditions are not met.
+        // View visibility is only checked for cameras, see below.
+        if !inherited_visibility.get() {
             continue;
         }
         // Skip if asset isn't loaded.
@@ -82,56 +125,92 @@ pub fn extract_world_lottie_assets(
             continue;
         };
 
-        // Check if any camera renders this asset
+        // Check if any view renders this asset
         let asset_render_layers = render_layers.unwrap_or_default();
-        if views.iter().any(|(_, camera_layers)| {
-            asset_render_layers.intersects(camera_layers.unwrap_or_default())
-        }) {
+        // Counted even on frames a texture target drawing it skips, so those don't redraw views
+        if views.may_draw_world_item(view_visibility, asset_render_layers) {
+            n_lotties += 1;
+        }
+        if views.draws_world_item(view_visibility, asset_render_layers) {
             commands
                 .spawn(ExtractedVelloLottie2d {
-                    asset: asset.clone(),
+                    asset: recolored.get(asset_handle.id(), asset, themes.get(entity, theme)),
                     transform: *transform,
                     asset_anchor: *asset_anchor,
-                    theme: theme.cloned(),
+                    render_layers: asset_render_layers.clone(),
+                    blend_mode: blend_mode.copied().unwrap_or_default(),
                     playhead: playhead.frame(),
-                    alpha: asset.alpha,
+                    alpha: asset.alpha * hierarchy.opacity(entity),
+                    group: hierarchy.group(entity),
+                    clip: hierarchy.clip(entity),
+                    sort_key: hierarchy.sort_key(entity, transform),
                 })
                 .insert(TemporaryRenderEntity);
-            n_lotties += 1;
         }
     }
 
+    // Redraw if any Lottie was added, removed or changed, or an asset changed
+    let assets_changed = asset_events.read().count() > 0;
+    if assets_changed || n_lotties != frame_data.n_world_lotties || !changed_items.is_empty() {
+        frame_changes.items_changed = true;
+    }
     frame_data.n_world_lotties = n_lotties;
+    themes.retain_seen();
 }
 
+#[allow(clippy::too_many_arguments, reason = "Many system parameters")]
 pub fn extract_ui_lottie_assets(
     mut commands: Commands,
-    query_views: Query<
-        (&ExtractedCamera, Option<&RenderLayers>),
-        (With<Camera2d>, With<VelloView>),
-    >,
+    views: VelloViewLayers,
+    hierarchy: VelloHierarchy,
+    ui_clips: VelloUiClips,
     query_vectors: Extract<
         Query<(
+            Entity,
             &UiVelloLottie,
             &UiGlobalTransform,
             &Playhead,
-            Option<&Theme>,
+            Option<Ref<Theme>>,
             &ComputedNode,
             Option<&RenderLayers>,
             &InheritedVisibility,
+            Option<&ComputedUiTargetCamera>,
             Option<&CalculatedClip>,
+            Option<&VelloBlendMode>,
         )>,
     >,
+    changed_items: Extract<
+        Query<
+            (),
+            (
+                With<UiVelloLottie>,
+                Or<(
+                    Changed<UiVelloLottie>,
+                    Changed<Playhead>,
+                    Changed<Theme>,
+                    Changed<UiGlobalTransform>,
+                    Changed<ComputedNode>,
+                    Changed<ComputedUiRenderTargetInfo>,
+                    Changed<ComputedUiTargetCamera>,
+                    Changed<CalculatedClip>,
+                    Changed<InheritedVisibility>,
+                    Changed<RenderLayers>,
+                    Changed<VelloBlendMode>,
+                )>,
+            ),
+        >,
+    >,
     assets: Extract<Res<Assets<VelloLottie>>>,
+    mut asset_events: Extract<MessageReader<AssetEvent<VelloLottie>>>,
     mut frame_data: ResMut<VelloEntityCountData>,
+    mut frame_changes: ResMut<VelloFrameChanges>,
+    mut themes: Local<SharedThemes>,
+    mut recolored: ResMut<RecoloredLotties>,
 ) {
     let mut n_lotties = 0;
 
-    // Sort cameras by rendering order
-    let mut views: Vec<_> = query_views.iter().collect();
-    views.sort_unstable_by_key(|(camera, _)| camera.order);
-
     for (
+        entity,
         asset_handle,
         ui_transform,
         playhead,
@@ -139,7 +218,9 @@ pub fn extract_ui_lottie_assets(
         ui_node,
         render_layers,
         inherited_visibility,
+        target_camera,
         calc_clip,
+        blend_mode,
     ) in query_vectors.iter()
     {
         // Skip if visibility conditions are not met.
@@ -155,108 +236,240 @@ pub fn extract_ui_lottie_assets(
 
         // Check if any camera renders this asset
         let asset_render_layers = render_layers.unwrap_or_default();
-        if views.iter().any(|(_, camera_layers)| {
-            asset_render_layers.intersects(camera_layers.unwrap_or_default())
-        }) {
+        if views.draws_ui_item(asset_render_layers) {
+            let (clip, clip_shapes) = ui_clips.get(entity, calc_clip);
             commands
                 .spawn(ExtractedUiVelloLottie {
-                    asset: asset.clone(),
+                    asset: recolored.get(asset_handle.id(), asset, themes.get(entity, theme)),
                     ui_transform: *ui_transform,
-                    theme: theme.cloned(),
                     playhead: playhead.frame(),
-                    alpha: asset.alpha,
+                    alpha: asset.alpha * hierarchy.opacity(entity),
+                    group: hierarchy.group(entity),
                     ui_node: *ui_node,
-                    clip: calc_clip.map(|c| c.clip),
+                    target_camera: target_camera.and_then(ComputedUiTargetCamera::get),
+                    render_layers: asset_render_layers.clone(),
+                    blend_mode: blend_mode.copied().unwrap_or_default(),
+                    clip,
+                    clip_shapes,
                 })
                 .insert(TemporaryRenderEntity);
             n_lotties += 1;
         }
     }
 
+    // Redraw if any Lottie was added, removed or changed, or an asset changed
+    let assets_changed = asset_events.read().count() > 0;
+    if assets_changed || n_lotties != frame_data.n_ui_lotties || !changed_items.is_empty() {
+        frame_changes.items_changed = true;
+    }
     frame_data.n_ui_lotties = n_lotties;
+    themes.retain_seen();
+}
+
+/// Themes shared with the render world, cloned and hashed only when they change instead of every
+/// frame.
+#[derive(Default)]
+pub struct SharedThemes {
+    themes: EntityHashMap<SharedTheme>,
+    seen: EntityHashMap<SharedTheme>,
+}
+
+/// A [`Theme`] with the hash of its contents.
+#[derive(Clone)]
+pub struct SharedTheme {
+    theme: Arc<Theme>,
+    hash: u64,
+}
+
+impl SharedThemes {
+    fn get(&mut self, entity: Entity, theme: Option<Ref<Theme>>) -> Option<SharedTheme> {
+        let theme = theme?;
+        let shared = match self.themes.remove(&entity) {
+            Some(shared) if !theme.is_changed() => shared,
+            _ => SharedTheme {
+                hash: theme.content_hash(),
+                theme: Arc::new(Theme::clone(&theme)),
+            },
+        };
+        self.seen.insert(entity, shared.clone());
+        Some(shared)
+    }
+
+    /// Drops the themes of entities that weren't extracted this frame.
+    fn retain_seen(&mut self) {
+        self.themes = std::mem::take(&mut self.seen);
+    }
+}
+
+/// Theme-recolored compositions, shared by all Lotties with the same asset and theme contents.
+///
+/// A composition is only recolored again when its asset or theme changes, so themed Lotties cost
+/// the same to render as unthemed ones.
+#[derive(Resource, Default)]
+pub struct RecoloredLotties {
+    entries: HashMap<(AssetId<VelloLottie>, u64), RecoloredLottie>,
+}
+
+struct RecoloredLottie {
+    /// The composition that was recolored, to notice asset changes.
+    source: Arc<Composition>,
+    theme: Arc<Theme>,
+    recolored: Arc<Composition>,
+    used: bool,
+}
+
+impl RecoloredLottie {
+    fn new(asset: &VelloLottie, theme: Arc<Theme>) -> Self {
+        Self {
+            source: asset.composition.clone(),
+            recolored: Arc::new(theme.recolor(&asset.composition)),
+            theme,
+            used: false,
+        }
+    }
+}
+
+impl RecoloredLotties {
+    /// Returns `asset` recolored by `theme`, recoloring it only if no cached composition matches.
+    fn get(
+        &mut self,
+        id: AssetId<VelloLottie>,
+        asset: &VelloLottie,
+        theme: Option<SharedTheme>,
+    ) -> VelloLottie {
+        let Some(SharedTheme { theme, hash }) = theme else {
+            return asset.clone();
+        };
+        let entry = self
+            .entries
+            .entry((id, hash))
+            .or_insert_with(|| RecoloredLottie::new(asset, theme.clone()));
+        // The asset was modified, or two themes share a hash
+        let stale = !Arc::ptr_eq(&entry.source, &asset.composition)
+            || !(Arc::ptr_eq(&entry.theme, &theme) || entry.theme == theme);
+        if stale {
+            *entry = RecoloredLottie::new(asset, theme);
+        }
+        entry.used = true;
+        VelloLottie {
+            composition: entry.recolored.clone(),
+            alpha: asset.alpha,
+        }
+    }
+
+    /// Drops the compositions no Lottie used since the last call.
+    fn retain_used(&mut self) {
+        self.entries
+            .retain(|_, entry| std::mem::take(&mut entry.used));
+    }
+}
+
+/// Drops recolored compositions that were not extracted this frame.
+pub fn retain_used_recolored_lotties(mut recolored: ResMut<RecoloredLotties>) {
+    recolored.retain_used();
 }
 
 pub fn prepare_asset_affines(
     mut commands: Commands,
-    views: Query<(&ExtractedCamera, &ExtractedView), (With<Camera2d>, With<VelloView>)>,
+    views: Query<(Entity, &ExtractedVelloView)>,
     render_entities: Query<(Entity, &ExtractedVelloLottie2d)>,
     render_ui_entities: Query<(Entity, &ExtractedUiVelloLottie)>,
 ) {
-    for (camera, view) in views.iter() {
-        // Render UI
-        for (entity, render_entity) in render_ui_entities.iter() {
-            let ui_transform = render_entity.ui_transform;
+    let ui_views: Vec<_> = views
+        .iter()
+        .filter_map(|(view_entity, view)| {
+            view.camera
+                .map(|camera| (view_entity, camera, view.render_layers.clone()))
+        })
+        .collect();
 
-            // A transposed (flipped over its diagonal) PostScript matrix
-            // | a c e |
-            // | b d f |
-            // | 0 0 1 |
-            //
-            // Components
-            // | scale_x skew_x translate_x |
-            // | skew_y scale_y translate_y |
-            // | skew_z skew_z scale_z |
-            //
-            // rotate (z)
-            // | cos(θ) -sin(θ) translate_x |
-            // | sin(θ) cos(θ) translate_y |
-            // | skew_z skew_z scale_z |
-            //
-            // The order of operations is important, as it affects the final transformation matrix.
-            //
-            // Order of operations:
-            // 1. Scale
-            // 2. Rotate
-            // 3. Translate
-            let transform: [f64; 6] = {
-                // Convert UiGlobalTransform to Mat4
-                let mat2 = ui_transform.matrix2;
-                let translation = ui_transform.translation;
-                let model_matrix = Mat4::from_cols_array_2d(&[
-                    [mat2.x_axis.x, mat2.x_axis.y, 0.0, 0.0],
-                    [mat2.y_axis.x, mat2.y_axis.y, 0.0, 0.0],
-                    [0.0, 0.0, 1.0, 0.0],
-                    [translation.x, translation.y, 0.0, 1.0],
-                ]);
-                let (width, height) = (
-                    render_entity.asset.composition.width as f32,
-                    render_entity.asset.composition.height as f32,
-                );
-                let local_center_matrix = Transform::from_translation(Vec3 {
-                    x: width / 2.0,
-                    y: height / 2.0,
-                    z: 0.0,
-                })
-                .to_matrix()
-                .inverse();
-                // Fill the bevy_ui Node with the asset size
-                let aspect_fill_matrix = {
-                    let asset_size = Vec2::new(width, height);
-                    let fill_scale = render_entity.ui_node.size() / asset_size;
-                    let scale_factor = fill_scale.x.min(fill_scale.y); // Maintain aspect ratio
-                    Mat4::from_scale(Vec3::new(scale_factor, scale_factor, 1.0))
-                };
+    // Render UI
+    for (entity, render_entity) in render_ui_entities.iter() {
+        let ui_transform = render_entity.ui_transform;
 
-                // Transform chain: ui_transform (in logical px) → aspect_fill → local_center
-                let raw_transform = model_matrix * aspect_fill_matrix * local_center_matrix;
-                let transform = raw_transform.to_cols_array();
-                [
-                    transform[0] as f64,  // a // scale_x
-                    transform[1] as f64,  // b // skew_y
-                    transform[4] as f64,  // c // skew_x
-                    transform[5] as f64,  // d // scale_y
-                    transform[12] as f64, // e // translate_x
-                    transform[13] as f64, // f // translate_y
-                ]
+        // A transposed (flipped over its diagonal) PostScript matrix
+        // | a c e |
+        // | b d f |
+        // | 0 0 1 |
+        //
+        // Components
+        // | scale_x skew_x translate_x |
+        // | skew_y scale_y translate_y |
+        // | skew_z skew_z scale_z |
+        //
+        // rotate (z)
+        // | cos(θ) -sin(θ) translate_x |
+        // | sin(θ) cos(θ) translate_y |
+        // | skew_z skew_z scale_z |
+        //
+        // The order of operations is important, as it affects the final transformation matrix.
+        //
+        // Order of operations:
+        // 1. Scale
+        // 2. Rotate
+        // 3. Translate
+        let transform: [f64; 6] = {
+            // Convert UiGlobalTransform to Mat4
+            let mat2 = ui_transform.matrix2;
+            let translation = ui_transform.translation;
+            let model_matrix = Mat4::from_cols_array_2d(&[
+                [mat2.x_axis.x, mat2.x_axis.y, 0.0, 0.0],
+                [mat2.y_axis.x, mat2.y_axis.y, 0.0, 0.0],
+                [0.0, 0.0, 1.0, 0.0],
+                [translation.x, translation.y, 0.0, 1.0],
+            ]);
+            let (width, height) = (
+                render_entity.asset.composition.width as f32,
+                render_entity.asset.composition.height as f32,
+            );
+            let local_center_matrix = Transform::from_translation(Vec3 {
+                x: width / 2.0,
+                y: height / 2.0,
+                z: 0.0,
+            })
+            .to_matrix()
+            .inverse();
+            // Fill the bevy_ui Node with the asset size
+            let aspect_fill_matrix = {
+                let asset_size = Vec2::new(width, height);
+                let fill_scale = render_entity.ui_node.size() / asset_size;
+                let scale_factor = fill_scale.x.min(fill_scale.y); // Maintain aspect ratio
+                Mat4::from_scale(Vec3::new(scale_factor, scale_factor, 1.0))
             };
 
-            commands
-                .entity(entity)
-                .insert(PreparedAffine(Affine::new(transform)));
-        }
+            // Transform chain: ui_transform (in logical px) → aspect_fill → local_center
+            let raw_transform = model_matrix * aspect_fill_matrix * local_center_matrix;
+            let transform = raw_transform.to_cols_array();
+            [
+                transform[0] as f64,  // a // scale_x
+                transform[1] as f64,  // b // skew_y
+                transform[4] as f64,  // c // skew_x
+                transform[5] as f64,  // d // scale_y
+                transform[12] as f64, // e // translate_x
+                transform[13] as f64, // f // translate_y
+            ]
+        };
+
+        let affine = Affine::new(transform);
+        commands.entity(entity).insert(PreparedAffines(
+            ui_node_views(
+                &ui_views,
+                render_entity.target_camera,
+                &render_entity.render_layers,
+            )
+            .map(|view_entity| (view_entity, affine))
+            .collect(),
+        ));
+    }
+
+    // Render World
+    for (entity, render_entity) in render_entities.iter() {
+        let mut affines = PreparedAffines::default();
+        for (view_entity, view) in views.iter() {
+            if !render_entity.render_layers.intersects(&view.render_layers) {
+                continue;
+            }
 
-        // Render World
-        for (entity, render_entity) in render_entities.iter() {
             // A transposed (flipped over its diagonal) PostScript matrix
             // | a c e |
             // | b d f |
@@ -311,7 +524,7 @@ pub fn prepare_asset_affines(
                 anchor_matrix.w_axis.y *= -1.0;
 
                 let ndc_to_pixels_matrix = {
-                    let size_pixels: UVec2 = camera.physical_viewport_size.unwrap();
+                    let size_pixels: UVec2 = view.size;
                     let (pixels_x, pixels_y) = (size_pixels.x as f32, size_pixels.y as f32);
                     Mat4::from_cols_array_2d(&[
                         [pixels_x / 2.0, 0.0, 0.0, pixels_x / 2.0],
@@ -356,9 +569,41 @@ pub fn prepare_asset_affines(
                 ]
             };
 
-            commands
-                .entity(entity)
-                .insert(PreparedAffine(Affine::new(transform)));
+            affines.insert(view_entity, Affine::new(transform));
         }
+
+        commands.entity(entity).insert(affines);
+    }
+}
+
+thread_local! {
+    /// Lotties are encoded on several threads at once, each with its own renderer.
+    static VELATO_RENDERER: RefCell<velato::Renderer> = RefCell::new(velato::Renderer::new());
+}
+
+/// Appends a Lottie frame to `scene`, faded by `alpha` and blended by `blend_mode`.
+pub(crate) fn append_lottie(
+    scene: &mut vello::Scene,
+    asset: &VelloLottie,
+    playhead: f64,
+    alpha: f32,
+    blend_mode: VelloBlendMode,
+    affine: Affine,
+) {
+    if alpha <= 0.0 {
+        return;
+    }
+    let bounds = vello::kurbo::Rect::new(
+        0.0,
+        0.0,
+        asset.composition.width as f64,
+        asset.composition.height as f64,
+    );
+    let layer = push_item_layer(scene, blend_mode, alpha, affine, Some(bounds));
+    VELATO_RENDERER.with_borrow_mut(|renderer| {
+        renderer.append(&asset.composition, playhead, affine, 1.0, scene);
+    });
+    if layer {
+        scene.pop_layer();
     }
 }
@@ -37,21 +37,24 @@ fn prev_f64(x: f64) -> f64 {
 /// Advance all playheads in the scene
 pub fn advance_playheads<A: LottieAssetVariant>(
     mut lotties: Query<(&A, &mut Playhead, &mut LottiePlayer<A>, &PlaybackOptions)>,
-    mut assets: ResMut<Assets<VelloLottie>>,
+    assets: Res<Assets<VelloLottie>>,
     time: Res<Time>,
 ) {
     let all_lotties = lotties.iter_mut();
 
     for (asset, mut playhead, mut player, options) in all_lotties {
         // Get asset
-        let Some(asset) = assets.get_mut(asset.asset_id()) else {
+        let Some(asset) = assets.get(asset.asset_id()) else {
             continue;
         };
 
-        // Keep playhead bounded
+        // Keep playhead bounded. Only write on change, to not trigger a redraw of idle Lotties.
         let start_frame = options.segments.start.max(asset.composition.frames.start);
         let end_frame = prev_f64(options.segments.end.min(asset.composition.frames.end));
-        playhead.frame = playhead.frame.clamp(start_frame, end_frame);
+        let bounded_frame = playhead.frame.clamp(start_frame, end_frame);
+        if playhead.frame != bounded_frame {
+            playhead.frame = bounded_frame;
+        }
 
         // Check if we are stopped
         if player.stopped {
@@ -59,7 +62,9 @@ pub fn advance_playheads<A: LottieAssetVariant>(
         }
 
         // Set first render
-        playhead.first_render.get_or_insert(Instant::now());
+        if playhead.first_render.is_none() {
+            playhead.first_render = Some(Instant::now());
+        }
 
         // Auto play
         if !player.started && options.autoplay {
@@ -156,10 +161,10 @@ pub fn advance_playheads<A: LottieAssetVariant>(
 pub fn run_time_transitions<A: LottieAssetVariant>(
     mut commands: Commands,
     query_player: Query<(Entity, &LottiePlayer<A>, &Playhead, &PlaybackOptions, &A)>,
-    mut assets: ResMut<Assets<VelloLottie>>,
+    assets: Res<Assets<VelloLottie>>,
 ) {
     for (entity, player, playhead, options, lottie) in query_player.iter() {
-        let Some(current_asset) = assets.get_mut(lottie.asset_id()) else {
+        let Some(current_asset) = assets.get(lottie.asset_id()) else {
             // Asset has not loaded yet and is therefore not visible. It would be odd to run transitions on assets that aren't visible.
             continue;
         };
@@ -168,6 +173,7 @@ pub fn run_time_transitions<A: LottieAssetVariant>(
             match transition {
                 PlayerTransition::OnMouseEnter { .. }
                 | PlayerTransition::OnMouseClick { .. }
+                | PlayerTransition::OnLayerClick { .. }
                 | PlayerTransition::OnMouseLeave { .. } => {
                     // Handled via observer
                 }
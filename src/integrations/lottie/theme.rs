@@ -3,7 +3,12 @@
 //! A long-term vision here is a selector-styled language, but now is just color swapping by layer
 //! name.
 
-use bevy::{platform::collections::HashMap, prelude::*};
+use std::hash::BuildHasher;
+
+use bevy::{
+    platform::{collections::HashMap, hash::FixedHasher},
+    prelude::*,
+};
 use velato::{
     Composition,
     model::{Brush, Shape},
@@ -52,6 +57,17 @@ impl Theme {
 }
 
 impl Theme {
+    /// A hash of the swapped colors, equal for themes that recolor compositions the same way.
+    pub(crate) fn content_hash(&self) -> u64 {
+        let mut colors: Vec<_> = self
+            .colors
+            .iter()
+            .map(|(name, color)| (name, color.to_srgba().to_f32_array().map(f32::to_bits)))
+            .collect();
+        colors.sort_unstable();
+        FixedHasher.hash_one(colors)
+    }
+
     pub fn recolor(&self, composition: &Composition) -> Composition {
         let mut composition = composition.clone();
         'layers: for layer in composition.layers.iter_mut() {
@@ -151,3 +167,23 @@ fn recolor_brush(brush: &mut Brush, target_color: vello::peniko::Color) {
         },
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn theme_hash_depends_on_contents_only() {
+        let red_blue = Theme::new()
+            .add("a", Color::srgb(1.0, 0.0, 0.0))
+            .add("b", Color::srgb(0.0, 0.0, 1.0));
+        let blue_red = Theme::new()
+            .add("b", Color::srgb(0.0, 0.0, 1.0))
+            .add("a", Color::srgb(1.0, 0.0, 0.0));
+        let red_red = Theme::new()
+            .add("a", Color::srgb(1.0, 0.0, 0.0))
+            .add("b", Color::srgb(1.0, 0.0, 0.0));
+        assert_eq!(red_blue.content_hash(), blue_red.content_hash());
+        assert_ne!(red_blue.content_hash(), red_red.content_hash());
+    }
+}
@@ -6,44 +6,148 @@ pub(crate) use plugin::SceneIntegrationPlugin;
 
 use bevy::camera::visibility::{self, VisibilityClass};
 use bevy::prelude::*;
+use std::{
+    ops::{Deref, DerefMut},
+    sync::Arc,
+};
 
 /// A renderable scene in the world.
 ///
-/// A simple newtype component wrapper for [`vello::Scene`].
-#[derive(Component, Default, Clone, Deref, DerefMut)]
+/// A newtype component wrapper for [`vello::Scene`]. The scene is shared with the render world
+/// instead of copied every frame, so static scenes cost almost nothing to extract. Mutating a
+/// scene the render world still uses copies it first, except for [`VelloScene2d::reset`].
+#[derive(Component, Default, Clone)]
 #[require(Aabb, Transform, Visibility, VisibilityClass)]
 #[cfg_attr(feature = "picking", require(Pickable))]
 #[component(on_add = visibility::add_visibility_class::<VelloScene2d>)]
-pub struct VelloScene2d(Box<vello::Scene>);
+pub struct VelloScene2d(pub(crate) Arc<vello::Scene>);
 
 impl VelloScene2d {
     pub fn new() -> Self {
         Self::default()
     }
+
+    /// Clears the scene, without copying it if the render world still uses it.
+    pub fn reset(&mut self) {
+        reset_shared_scene(&mut self.0);
+    }
 }
 
 impl From<vello::Scene> for VelloScene2d {
     fn from(scene: vello::Scene) -> Self {
-        Self(Box::new(scene))
+        Self(Arc::new(scene))
+    }
+}
+
+impl Deref for VelloScene2d {
+    type Target = vello::Scene;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for VelloScene2d {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        Arc::make_mut(&mut self.0)
     }
 }
 
 /// A renderable scene that may be used in Bevy UI.
 ///
-/// A simple newtype component wrapper for [`vello::Scene`].
-#[derive(Component, Default, Clone, Deref, DerefMut)]
+/// A newtype component wrapper for [`vello::Scene`], shared with the render world like
+/// [`VelloScene2d`].
+#[derive(Component, Default, Clone)]
 #[require(Aabb, UiTransform, Visibility, VisibilityClass)]
 #[component(on_add = visibility::add_visibility_class::<UiVelloScene>)]
-pub struct UiVelloScene(Box<vello::Scene>);
+pub struct UiVelloScene(Arc<vello::Scene>);
 
 impl UiVelloScene {
     pub fn new() -> Self {
         Self::default()
     }
+
+    /// Clears the scene, without copying it if the render world still uses it.
+    pub fn reset(&mut self) {
+        reset_shared_scene(&mut self.0);
+    }
 }
 
 impl From<vello::Scene> for UiVelloScene {
     fn from(scene: vello::Scene) -> Self {
-        Self(Box::new(scene))
+        Self(Arc::new(scene))
+    }
+}
+
+impl Deref for UiVelloScene {
+    type Target = vello::Scene;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for UiVelloScene {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        Arc::make_mut(&mut self.0)
+    }
+}
+
+/// Clears a scene in place, or replaces it with an empty one if it is shared.
+fn reset_shared_scene(scene: &mut Arc<vello::Scene>) {
+    match Arc::get_mut(scene) {
+        // Keeps the allocations for re-encoding
+        Some(scene) => scene.reset(),
+        None => *scene = Arc::default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Fills a 10x10 square.
+    fn fill(scene: &mut VelloScene2d) {
+        scene.fill(
+            vello::peniko::Fill::NonZero,
+            vello::kurbo::Affine::IDENTITY,
+            vello::peniko::Color::WHITE,
+            None,
+            &vello::kurbo::Rect::new(0.0, 0.0, 10.0, 10.0),
+        );
+    }
+
+    #[test]
+    fn extracted_scenes_are_shared_not_copied() {
+        let mut scene = VelloScene2d::new();
+        fill(&mut scene);
+
+        // The render world's copy points at the same encoding.
+        let extracted = scene.clone();
+        assert!(std::ptr::eq::<vello::Scene>(&*scene, &*extracted));
+
+        // Changing the main world scene leaves the extracted one untouched.
+        scene.reset();
+        assert_eq!(scene.encoding().n_paths, 0);
+        assert_eq!(extracted.encoding().n_paths, 1);
+        fill(&mut scene);
+        fill(&mut scene);
+        assert_eq!(scene.encoding().n_paths, 2);
+        assert_eq!(extracted.encoding().n_paths, 1);
+    }
+
+    #[test]
+    fn resetting_unshared_scenes_keeps_their_allocations() {
+        let mut scene = VelloScene2d::new();
+        fill(&mut scene);
+        let encoding = Arc::as_ptr(&scene.0);
+        let capacity = scene.encoding().path_tags.capacity();
+
+        // Cleared in place, ready to re-encode into the same buffers.
+        scene.reset();
+        assert_eq!(Arc::as_ptr(&scene.0), encoding);
+        assert_eq!(scene.encoding().n_paths, 0);
+        assert!(scene.encoding().path_tags.is_empty());
+        assert_eq!(scene.encoding().path_tags.capacity(), capacity);
     }
 }
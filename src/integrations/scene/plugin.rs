@@ -10,7 +10,10 @@ pub struct SceneIntegrationPlugin;
 impl Plugin for SceneIntegrationPlugin {
     fn build(&self, app: &mut App) {
         #[cfg(feature = "picking")]
-        app.add_plugins(crate::picking::WorldPickingPlugin::<super::VelloScene2d>::default());
+        app.add_plugins((
+            crate::picking::WorldPickingPlugin::<super::VelloScene2d>::default(),
+            crate::picking::UiPickingPlugin::<super::UiVelloScene>::default(),
+        ));
 
         let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
             return;
@@ -24,7 +27,7 @@ impl Plugin for SceneIntegrationPlugin {
             )
             .add_systems(
                 Render,
-                render::prepare_scene_affines.in_set(RenderSystems::Prepare),
+                render::prepare_scene_affines.in_set(RenderSystems::Queue),
             );
     }
 }
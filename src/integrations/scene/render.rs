@@ -1,19 +1,25 @@
 use bevy::camera::visibility::RenderLayers;
 use bevy::prelude::*;
 use bevy::render::Extract;
-use bevy::render::camera::ExtractedCamera;
 use bevy::render::sync_world::TemporaryRenderEntity;
-use bevy::render::view::ExtractedView;
-use vello::kurbo::Affine;
 
 use crate::integrations::scene::{UiVelloScene, VelloScene2d};
-use crate::render::prepare::PreparedAffine;
-use crate::render::{VelloEntityCountData, VelloView};
+use crate::render::extract::{
+    ExtractedVelloView, UiClipShapes, VelloHierarchy, VelloUiClips, VelloViewLayers,
+};
+use crate::render::prepare::{PreparedAffines, ui_affine, ui_node_views, world_affine};
+use crate::render::{VelloBlendMode, VelloEntityCountData, VelloFrameChanges, WorldSortKey};
 
 #[derive(Component, Clone)]
 pub struct ExtractedVelloScene2d {
     pub scene: VelloScene2d,
     pub transform: GlobalTransform,
+    pub render_layers: RenderLayers,
+    pub blend_mode: VelloBlendMode,
+    pub group: Option<Entity>,
+    pub clip: Option<Entity>,
+    pub sort_key: WorldSortKey,
+    pub alpha: f32,
 }
 
 #[derive(Component, Clone)]
@@ -22,94 +28,155 @@ pub struct ExtractedUiVelloScene {
     pub ui_transform: UiGlobalTransform,
     pub ui_node: ComputedNode,
     pub ui_render_target: ComputedUiRenderTargetInfo,
+    pub target_camera: Option<Entity>,
+    pub render_layers: RenderLayers,
+    pub blend_mode: VelloBlendMode,
+    pub group: Option<Entity>,
+    pub alpha: f32,
     pub clip: Option<Rect>,
+    pub clip_shapes: UiClipShapes,
 }
 
 pub fn extract_world_scenes(
     mut commands: Commands,
-    query_views: Query<
-        (&ExtractedCamera, Option<&RenderLayers>),
-        (With<Camera2d>, With<VelloView>),
-    >,
+    views: VelloViewLayers,
+    hierarchy: VelloHierarchy,
     query_scenes: Extract<
         Query<
             (
+                Entity,
                 &VelloScene2d,
                 &GlobalTransform,
                 &ViewVisibility,
                 &InheritedVisibility,
                 Option<&RenderLayers>,
+                Option<&VelloBlendMode>,
             ),
             Without<Node>,
         >,
     >,
+    changed_items: Extract<
+        Query<
+            (),
+            (
+                With<VelloScene2d>,
+                Without<Node>,
+                Or<(
+                    Changed<VelloScene2d>,
+                    Changed<GlobalTransform>,
+                    Changed<ViewVisibility>,
+                    Changed<InheritedVisibility>,
+                    Changed<RenderLayers>,
+                    Changed<VelloBlendMode>,
+                )>,
+            ),
+        >,
+    >,
     mut frame_data: ResMut<VelloEntityCountData>,
+    mut frame_changes: ResMut<VelloFrameChanges>,
 ) {
     let mut n_scenes = 0;
 
-    // Sort cameras by rendering order
-    let mut views: Vec<_> = query_views.iter().collect();
-    views.sort_unstable_by_key(|(camera, _)| camera.order);
-
-    for (scene, transform, view_visibility, inherited_visibility, render_layers) in
-        query_scenes.iter()
+    for (
+        entity,
+        scene,
+        transform,
+        view_visibility,
+        inherited_visibility,
+        render_layers,
+        blend_mode,
+    ) in query_scenes.iter()
     {
-        // Skip if visibility conditions are not met
-        if !view_visibility.get() || !inherited_visibility.get() {
+        // Skip if visibility conditions are not met.
+        // View visibility is only checked for cameras, see below.
+        if !inherited_visibility.get() {
             continue;
         }
 
-        // Check if any camera renders this asset
+        // Check if any view renders this asset
         let asset_render_layers = render_layers.unwrap_or_default();
-        if views.iter().any(|(_, camera_layers)| {
-            asset_render_layers.intersects(camera_layers.unwrap_or_default())
-        }) {
+        // Counted even on frames a texture target drawing it skips, so those don't redraw views
+        if views.may_draw_world_item(view_visibility, asset_render_layers) {
+            n_scenes += 1;
+        }
+        if views.draws_world_item(view_visibility, asset_render_layers) {
             commands
                 .spawn(ExtractedVelloScene2d {
                     transform: *transform,
                     scene: scene.clone(),
+                    render_layers: asset_render_layers.clone(),
+                    blend_mode: blend_mode.copied().unwrap_or_default(),
+                    alpha: hierarchy.opacity(entity),
+                    group: hierarchy.group(entity),
+                    clip: hierarchy.clip(entity),
+                    sort_key: hierarchy.sort_key(entity, transform),
                 })
                 .insert(TemporaryRenderEntity);
-            n_scenes += 1;
         }
     }
 
+    // Redraw if any scene was added, removed or changed
+    if n_scenes != frame_data.n_world_scenes || !changed_items.is_empty() {
+        frame_changes.items_changed = true;
+    }
     frame_data.n_world_scenes = n_scenes;
 }
 
+#[allow(clippy::too_many_arguments, reason = "Many system parameters")]
 pub fn extract_ui_scenes(
     mut commands: Commands,
-    query_views: Query<
-        (&ExtractedCamera, Option<&RenderLayers>),
-        (With<Camera2d>, With<VelloView>),
-    >,
+    views: VelloViewLayers,
+    hierarchy: VelloHierarchy,
+    ui_clips: VelloUiClips,
     query_scenes: Extract<
         Query<(
+            Entity,
             &UiVelloScene,
             &ComputedNode,
             &ComputedUiRenderTargetInfo,
             &UiGlobalTransform,
             &InheritedVisibility,
             Option<&RenderLayers>,
+            Option<&ComputedUiTargetCamera>,
             Option<&CalculatedClip>,
+            Option<&VelloBlendMode>,
         )>,
     >,
+    changed_items: Extract<
+        Query<
+            (),
+            (
+                With<UiVelloScene>,
+                Or<(
+                    Changed<UiVelloScene>,
+                    Changed<UiGlobalTransform>,
+                    Changed<ComputedNode>,
+                    Changed<ComputedUiRenderTargetInfo>,
+                    Changed<ComputedUiTargetCamera>,
+                    Changed<CalculatedClip>,
+                    Changed<InheritedVisibility>,
+                    Changed<RenderLayers>,
+                    Changed<VelloBlendMode>,
+                )>,
+            ),
+        >,
+    >,
     mut frame_data: ResMut<VelloEntityCountData>,
+    mut frame_changes: ResMut<VelloFrameChanges>,
 ) {
     let mut n_scenes = 0;
 
-    // Sort cameras by rendering order
-    let mut views: Vec<_> = query_views.iter().collect();
-    views.sort_unstable_by_key(|(camera, _)| camera.order);
-
     for (
+        entity,
         scene,
         ui_node,
         ui_render_target,
         ui_transform,
         inherited_visibility,
         render_layers,
+        target_camera,
         calc_clip,
+        blend_mode,
     ) in query_scenes.iter()
     {
         // Skip if visibility conditions are not met.
@@ -119,168 +186,77 @@ pub fn extract_ui_scenes(
         }
         // Check if any camera renders this asset
         let asset_render_layers = render_layers.unwrap_or_default();
-        if views.iter().any(|(_, camera_layers)| {
-            asset_render_layers.intersects(camera_layers.unwrap_or_default())
-        }) {
+        if views.draws_ui_item(asset_render_layers) {
+            let (clip, clip_shapes) = ui_clips.get(entity, calc_clip);
             commands
                 .spawn(ExtractedUiVelloScene {
                     scene: scene.clone(),
                     ui_transform: *ui_transform,
                     ui_node: *ui_node,
                     ui_render_target: *ui_render_target,
-                    clip: calc_clip.map(|c| c.clip),
+                    target_camera: target_camera.and_then(ComputedUiTargetCamera::get),
+                    render_layers: asset_render_layers.clone(),
+                    blend_mode: blend_mode.copied().unwrap_or_default(),
+                    alpha: hierarchy.opacity(entity),
+                    group: hierarchy.group(entity),
+                    clip,
+                    clip_shapes,
                 })
                 .insert(TemporaryRenderEntity);
             n_scenes += 1;
         }
     }
 
+    // Redraw if any scene was added, removed or changed
+    if n_scenes != frame_data.n_ui_scenes || !changed_items.is_empty() {
+        frame_changes.items_changed = true;
+    }
     frame_data.n_ui_scenes = n_scenes;
 }
 
 pub fn prepare_scene_affines(
     mut commands: Commands,
-    views: Query<(&ExtractedCamera, &ExtractedView), (With<Camera2d>, With<VelloView>)>,
+    views: Query<(Entity, &ExtractedVelloView)>,
     render_entities: Query<(Entity, &ExtractedVelloScene2d)>,
     render_ui_entities: Query<(Entity, &ExtractedUiVelloScene)>,
 ) {
-    for (camera, view) in views.iter() {
-        // Render UI
-        for (entity, render_entity) in render_ui_entities.iter() {
-            let pixel_scale = render_entity.ui_render_target.scale_factor();
-            let pixel_scale_matrix = Mat4::from_scale(Vec3::new(pixel_scale, pixel_scale, 1.0));
-            let ui_transform = render_entity.ui_transform;
-            let ui_node = render_entity.ui_node;
+    let ui_views: Vec<_> = views
+        .iter()
+        .filter_map(|(view_entity, view)| {
+            view.camera
+                .map(|camera| (view_entity, camera, view.render_layers.clone()))
+        })
+        .collect();
 
-            // A transposed (flipped over its diagonal) PostScript matrix
-            // | a c e |
-            // | b d f |
-            // | 0 0 1 |
-            //
-            // Components
-            // | scale_x skew_x translate_x |
-            // | skew_y scale_y translate_y |
-            // | skew_z skew_z scale_z |
-            //
-            // rotate (z)
-            // | cos(θ) -sin(θ) translate_x |
-            // | sin(θ) cos(θ) translate_y |
-            // | skew_z skew_z scale_z |
-            //
-            // The order of operations is important, as it affects the final transformation matrix.
-            //
-            // Order of operations:
-            // 1. Scale
-            // 2. Rotate
-            // 3. Translate
-            let transform: [f64; 6] = {
-                // Convert UiGlobalTransform to Mat4
-                let mat2 = ui_transform.matrix2;
-                let translation = ui_transform.translation;
-                let model_matrix = Mat4::from_cols_array_2d(&[
-                    [mat2.x_axis.x, mat2.x_axis.y, 0.0, 0.0],
-                    [mat2.y_axis.x, mat2.y_axis.y, 0.0, 0.0],
-                    [0.0, 0.0, 1.0, 0.0],
-                    [translation.x, translation.y, 0.0, 1.0],
-                ]);
-
-                // Apply node centering transformation
-                // Get node center
-                let local_center_matrix = {
-                    let Vec2 {
-                        x: width,
-                        y: height,
-                    } = ui_node.size();
-                    Mat4::from_translation(Vec3::new(width / 2.0, height / 2.0, 0.0)).inverse()
-                };
+    // Render UI
+    for (entity, render_entity) in render_ui_entities.iter() {
+        let affine = ui_affine(
+            &render_entity.ui_transform,
+            &render_entity.ui_node,
+            render_entity.ui_render_target.scale_factor(),
+        );
+        commands.entity(entity).insert(PreparedAffines(
+            ui_node_views(
+                &ui_views,
+                render_entity.target_camera,
+                &render_entity.render_layers,
+            )
+            .map(|view_entity| (view_entity, affine))
+            .collect(),
+        ));
+    }
 
-                // Transform chain: ui_transform (already in px) → pixel_scale
-                let raw_transform = model_matrix * local_center_matrix * pixel_scale_matrix;
-                let transform = raw_transform.to_cols_array();
-                [
-                    transform[0] as f64,  // a // scale_x
-                    transform[1] as f64,  // b // skew_y
-                    transform[4] as f64,  // c // skew_x
-                    transform[5] as f64,  // d // scale_y
-                    transform[12] as f64, // e // translate_x
-                    transform[13] as f64, // f // translate_y
-                ]
-            };
+    // Render World
+    for (entity, render_entity) in render_entities.iter() {
+        let mut affines = PreparedAffines::default();
+        for (view_entity, view) in views.iter() {
+            if !render_entity.render_layers.intersects(&view.render_layers) {
+                continue;
+            }
 
-            commands
-                .entity(entity)
-                .insert(PreparedAffine(Affine::new(transform)));
+            affines.insert(view_entity, world_affine(view, &render_entity.transform));
         }
 
-        // Render World
-        for (entity, render_entity) in render_entities.iter() {
-            let world_transform = render_entity.transform;
-
-            // A transposed (flipped over its diagonal) PostScript matrix
-            // | a c e |
-            // | b d f |
-            // | 0 0 1 |
-            //
-            // Components
-            // | scale_x skew_x translate_x |
-            // | skew_y scale_y translate_y |
-            // | skew_z skew_z scale_z |
-            //
-            // rotate (z)
-            // | cos(θ) -sin(θ) translate_x |
-            // | sin(θ) cos(θ) translate_y |
-            // | skew_z skew_z scale_z |
-            //
-            // The order of operations is important, as it affects the final transformation matrix.
-            //
-            // Order of operations:
-            // 1. Scale
-            // 2. Rotate
-            // 3. Translate
-            let transform: [f64; 6] = {
-                let ndc_to_pixels_matrix = {
-                    let size_pixels: UVec2 = camera.physical_viewport_size.unwrap();
-                    let (pixels_x, pixels_y) = (size_pixels.x as f32, size_pixels.y as f32);
-                    Mat4::from_cols_array_2d(&[
-                        [pixels_x / 2.0, 0.0, 0.0, pixels_x / 2.0],
-                        [0.0, pixels_y / 2.0, 0.0, pixels_y / 2.0],
-                        [0.0, 0.0, 1.0, 0.0],
-                        [0.0, 0.0, 0.0, 1.0],
-                    ])
-                    .transpose()
-                };
-                let view_proj_matrix = {
-                    let mut view_mat = view.world_from_view.to_matrix();
-                    // Flip Y-axis to match Vello's y-down coordinate space
-                    view_mat.w_axis.y *= -1.0;
-                    let proj_mat = view.clip_from_view;
-                    proj_mat * view_mat.inverse()
-                };
-                let model_matrix = {
-                    let mut model_matrix = world_transform.to_matrix();
-                    // Flip Y-axis to match Vello's y-down coordinate space
-                    model_matrix.w_axis.y *= -1.0;
-                    model_matrix
-                };
-
-                // Transform chain: world → view → projection → NDC → pixels → pixel_scale
-                let raw_transform = ndc_to_pixels_matrix * view_proj_matrix * model_matrix;
-                let transform = raw_transform.to_cols_array();
-
-                // Negate skew_x and skew_y to match rotation of the Bevy's y-up world
-                [
-                    transform[0] as f64,  // a // scale_x
-                    -transform[1] as f64, // b // skew_y
-                    -transform[4] as f64, // c // skew_x
-                    transform[5] as f64,  // d // scale_y
-                    transform[12] as f64, // e // translate_x
-                    transform[13] as f64, // f // translate_y
-                ]
-            };
-
-            commands
-                .entity(entity)
-                .insert(PreparedAffine(Affine::new(transform)));
-        }
+        commands.entity(entity).insert(affines);
     }
 }
@@ -25,6 +25,22 @@ use bevy::{
 #[component(on_add = bevy::camera::visibility::add_visibility_class::<VelloSvg2d>)]
 pub struct VelloSvg2d(pub Handle<VelloSvg>);
 
+#[cfg(feature = "picking")]
+impl crate::picking::VelloPickingShape for VelloSvg2d {
+    type Data = ();
+    type Param = Res<'static, Assets<VelloSvg>>;
+
+    fn contains(
+        &self,
+        _data: &(),
+        svgs: &Res<Assets<VelloSvg>>,
+        point: vello::kurbo::Point,
+    ) -> bool {
+        svgs.get(&self.0)
+            .is_some_and(|svg| crate::picking::scene_contains(&svg.scene, point))
+    }
+}
+
 /// A renderable SVG that may be used in Bevy UI.
 ///
 /// ### Object fit
@@ -35,6 +51,35 @@ pub struct VelloSvg2d(pub Handle<VelloSvg>);
 #[component(on_add = bevy::camera::visibility::add_visibility_class::<UiVelloSvg>)]
 pub struct UiVelloSvg(pub Handle<VelloSvg>);
 
+#[cfg(feature = "picking")]
+impl crate::picking::VelloUiPickingShape for UiVelloSvg {
+    type Data = ();
+    type Param = Res<'static, Assets<VelloSvg>>;
+
+    fn content_position(
+        &self,
+        _data: &(),
+        svgs: &Res<Assets<VelloSvg>>,
+        node: &ComputedNode,
+        point: Vec2,
+    ) -> Option<Vec2> {
+        let svg = svgs.get(&self.0)?;
+        crate::picking::fit_content_position(node, Vec2::new(svg.width, svg.height), point)
+    }
+
+    fn contains(
+        &self,
+        _data: &(),
+        svgs: &Res<Assets<VelloSvg>>,
+        point: vello::kurbo::Point,
+    ) -> bool {
+        svgs.get(&self.0).is_some_and(|svg| {
+            crate::picking::in_content(Vec2::new(svg.width, svg.height), point)
+                && crate::picking::scene_contains(&svg.scene, point)
+        })
+    }
+}
+
 /// Describes how the asset is positioned relative to its [`Transform`]. It defaults to
 /// [`VelloSvgAnchor::Center`].
 ///
@@ -64,3 +109,20 @@ pub enum VelloSvgAnchor {
     /// Bounds start from the render position and advance down and to the left.
     TopRight,
 }
+
+impl VelloSvgAnchor {
+    /// The anchor point within content of the given size, y-down from its top-left corner.
+    pub(crate) fn content_position(self, width: f32, height: f32) -> Vec2 {
+        match self {
+            VelloSvgAnchor::TopLeft => Vec2::ZERO,
+            VelloSvgAnchor::Left => Vec2::new(0.0, height / 2.0),
+            VelloSvgAnchor::BottomLeft => Vec2::new(0.0, height),
+            VelloSvgAnchor::Top => Vec2::new(width / 2.0, 0.0),
+            VelloSvgAnchor::Center => Vec2::new(width / 2.0, height / 2.0),
+            VelloSvgAnchor::Bottom => Vec2::new(width / 2.0, height),
+            VelloSvgAnchor::TopRight => Vec2::new(width, 0.0),
+            VelloSvgAnchor::Right => Vec2::new(width, height / 2.0),
+            VelloSvgAnchor::BottomRight => Vec2::new(width, height),
+        }
+    }
+}
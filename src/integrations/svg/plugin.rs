@@ -14,7 +14,10 @@ pub struct SvgIntegrationPlugin;
 impl Plugin for SvgIntegrationPlugin {
     fn build(&self, app: &mut App) {
         #[cfg(feature = "picking")]
-        app.add_plugins(crate::picking::WorldPickingPlugin::<VelloSvg2d>::default());
+        app.add_plugins((
+            crate::picking::WorldPickingPlugin::<VelloSvg2d>::default(),
+            crate::picking::UiPickingPlugin::<UiVelloSvg>::default(),
+        ));
 
         app.init_asset_loader::<VelloSvgLoader>()
             .init_asset::<VelloSvg>()
@@ -49,7 +52,7 @@ impl Plugin for SvgIntegrationPlugin {
             )
             .add_systems(
                 Render,
-                (render::prepare_asset_affines).in_set(RenderSystems::Prepare),
+                (render::prepare_asset_affines).in_set(RenderSystems::Queue),
             );
     }
 }
@@ -1,16 +1,21 @@
 use bevy::{
     camera::visibility::RenderLayers,
     prelude::*,
-    render::{
-        Extract, camera::ExtractedCamera, sync_world::TemporaryRenderEntity, view::ExtractedView,
-    },
+    render::{Extract, sync_world::TemporaryRenderEntity},
 };
 use kurbo::Affine;
 
 use super::{VelloSvgAnchor, asset::VelloSvg};
 use crate::{
     prelude::*,
-    render::{VelloEntityCountData, prepare::PreparedAffine},
+    render::{
+        VelloEntityCountData, VelloFrameChanges, WorldSortKey,
+        encode::push_item_layer,
+        extract::{
+            ExtractedVelloView, UiClipShapes, VelloHierarchy, VelloUiClips, VelloViewLayers,
+        },
+        prepare::{PreparedAffines, ui_node_views},
+    },
 };
 
 #[derive(Component, Clone)]
@@ -18,6 +23,11 @@ pub struct ExtractedVelloSvg2d {
     pub asset: VelloSvg,
     pub asset_anchor: VelloSvgAnchor,
     pub transform: GlobalTransform,
+    pub render_layers: RenderLayers,
+    pub blend_mode: VelloBlendMode,
+    pub group: Option<Entity>,
+    pub clip: Option<Entity>,
+    pub sort_key: WorldSortKey,
     pub alpha: f32,
 }
 
@@ -27,48 +37,73 @@ pub struct ExtractedUiVelloSvg {
     pub ui_transform: UiGlobalTransform,
     pub alpha: f32,
     pub ui_node: ComputedNode,
+    pub target_camera: Option<Entity>,
+    pub render_layers: RenderLayers,
+    pub blend_mode: VelloBlendMode,
+    pub group: Option<Entity>,
     pub clip: Option<Rect>,
+    pub clip_shapes: UiClipShapes,
 }
 
+#[allow(clippy::too_many_arguments, reason = "Many system parameters")]
 pub fn extract_world_svg_assets(
     mut commands: Commands,
-    query_views: Query<
-        (&ExtractedCamera, Option<&RenderLayers>),
-        (With<Camera2d>, With<VelloView>),
-    >,
+    views: VelloViewLayers,
+    hierarchy: VelloHierarchy,
     query_vectors: Extract<
         Query<
             (
+                Entity,
                 &VelloSvg2d,
                 &VelloSvgAnchor,
                 &GlobalTransform,
                 Option<&RenderLayers>,
                 &ViewVisibility,
                 &InheritedVisibility,
+                Option<&VelloBlendMode>,
             ),
             Without<Node>,
         >,
     >,
+    changed_items: Extract<
+        Query<
+            (),
+            (
+                With<VelloSvg2d>,
+                Without<Node>,
+                Or<(
+                    Changed<VelloSvg2d>,
+                    Changed<VelloSvgAnchor>,
+                    Changed<GlobalTransform>,
+                    Changed<ViewVisibility>,
+                    Changed<InheritedVisibility>,
+                    Changed<RenderLayers>,
+                    Changed<VelloBlendMode>,
+                )>,
+            ),
+        >,
+    >,
     assets: Extract<Res<Assets<VelloSvg>>>,
+    mut asset_events: Extract<MessageReader<AssetEvent<VelloSvg>>>,
     mut frame_data: ResMut<VelloEntityCountData>,
+    mut frame_changes: ResMut<VelloFrameChanges>,
 ) {
     let mut n_svgs = 0;
 
-    // Sort cameras by rendering order
-    let mut views: Vec<_> = query_views.iter().collect();
-    views.sort_unstable_by_key(|(camera, _)| camera.order);
-
     for (
+        entity,
         asset_handle,
         asset_anchor,
         transform,
         render_layers,
         view_visibility,
         inherited_visibility,
+        blend_mode,
     ) in query_vectors.iter()
     {
-        // Skip if visibility conditions are not met
-        if !view_visibility.get() || !inherited_visibility.get() {
+        // Skip if visibility conditions are not met.
+        // View visibility is only checked for cameras, see below.
+        if !inherited_visibility.get() {
             continue;
         }
         // Skip if asset isn't loaded.
@@ -76,53 +111,93 @@ pub fn extract_world_svg_assets(
             continue;
         };
 
-        // Check if any camera renders this asset
+        // Check if any view renders this asset
         let asset_render_layers = render_layers.unwrap_or_default();
-        if views.iter().any(|(_, camera_layers)| {
-            asset_render_layers.intersects(camera_layers.unwrap_or_default())
-        }) {
+        // Counted even on frames a texture target drawing it skips, so those don't redraw views
+        if views.may_draw_world_item(view_visibility, asset_render_layers) {
+            n_svgs += 1;
+        }
+        if views.draws_world_item(view_visibility, asset_render_layers) {
             commands
                 .spawn(ExtractedVelloSvg2d {
                     asset: asset.to_owned(),
                     transform: *transform,
                     asset_anchor: *asset_anchor,
-                    alpha: asset.alpha,
+                    render_layers: asset_render_layers.clone(),
+                    blend_mode: blend_mode.copied().unwrap_or_default(),
+                    alpha: asset.alpha * hierarchy.opacity(entity),
+                    group: hierarchy.group(entity),
+                    clip: hierarchy.clip(entity),
+                    sort_key: hierarchy.sort_key(entity, transform),
                 })
                 .insert(TemporaryRenderEntity);
-            n_svgs += 1;
         }
     }
 
+    // Redraw if any SVG was added, removed or changed, or an asset changed
+    let assets_changed = asset_events.read().count() > 0;
+    if assets_changed || n_svgs != frame_data.n_world_svgs || !changed_items.is_empty() {
+        frame_changes.items_changed = true;
+    }
     frame_data.n_world_svgs = n_svgs;
 }
 
+#[allow(clippy::too_many_arguments, reason = "Many system parameters")]
 pub fn extract_ui_svg_assets(
     mut commands: Commands,
-    query_views: Query<
-        (&ExtractedCamera, Option<&RenderLayers>),
-        (With<Camera2d>, With<VelloView>),
-    >,
+    views: VelloViewLayers,
+    hierarchy: VelloHierarchy,
+    ui_clips: VelloUiClips,
     query_vectors: Extract<
         Query<(
+            Entity,
             &UiVelloSvg,
             &UiGlobalTransform,
             &ComputedNode,
             Option<&RenderLayers>,
             &InheritedVisibility,
+            Option<&ComputedUiTargetCamera>,
             Option<&CalculatedClip>,
+            Option<&VelloBlendMode>,
         )>,
     >,
+    changed_items: Extract<
+        Query<
+            (),
+            (
+                With<UiVelloSvg>,
+                Or<(
+                    Changed<UiVelloSvg>,
+                    Changed<UiGlobalTransform>,
+                    Changed<ComputedNode>,
+                    Changed<ComputedUiRenderTargetInfo>,
+                    Changed<ComputedUiTargetCamera>,
+                    Changed<CalculatedClip>,
+                    Changed<InheritedVisibility>,
+                    Changed<RenderLayers>,
+                    Changed<VelloBlendMode>,
+                )>,
+            ),
+        >,
+    >,
     assets: Extract<Res<Assets<VelloSvg>>>,
+    mut asset_events: Extract<MessageReader<AssetEvent<VelloSvg>>>,
     mut frame_data: ResMut<VelloEntityCountData>,
+    mut frame_changes: ResMut<VelloFrameChanges>,
 ) {
     let mut n_svgs = 0;
 
-    // Sort cameras by rendering order
-    let mut views: Vec<_> = query_views.iter().collect();
-    views.sort_unstable_by_key(|(camera, _)| camera.order);
-
-    for (asset_handle, ui_transform, ui_node, render_layers, inherited_visibility, calc_clip) in
-        query_vectors.iter()
+    for (
+        entity,
+        asset_handle,
+        ui_transform,
+        ui_node,
+        render_layers,
+        inherited_visibility,
+        target_camera,
+        calc_clip,
+        blend_mode,
+    ) in query_vectors.iter()
     {
         // Skip if visibility conditions are not met.
         // UI does not check view visibility, only inherited visibility.
@@ -136,103 +211,131 @@ pub fn extract_ui_svg_assets(
 
         // Check if any camera renders this asset
         let asset_render_layers = render_layers.unwrap_or_default();
-        if views.iter().any(|(_, camera_layers)| {
-            asset_render_layers.intersects(camera_layers.unwrap_or_default())
-        }) {
+        if views.draws_ui_item(asset_render_layers) {
+            let (clip, clip_shapes) = ui_clips.get(entity, calc_clip);
             commands
                 .spawn(ExtractedUiVelloSvg {
                     asset: asset.to_owned(),
                     ui_transform: *ui_transform,
                     ui_node: *ui_node,
-                    alpha: asset.alpha,
-                    clip: calc_clip.map(|c| c.clip),
+                    alpha: asset.alpha * hierarchy.opacity(entity),
+                    group: hierarchy.group(entity),
+                    target_camera: target_camera.and_then(ComputedUiTargetCamera::get),
+                    render_layers: asset_render_layers.clone(),
+                    blend_mode: blend_mode.copied().unwrap_or_default(),
+                    clip,
+                    clip_shapes,
                 })
                 .insert(TemporaryRenderEntity);
             n_svgs += 1;
         }
     }
 
+    // Redraw if any SVG was added, removed or changed, or an asset changed
+    let assets_changed = asset_events.read().count() > 0;
+    if assets_changed || n_svgs != frame_data.n_ui_svgs || !changed_items.is_empty() {
+        frame_changes.items_changed = true;
+    }
     frame_data.n_ui_svgs = n_svgs;
 }
 
 pub fn prepare_asset_affines(
     mut commands: Commands,
-    views: Query<(&ExtractedCamera, &ExtractedView), (With<Camera2d>, With<VelloView>)>,
+    views: Query<(Entity, &ExtractedVelloView)>,
     render_entities: Query<(Entity, &ExtractedVelloSvg2d)>,
     render_ui_entities: Query<(Entity, &ExtractedUiVelloSvg)>,
 ) {
-    for (camera, view) in views.iter() {
-        // Render UI
-        for (entity, render_entity) in render_ui_entities.iter() {
-            let ui_transform = render_entity.ui_transform;
+    let ui_views: Vec<_> = views
+        .iter()
+        .filter_map(|(view_entity, view)| {
+            view.camera
+                .map(|camera| (view_entity, camera, view.render_layers.clone()))
+        })
+        .collect();
 
-            // A transposed (flipped over its diagonal) PostScript matrix
-            // | a c e |
-            // | b d f |
-            // | 0 0 1 |
-            //
-            // Components
-            // | scale_x skew_x translate_x |
-            // | skew_y scale_y translate_y |
-            // | skew_z skew_z scale_z |
-            //
-            // rotate (z)
-            // | cos(θ) -sin(θ) translate_x |
-            // | sin(θ) cos(θ) translate_y |
-            // | skew_z skew_z scale_z |
-            //
-            // The order of operations is important, as it affects the final transformation matrix.
-            //
-            // Order of operations:
-            // 1. Scale
-            // 2. Rotate
-            // 3. Translate
-            let transform: [f64; 6] = {
-                // Convert UiGlobalTransform to Mat4
-                let mat2 = ui_transform.matrix2;
-                let translation = ui_transform.translation;
-                let model_matrix = Mat4::from_cols_array_2d(&[
-                    [mat2.x_axis.x, mat2.x_axis.y, 0.0, 0.0],
-                    [mat2.y_axis.x, mat2.y_axis.y, 0.0, 0.0],
-                    [0.0, 0.0, 1.0, 0.0],
-                    [translation.x, translation.y, 0.0, 1.0],
-                ]);
-                let local_center_matrix = Transform::from_translation(Vec3 {
-                    x: render_entity.asset.width / 2.0,
-                    y: render_entity.asset.height / 2.0,
-                    z: 0.0,
-                })
-                .to_matrix()
-                .inverse();
-                // Fill the bevy_ui Node with the asset size
-                let aspect_fill_matrix = {
-                    let asset_size =
-                        Vec2::new(render_entity.asset.width, render_entity.asset.height);
-                    let fill_scale = render_entity.ui_node.size() / asset_size;
-                    let scale_factor = fill_scale.x.min(fill_scale.y); // Maintain aspect ratio
-                    Mat4::from_scale(Vec3::new(scale_factor, scale_factor, 1.0))
-                };
+    // Render UI
+    for (entity, render_entity) in render_ui_entities.iter() {
+        let ui_transform = render_entity.ui_transform;
 
-                // Transform chain: ui_transform (in logical px) → aspect_fill → local_center
-                let raw_transform = model_matrix * aspect_fill_matrix * local_center_matrix;
-                let transform = raw_transform.to_cols_array();
-                [
-                    transform[0] as f64,  // a // scale_x
-                    transform[1] as f64,  // b // skew_y
-                    transform[4] as f64,  // c // skew_x
-                    transform[5] as f64,  // d // scale_y
-                    transform[12] as f64, // e // translate_x
-                    transform[13] as f64, // f // translate_y
-                ]
+        // A transposed (flipped over its diagonal) PostScript matrix
+        // | a c e |
+        // | b d f |
+        // | 0 0 1 |
+        //
+        // Components
+        // | scale_x skew_x translate_x |
+        // | skew_y scale_y translate_y |
+        // | skew_z skew_z scale_z |
+        //
+        // rotate (z)
+        // | cos(θ) -sin(θ) translate_x |
+        // | sin(θ) cos(θ) translate_y |
+        // | skew_z skew_z scale_z |
+        //
+        // The order of operations is important, as it affects the final transformation matrix.
+        //
+        // Order of operations:
+        // 1. Scale
+        // 2. Rotate
+        // 3. Translate
+        let transform: [f64; 6] = {
+            // Convert UiGlobalTransform to Mat4
+            let mat2 = ui_transform.matrix2;
+            let translation = ui_transform.translation;
+            let model_matrix = Mat4::from_cols_array_2d(&[
+                [mat2.x_axis.x, mat2.x_axis.y, 0.0, 0.0],
+                [mat2.y_axis.x, mat2.y_axis.y, 0.0, 0.0],
+                [0.0, 0.0, 1.0, 0.0],
+                [translation.x, translation.y, 0.0, 1.0],
+            ]);
+            let local_center_matrix = Transform::from_translation(Vec3 {
+                x: render_entity.asset.width / 2.0,
+                y: render_entity.asset.height / 2.0,
+                z: 0.0,
+            })
+            .to_matrix()
+            .inverse();
+            // Fill the bevy_ui Node with the asset size
+            let aspect_fill_matrix = {
+                let asset_size = Vec2::new(render_entity.asset.width, render_entity.asset.height);
+                let fill_scale = render_entity.ui_node.size() / asset_size;
+                let scale_factor = fill_scale.x.min(fill_scale.y); // Maintain aspect ratio
+                Mat4::from_scale(Vec3::new(scale_factor, scale_factor, 1.0))
             };
 
-            commands
-                .entity(entity)
-                .insert(PreparedAffine(Affine::new(transform)));
-        }
+            // Transform chain: ui_transform (in logical px) → aspect_fill → local_center
+            let raw_transform = model_matrix * aspect_fill_matrix * local_center_matrix;
+            let transform = raw_transform.to_cols_array();
+            [
+                transform[0] as f64,  // a // scale_x
+                transform[1] as f64,  // b // skew_y
+                transform[4] as f64,  // c // skew_x
+                transform[5] as f64,  // d // scale_y
+                transform[12] as f64, // e // translate_x
+                transform[13] as f64, // f // translate_y
+            ]
+        };
+
+        let affine = Affine::new(transform);
+        commands.entity(entity).insert(PreparedAffines(
+            ui_node_views(
+                &ui_views,
+                render_entity.target_camera,
+                &render_entity.render_layers,
+            )
+            .map(|view_entity| (view_entity, affine))
+            .collect(),
+        ));
+    }
+
+    // Render World
+    for (entity, render_entity) in render_entities.iter() {
+        let mut affines = PreparedAffines::default();
+        for (view_entity, view) in views.iter() {
+            if !render_entity.render_layers.intersects(&view.render_layers) {
+                continue;
+            }
 
-        // Render World
-        for (entity, render_entity) in render_entities.iter() {
             // A transposed (flipped over its diagonal) PostScript matrix
             // | a c e |
             // | b d f |
@@ -264,31 +367,10 @@ pub fn prepare_asset_affines(
                 } = world_transform;
 
                 // Calculate anchor offset in local space (Vello's top-left origin)
-                let anchor_local = match render_entity.asset_anchor {
-                    VelloSvgAnchor::TopLeft => Vec3::ZERO,
-                    VelloSvgAnchor::Left => Vec3::new(0.0, render_entity.asset.height / 2.0, 0.0),
-                    VelloSvgAnchor::BottomLeft => Vec3::new(0.0, render_entity.asset.height, 0.0),
-                    VelloSvgAnchor::Top => Vec3::new(render_entity.asset.width / 2.0, 0.0, 0.0),
-                    VelloSvgAnchor::Center => Vec3::new(
-                        render_entity.asset.width / 2.0,
-                        render_entity.asset.height / 2.0,
-                        0.0,
-                    ),
-                    VelloSvgAnchor::Bottom => Vec3::new(
-                        render_entity.asset.width / 2.0,
-                        render_entity.asset.height,
-                        0.0,
-                    ),
-                    VelloSvgAnchor::TopRight => Vec3::new(render_entity.asset.width, 0.0, 0.0),
-                    VelloSvgAnchor::Right => Vec3::new(
-                        render_entity.asset.width,
-                        render_entity.asset.height / 2.0,
-                        0.0,
-                    ),
-                    VelloSvgAnchor::BottomRight => {
-                        Vec3::new(render_entity.asset.width, render_entity.asset.height, 0.0)
-                    }
-                };
+                let anchor_local = render_entity
+                    .asset_anchor
+                    .content_position(render_entity.asset.width, render_entity.asset.height)
+                    .extend(0.0);
                 let mut anchor_matrix = Mat4::from_translation(-anchor_local);
                 // The anchor offset is in Vello's y-down coordinate space, but needs to be applied
                 // in the transform chain that operates in Bevy's y-up space. This y-flip compensates
@@ -296,7 +378,7 @@ pub fn prepare_asset_affines(
                 anchor_matrix.w_axis.y *= -1.0;
 
                 let ndc_to_pixels_matrix = {
-                    let size_pixels: UVec2 = camera.physical_viewport_size.unwrap();
+                    let size_pixels: UVec2 = view.size;
                     let (pixels_x, pixels_y) = (size_pixels.x as f32, size_pixels.y as f32);
                     Mat4::from_cols_array_2d(&[
                         [pixels_x / 2.0, 0.0, 0.0, pixels_x / 2.0],
@@ -341,9 +423,28 @@ pub fn prepare_asset_affines(
                 ]
             };
 
-            commands
-                .entity(entity)
-                .insert(PreparedAffine(Affine::new(transform)));
+            affines.insert(view_entity, Affine::new(transform));
         }
+
+        commands.entity(entity).insert(affines);
+    }
+}
+
+/// Appends an SVG to `scene`, faded by `alpha` and blended by `blend_mode`.
+pub(crate) fn append_svg(
+    scene: &mut vello::Scene,
+    asset: &VelloSvg,
+    alpha: f32,
+    blend_mode: VelloBlendMode,
+    affine: Affine,
+) {
+    if alpha <= 0.0 {
+        return;
+    }
+    let bounds = vello::kurbo::Rect::new(0.0, 0.0, asset.width as f64, asset.height as f64);
+    let layer = push_item_layer(scene, blend_mode, alpha, affine, Some(bounds));
+    scene.append(&asset.scene, Some(affine));
+    if layer {
+        scene.pop_layer();
     }
 }
@@ -29,6 +29,13 @@ pub struct VelloText2d {
     pub max_advance: Option<f32>,
 }
 
+/// Glyphs are not tested, so text is picked by its bounds.
+#[cfg(feature = "picking")]
+impl crate::picking::VelloPickingShape for VelloText2d {
+    type Data = ();
+    type Param = ();
+}
+
 #[derive(Component, Default, Clone)]
 #[require(ContentSize, VelloTextAnchor, UiTransform, Visibility, VisibilityClass)]
 #[component(on_add = bevy::camera::visibility::add_visibility_class::<UiVelloText>)]
@@ -39,6 +46,13 @@ pub struct UiVelloText {
     pub max_advance: Option<f32>,
 }
 
+/// Glyphs are not tested, so text is picked by its node.
+#[cfg(feature = "picking")]
+impl crate::picking::VelloUiPickingShape for UiVelloText {
+    type Data = ();
+    type Param = ();
+}
+
 #[derive(Clone)]
 pub struct VelloTextStyle {
     pub font: Handle<VelloFont>,
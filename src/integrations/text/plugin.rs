@@ -11,7 +11,10 @@ pub struct VelloTextIntegrationPlugin;
 impl Plugin for VelloTextIntegrationPlugin {
     fn build(&self, app: &mut App) {
         #[cfg(feature = "picking")]
-        app.add_plugins(crate::picking::WorldPickingPlugin::<super::VelloText2d>::default());
+        app.add_plugins((
+            crate::picking::WorldPickingPlugin::<super::VelloText2d>::default(),
+            crate::picking::UiPickingPlugin::<super::UiVelloText>::default(),
+        ));
 
         app.init_asset::<VelloFont>()
             .init_asset_loader::<VelloFontLoader>()
@@ -53,7 +56,7 @@ impl Plugin for VelloTextIntegrationPlugin {
             )
             .add_systems(
                 Render,
-                render::prepare_text_affines.in_set(RenderSystems::Prepare),
+                render::prepare_text_affines.in_set(RenderSystems::Queue),
             );
     }
 }
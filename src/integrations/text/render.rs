@@ -1,21 +1,29 @@
 use bevy::{
     camera::visibility::RenderLayers,
     prelude::*,
-    render::{
-        Extract, camera::ExtractedCamera, sync_world::TemporaryRenderEntity, view::ExtractedView,
-    },
+    render::{Extract, sync_world::TemporaryRenderEntity},
     ui::CalculatedClip,
 };
 use vello::kurbo::Affine;
 
 use super::{UiVelloText, VelloFont, VelloText2d, VelloTextAnchor};
-use crate::render::{VelloEntityCountData, VelloView, prepare::PreparedAffine};
+use crate::render::{
+    VelloBlendMode, VelloEntityCountData, VelloFrameChanges, WorldSortKey,
+    extract::{ExtractedVelloView, UiClipShapes, VelloHierarchy, VelloUiClips, VelloViewLayers},
+    prepare::{PreparedAffines, ui_node_views},
+};
 
 #[derive(Component, Clone)]
 pub struct ExtractedVelloText2d {
     pub text: VelloText2d,
     pub text_anchor: VelloTextAnchor,
     pub transform: GlobalTransform,
+    pub render_layers: RenderLayers,
+    pub blend_mode: VelloBlendMode,
+    pub group: Option<Entity>,
+    pub clip: Option<Entity>,
+    pub sort_key: WorldSortKey,
+    pub alpha: f32,
 }
 
 #[derive(Component, Clone)]
@@ -25,42 +33,74 @@ pub struct ExtractedUiVelloText {
     pub ui_transform: UiGlobalTransform,
     pub ui_node: ComputedNode,
     pub ui_render_target: ComputedUiRenderTargetInfo,
+    pub target_camera: Option<Entity>,
+    pub render_layers: RenderLayers,
+    pub blend_mode: VelloBlendMode,
+    pub group: Option<Entity>,
+    pub alpha: f32,
     pub clip: Option<Rect>,
+    pub clip_shapes: UiClipShapes,
 }
 
+#[allow(clippy::too_many_arguments, reason = "Many system parameters")]
 pub fn extract_world_text(
     mut commands: Commands,
-    query_views: Query<
-        (&ExtractedCamera, Option<&RenderLayers>),
-        (With<Camera2d>, With<VelloView>),
-    >,
+    views: VelloViewLayers,
+    hierarchy: VelloHierarchy,
     query_scenes: Extract<
         Query<
             (
+                Entity,
                 &VelloText2d,
                 &VelloTextAnchor,
                 &GlobalTransform,
                 &ViewVisibility,
                 &InheritedVisibility,
                 Option<&RenderLayers>,
+                Option<&VelloBlendMode>,
             ),
             Without<Node>,
         >,
     >,
+    changed_items: Extract<
+        Query<
+            (),
+            (
+                With<VelloText2d>,
+                Without<Node>,
+                Or<(
+                    Changed<VelloText2d>,
+                    Changed<VelloTextAnchor>,
+                    Changed<GlobalTransform>,
+                    Changed<ViewVisibility>,
+                    Changed<InheritedVisibility>,
+                    Changed<RenderLayers>,
+                    Changed<VelloBlendMode>,
+                )>,
+            ),
+        >,
+    >,
     fonts: Extract<Res<Assets<VelloFont>>>,
+    mut asset_events: Extract<MessageReader<AssetEvent<VelloFont>>>,
     mut frame_data: ResMut<VelloEntityCountData>,
+    mut frame_changes: ResMut<VelloFrameChanges>,
 ) {
     let mut n_texts = 0;
 
-    // Sort cameras by rendering order
-    let mut views: Vec<_> = query_views.iter().collect();
-    views.sort_unstable_by_key(|(camera, _)| camera.order);
-
-    for (text, text_anchor, transform, view_visibility, inherited_visibility, render_layers) in
-        query_scenes.iter()
+    for (
+        entity,
+        text,
+        text_anchor,
+        transform,
+        view_visibility,
+        inherited_visibility,
+        render_layers,
+        blend_mode,
+    ) in query_scenes.iter()
     {
-        // Skip if visibility conditions are not met
-        if !view_visibility.get() || !inherited_visibility.get() {
+        // Skip if visibility conditions are not met.
+        // View visibility is only checked for cameras, see below.
+        if !inherited_visibility.get() {
             continue;
         }
         // Skip if font isn't loaded.
@@ -68,33 +108,46 @@ pub fn extract_world_text(
             continue;
         };
 
-        // Check if any camera renders this asset
+        // Check if any view renders this asset
         let asset_render_layers = render_layers.unwrap_or_default();
-        if views.iter().any(|(_, camera_layers)| {
-            asset_render_layers.intersects(camera_layers.unwrap_or_default())
-        }) {
+        // Counted even on frames a texture target drawing it skips, so those don't redraw views
+        if views.may_draw_world_item(view_visibility, asset_render_layers) {
+            n_texts += 1;
+        }
+        if views.draws_world_item(view_visibility, asset_render_layers) {
             commands
                 .spawn(ExtractedVelloText2d {
                     text: text.clone(),
                     text_anchor: *text_anchor,
                     transform: *transform,
+                    render_layers: asset_render_layers.clone(),
+                    blend_mode: blend_mode.copied().unwrap_or_default(),
+                    alpha: hierarchy.opacity(entity),
+                    group: hierarchy.group(entity),
+                    clip: hierarchy.clip(entity),
+                    sort_key: hierarchy.sort_key(entity, transform),
                 })
                 .insert(TemporaryRenderEntity);
-            n_texts += 1;
         }
     }
 
+    // Redraw if any text was added, removed or changed, or an asset changed
+    let assets_changed = asset_events.read().count() > 0;
+    if assets_changed || n_texts != frame_data.n_world_texts || !changed_items.is_empty() {
+        frame_changes.items_changed = true;
+    }
     frame_data.n_world_texts = n_texts;
 }
 
+#[allow(clippy::too_many_arguments, reason = "Many system parameters")]
 pub fn extract_ui_text(
     mut commands: Commands,
-    query_views: Query<
-        (&ExtractedCamera, Option<&RenderLayers>),
-        (With<Camera2d>, With<VelloView>),
-    >,
+    views: VelloViewLayers,
+    hierarchy: VelloHierarchy,
+    ui_clips: VelloUiClips,
     query_scenes: Extract<
         Query<(
+            Entity,
             &UiVelloText,
             &VelloTextAnchor,
             &UiGlobalTransform,
@@ -102,19 +155,40 @@ pub fn extract_ui_text(
             Option<&RenderLayers>,
             &ComputedNode,
             &ComputedUiRenderTargetInfo,
+            Option<&ComputedUiTargetCamera>,
             Option<&CalculatedClip>,
+            Option<&VelloBlendMode>,
         )>,
     >,
+    changed_items: Extract<
+        Query<
+            (),
+            (
+                With<UiVelloText>,
+                Or<(
+                    Changed<UiVelloText>,
+                    Changed<VelloTextAnchor>,
+                    Changed<UiGlobalTransform>,
+                    Changed<ComputedNode>,
+                    Changed<ComputedUiRenderTargetInfo>,
+                    Changed<ComputedUiTargetCamera>,
+                    Changed<CalculatedClip>,
+                    Changed<InheritedVisibility>,
+                    Changed<RenderLayers>,
+                    Changed<VelloBlendMode>,
+                )>,
+            ),
+        >,
+    >,
     fonts: Extract<Res<Assets<VelloFont>>>,
+    mut asset_events: Extract<MessageReader<AssetEvent<VelloFont>>>,
     mut frame_data: ResMut<VelloEntityCountData>,
+    mut frame_changes: ResMut<VelloFrameChanges>,
 ) {
     let mut n_texts = 0;
 
-    // Sort cameras by rendering order
-    let mut views: Vec<_> = query_views.iter().collect();
-    views.sort_unstable_by_key(|(camera, _)| camera.order);
-
     for (
+        entity,
         text,
         text_anchor,
         ui_transform,
@@ -122,7 +196,9 @@ pub fn extract_ui_text(
         render_layers,
         ui_node,
         ui_render_target,
+        target_camera,
         calc_clip,
+        blend_mode,
     ) in query_scenes.iter()
     {
         // Skip if visibility conditions are not met.
@@ -138,9 +214,8 @@ pub fn extract_ui_text(
 
         // Check if any camera renders this asset
         let asset_render_layers = render_layers.unwrap_or_default();
-        if views.iter().any(|(_, camera_layers)| {
-            asset_render_layers.intersects(camera_layers.unwrap_or_default())
-        }) {
+        if views.draws_ui_item(asset_render_layers) {
+            let (clip, clip_shapes) = ui_clips.get(entity, calc_clip);
             commands
                 .spawn(ExtractedUiVelloText {
                     text: text.clone(),
@@ -148,81 +223,112 @@ pub fn extract_ui_text(
                     ui_transform: *ui_transform,
                     ui_node: *ui_node,
                     ui_render_target: *ui_render_target,
-                    clip: calc_clip.map(|c| c.clip),
+                    target_camera: target_camera.and_then(ComputedUiTargetCamera::get),
+                    render_layers: asset_render_layers.clone(),
+                    blend_mode: blend_mode.copied().unwrap_or_default(),
+                    alpha: hierarchy.opacity(entity),
+                    group: hierarchy.group(entity),
+                    clip,
+                    clip_shapes,
                 })
                 .insert(TemporaryRenderEntity);
             n_texts += 1;
         }
     }
 
+    // Redraw if any text was added, removed or changed, or an asset changed
+    let assets_changed = asset_events.read().count() > 0;
+    if assets_changed || n_texts != frame_data.n_ui_texts || !changed_items.is_empty() {
+        frame_changes.items_changed = true;
+    }
     frame_data.n_ui_texts = n_texts;
 }
 
 pub fn prepare_text_affines(
     mut commands: Commands,
-    views: Query<(&ExtractedCamera, &ExtractedView), (With<Camera2d>, With<VelloView>)>,
+    views: Query<(Entity, &ExtractedVelloView)>,
     render_entities: Query<(Entity, &ExtractedVelloText2d)>,
     render_ui_entities: Query<(Entity, &ExtractedUiVelloText)>,
 ) {
-    for (camera, view) in views.iter() {
-        // Render UI
-        for (entity, render_entity) in render_ui_entities.iter() {
-            let pixel_scale = render_entity.ui_render_target.scale_factor();
-            let pixel_scale_matrix = Mat4::from_scale(Vec3::new(pixel_scale, pixel_scale, 1.0));
-            let ui_transform = render_entity.ui_transform;
+    let ui_views: Vec<_> = views
+        .iter()
+        .filter_map(|(view_entity, view)| {
+            view.camera
+                .map(|camera| (view_entity, camera, view.render_layers.clone()))
+        })
+        .collect();
 
-            // A transposed (flipped over its diagonal) PostScript matrix
-            // | a c e |
-            // | b d f |
-            // | 0 0 1 |
-            //
-            // Components
-            // | scale_x skew_x translate_x |
-            // | skew_y scale_y translate_y |
-            // | skew_z skew_z scale_z |
-            //
-            // rotate (z)
-            // | cos(θ) -sin(θ) translate_x |
-            // | sin(θ) cos(θ) translate_y |
-            // | skew_z skew_z scale_z |
-            //
-            // The order of operations is important, as it affects the final transformation matrix.
-            //
-            // Order of operations:
-            // 1. Scale
-            // 2. Rotate
-            // 3. Translate
-            let transform: [f64; 6] = {
-                // Convert UiGlobalTransform to Mat4
-                let mat2 = ui_transform.matrix2;
-                let translation = ui_transform.translation;
-                let model_matrix = Mat4::from_cols_array_2d(&[
-                    [mat2.x_axis.x, mat2.x_axis.y, 0.0, 0.0],
-                    [mat2.y_axis.x, mat2.y_axis.y, 0.0, 0.0],
-                    [0.0, 0.0, 1.0, 0.0],
-                    [translation.x, translation.y, 0.0, 1.0],
-                ]);
+    // Render UI
+    for (entity, render_entity) in render_ui_entities.iter() {
+        let pixel_scale = render_entity.ui_render_target.scale_factor();
+        let pixel_scale_matrix = Mat4::from_scale(Vec3::new(pixel_scale, pixel_scale, 1.0));
+        let ui_transform = render_entity.ui_transform;
 
-                // Transform chain: ui_transform (already in px) → pixel_scale
-                let raw_transform = model_matrix * pixel_scale_matrix;
-                let transform = raw_transform.to_cols_array();
-                [
-                    transform[0] as f64,  // a // scale_x
-                    transform[1] as f64,  // b // skew_y
-                    transform[4] as f64,  // c // skew_x
-                    transform[5] as f64,  // d // scale_y
-                    transform[12] as f64, // e // translate_x
-                    transform[13] as f64, // f // translate_y
-                ]
-            };
+        // A transposed (flipped over its diagonal) PostScript matrix
+        // | a c e |
+        // | b d f |
+        // | 0 0 1 |
+        //
+        // Components
+        // | scale_x skew_x translate_x |
+        // | skew_y scale_y translate_y |
+        // | skew_z skew_z scale_z |
+        //
+        // rotate (z)
+        // | cos(θ) -sin(θ) translate_x |
+        // | sin(θ) cos(θ) translate_y |
+        // | skew_z skew_z scale_z |
+        //
+        // The order of operations is important, as it affects the final transformation matrix.
+        //
+        // Order of operations:
+        // 1. Scale
+        // 2. Rotate
+        // 3. Translate
+        let transform: [f64; 6] = {
+            // Convert UiGlobalTransform to Mat4
+            let mat2 = ui_transform.matrix2;
+            let translation = ui_transform.translation;
+            let model_matrix = Mat4::from_cols_array_2d(&[
+                [mat2.x_axis.x, mat2.x_axis.y, 0.0, 0.0],
+                [mat2.y_axis.x, mat2.y_axis.y, 0.0, 0.0],
+                [0.0, 0.0, 1.0, 0.0],
+                [translation.x, translation.y, 0.0, 1.0],
+            ]);
 
-            commands
-                .entity(entity)
-                .insert(PreparedAffine(Affine::new(transform)));
-        }
+            // Transform chain: ui_transform (already in px) → pixel_scale
+            let raw_transform = model_matrix * pixel_scale_matrix;
+            let transform = raw_transform.to_cols_array();
+            [
+                transform[0] as f64,  // a // scale_x
+                transform[1] as f64,  // b // skew_y
+                transform[4] as f64,  // c // skew_x
+                transform[5] as f64,  // d // scale_y
+                transform[12] as f64, // e // translate_x
+                transform[13] as f64, // f // translate_y
+            ]
+        };
+
+        let affine = Affine::new(transform);
+        commands.entity(entity).insert(PreparedAffines(
+            ui_node_views(
+                &ui_views,
+                render_entity.target_camera,
+                &render_entity.render_layers,
+            )
+            .map(|view_entity| (view_entity, affine))
+            .collect(),
+        ));
+    }
+
+    // Render World
+    for (entity, render_entity) in render_entities.iter() {
+        let mut affines = PreparedAffines::default();
+        for (view_entity, view) in views.iter() {
+            if !render_entity.render_layers.intersects(&view.render_layers) {
+                continue;
+            }
 
-        // Render World
-        for (entity, render_entity) in render_entities.iter() {
             let world_transform = render_entity.transform;
 
             // A transposed (flipped over its diagonal) PostScript matrix
@@ -248,7 +354,7 @@ pub fn prepare_text_affines(
             // 3. Translate
             let transform: [f64; 6] = {
                 let ndc_to_pixels_matrix = {
-                    let size_pixels: UVec2 = camera.physical_viewport_size.unwrap();
+                    let size_pixels: UVec2 = view.size;
                     let (pixels_x, pixels_y) = (size_pixels.x as f32, size_pixels.y as f32);
                     Mat4::from_cols_array_2d(&[
                         [pixels_x / 2.0, 0.0, 0.0, pixels_x / 2.0],
@@ -287,9 +393,9 @@ pub fn prepare_text_affines(
                 ]
             };
 
-            commands
-                .entity(entity)
-                .insert(PreparedAffine(Affine::new(transform)));
+            affines.insert(view_entity, Affine::new(transform));
         }
+
+        commands.entity(entity).insert(affines);
     }
 }
@@ -26,9 +26,17 @@ pub mod prelude {
 
     pub use crate::{
         integrations::scene::{UiVelloScene, VelloScene2d},
-        render::{VelloRenderSettings, VelloView},
+        render::{
+            VelloBlendMode, VelloCanvasFormat, VelloClip, VelloClipSource, VelloGroup,
+            VelloMaskMode, VelloOpacity, VelloRecoveryPolicy, VelloRenderError,
+            VelloRenderSettings, VelloRenderable, VelloRenderableAppExt, VelloRendererReady,
+            VelloTextureRefresh, VelloTextureTarget, VelloView, VelloViewSettings, VelloZIndex,
+        },
     };
 
+    #[cfg(feature = "picking")]
+    pub use crate::picking::{VelloHit, VelloPickingHits, VelloPickingMode, VelloPickingSurface};
+
     #[cfg(feature = "lottie")]
     pub use crate::integrations::lottie::{
         LottieExt, LottiePlayer, PlaybackDirection, PlaybackLoopBehavior, PlaybackOptions,
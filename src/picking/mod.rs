@@ -0,0 +1,659 @@
+//! Picking backends for world items and UI nodes, testing their bounds or, opted in with
+//! [`VelloPickingMode::Shape`], the geometry they paint.
+
+mod shape;
+mod surface;
+mod ui;
+
+use crate::{
+    integrations::scene::VelloScene2d,
+    render::{
+        VelloCanvasSettings, VelloRenderable, VelloTextureTarget, VelloView, VelloZIndex,
+        WorldSortKey,
+    },
+};
+use bevy::{
+    camera::{NormalizedRenderTarget, RenderTarget, primitives::Aabb, visibility::RenderLayers},
+    ecs::{
+        entity::EntityHashMap,
+        query::{QueryItem, ReadOnlyQueryData},
+        system::{ReadOnlySystemParam, StaticSystemParam, SystemParamItem},
+    },
+    picking::{
+        PickingSystems,
+        backend::{HitData, PointerHits},
+        pointer::{PointerId, PointerLocation},
+    },
+    platform::collections::HashMap,
+    prelude::*,
+    window::PrimaryWindow,
+};
+use std::marker::PhantomData;
+use tracing::debug;
+use vello::kurbo::{Affine, Point};
+
+pub use surface::VelloPickingSurface;
+
+pub(crate) use shape::scene_contains;
+pub(crate) use ui::UiPickingPlugin;
+#[cfg(any(feature = "svg", feature = "lottie", feature = "text"))]
+pub(crate) use ui::VelloUiPickingShape;
+#[cfg(any(feature = "svg", feature = "lottie"))]
+pub(crate) use ui::{fit_content_position, in_content};
+
+/// How the pointer is tested against a world item or UI node. Items without this component are
+/// picked by their bounds.
+#[derive(Component, Default, Debug, Clone, Copy, PartialEq, Eq, Reflect)]
+#[reflect(Component)]
+pub enum VelloPickingMode {
+    /// The item is hit anywhere inside its [`Aabb`], or its node.
+    #[default]
+    Bounds,
+    /// The item is hit only where it paints: inside its filled paths, by their fill rule, or on
+    /// its strokes. Lotties are tested at their current frame. Text is tested against its bounds.
+    /// In UI nodes, the letterboxing around SVGs and Lotties is not hit.
+    Shape,
+}
+
+/// A hit of a Vello picking backend.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VelloHit {
+    /// The hit position in the item's own coordinates, y-down: from the top-left corner of an
+    /// SVG, Lottie or text, or from the origin of a scene.
+    pub position: Vec2,
+}
+
+/// The hits of Vello items this frame, by pointer and entity, for pointer observers to look up.
+#[derive(Resource, Default, Debug)]
+pub struct VelloPickingHits(HashMap<(PointerId, Entity), VelloHit>);
+
+impl VelloPickingHits {
+    /// The hit of `entity` by `pointer`, if any.
+    pub fn get(&self, pointer: PointerId, entity: Entity) -> Option<&VelloHit> {
+        self.0.get(&(pointer, entity))
+    }
+}
+
+/// The geometry of a world item type, for precise picking.
+pub(crate) trait VelloPickingShape: Component {
+    /// Per-entity data needed to test the item, like a Lottie's playhead.
+    type Data: ReadOnlyQueryData;
+    /// Data shared by all items, like their assets.
+    type Param: ReadOnlySystemParam;
+
+    /// The origin of the item's coordinates in its local space, y-up. Defaults to the top-left
+    /// corner of its bounds.
+    fn content_origin(&self, aabb: &Aabb) -> Vec2 {
+        Vec2::new(aabb.min().x, aabb.max().y)
+    }
+
+    /// Returns whether the item paints at `point`, in its own coordinates. Defaults to anywhere
+    /// inside its bounds.
+    fn contains(
+        &self,
+        _data: &QueryItem<'_, '_, Self::Data>,
+        _param: &SystemParamItem<'_, '_, Self::Param>,
+        _point: Point,
+    ) -> bool {
+        true
+    }
+}
+
+impl VelloPickingShape for VelloScene2d {
+    type Data = ();
+    type Param = ();
+
+    fn content_origin(&self, _aabb: &Aabb) -> Vec2 {
+        Vec2::ZERO
+    }
+
+    fn contains(&self, _data: &(), _param: &(), point: Point) -> bool {
+        scene_contains(self, point)
+    }
+}
+
+impl<T: VelloRenderable + Component + Clone> VelloPickingShape for T {
+    type Data = Entity;
+    type Param = Res<'static, RenderableShapes<T>>;
+
+    fn content_origin(&self, _aabb: &Aabb) -> Vec2 {
+        Vec2::ZERO
+    }
+
+    fn contains(&self, entity: &Entity, shapes: &Res<RenderableShapes<T>>, point: Point) -> bool {
+        shapes.contains(*entity, point)
+    }
+}
+
+/// The scenes of the [`VelloRenderable`] items picked by their shape, encoded like the render
+/// world draws them and kept until the item changes.
+#[derive(Resource)]
+pub(crate) struct RenderableShapes<T> {
+    scenes: EntityHashMap<vello::Scene>,
+    _type: PhantomData<T>,
+}
+
+impl<T> Default for RenderableShapes<T> {
+    fn default() -> Self {
+        Self {
+            scenes: EntityHashMap::default(),
+            _type: PhantomData,
+        }
+    }
+}
+
+impl<T> RenderableShapes<T> {
+    /// Whether the item `entity` paints at `point`, in its own coordinates.
+    pub(crate) fn contains(&self, entity: Entity, point: Point) -> bool {
+        self.scenes
+            .get(&entity)
+            .is_some_and(|scene| scene_contains(scene, point))
+    }
+}
+
+/// Picks world and UI items of a [`VelloRenderable`] type by their shape.
+pub(crate) struct RenderableShapesPlugin<T>(PhantomData<T>);
+
+impl<T> Default for RenderableShapesPlugin<T> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<T: VelloRenderable + Component + Clone> Plugin for RenderableShapesPlugin<T> {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<RenderableShapes<T>>().add_systems(
+            PreUpdate,
+            update_renderable_shapes::<T>.before(PickingSystems::Backend),
+        );
+    }
+}
+
+/// Encodes the items picked by their shape when they change, and drops the scenes of items no
+/// longer picked by their shape.
+fn update_renderable_shapes<T: VelloRenderable + Component + Clone>(
+    mut shapes: ResMut<RenderableShapes<T>>,
+    items: Query<(Entity, Ref<T>, Ref<VelloPickingMode>)>,
+) {
+    shapes.scenes.retain(|entity, _| {
+        items
+            .get(*entity)
+            .is_ok_and(|(_, _, mode)| *mode == VelloPickingMode::Shape)
+    });
+    for (entity, item, mode) in &items {
+        if *mode != VelloPickingMode::Shape
+            || (!item.is_changed() && !mode.is_changed() && shapes.scenes.contains_key(&entity))
+        {
+            continue;
+        }
+        // Tested like the render world draws it
+        let mut item = item.extract();
+        item.prepare();
+        let mut scene = vello::Scene::new();
+        item.encode(&mut scene, Affine::IDENTITY);
+        shapes.scenes.insert(entity, scene);
+    }
+}
+
+pub struct WorldPickingPlugin<C: VelloPickingShape> {
+    _type: PhantomData<C>,
+}
+
+impl<C: VelloPickingShape> Default for WorldPickingPlugin<C> {
+    fn default() -> Self {
+        Self { _type: PhantomData }
+    }
+}
+
+impl<C: VelloPickingShape> Plugin for WorldPickingPlugin<C> {
+    fn build(&self, app: &mut App) {
+        debug!("Adding picking support for {}", std::any::type_name::<C>());
+        init_picking(app);
+        app.add_systems(
+            PreUpdate,
+            update_aabb_hits::<C>.in_set(PickingSystems::Backend),
+        );
+    }
+}
+
+/// Sets up what the picking backends share, once.
+fn init_picking(app: &mut App) {
+    if app.world().contains_resource::<VelloPickingHits>() {
+        return;
+    }
+    app.init_resource::<VelloPickingHits>()
+        .init_resource::<ui::VelloUiHitTests>()
+        .register_type::<VelloPickingMode>()
+        .register_type::<VelloPickingSurface>()
+        .add_systems(
+            PreUpdate,
+            (
+                surface::update_surface_pointers
+                    .after(PickingSystems::ProcessInput)
+                    .before(PickingSystems::Backend),
+                clear_picking_hits
+                    .after(PickingSystems::PostInput)
+                    .before(PickingSystems::Backend),
+                ui::update_ui_hits
+                    .after(PickingSystems::Backend)
+                    .before(PickingSystems::Hover),
+            ),
+        );
+}
+
+fn clear_picking_hits(
+    mut hits: ResMut<VelloPickingHits>,
+    mut ui_tests: ResMut<ui::VelloUiHitTests>,
+) {
+    hits.0.clear();
+    ui_tests.0.clear();
+}
+
+#[allow(clippy::too_many_arguments, reason = "Many system parameters")]
+fn update_aabb_hits<C: VelloPickingShape>(
+    primary_window: Query<Entity, With<PrimaryWindow>>,
+    pointers: Query<(&PointerId, &PointerLocation)>,
+    cameras: Query<
+        (
+            Entity,
+            &Camera,
+            &RenderTarget,
+            &GlobalTransform,
+            Option<&RenderLayers>,
+        ),
+        With<VelloView>,
+    >,
+    aabb_query: Query<(
+        Entity,
+        &C,
+        &Aabb,
+        &GlobalTransform,
+        &Pickable,
+        Option<&VelloZIndex>,
+        Option<&VelloPickingMode>,
+        Option<&RenderLayers>,
+        C::Data,
+    )>,
+    texture_targets: Query<(
+        Entity,
+        &VelloTextureTarget,
+        &GlobalTransform,
+        Option<&RenderLayers>,
+    )>,
+    images: Res<Assets<Image>>,
+    shape_param: StaticSystemParam<C::Param>,
+    canvas_settings: Res<VelloCanvasSettings>,
+    mut vello_hits: ResMut<VelloPickingHits>,
+    mut pointer_hits_writer: MessageWriter<PointerHits>,
+) {
+    // Hit entities drawn on top first, in reverse draw order
+    let mut sorted_entities: Vec<_> = aabb_query
+        .iter()
+        .map(|item| {
+            let (entity, _, _, transform, _, z_index, ..) = item;
+            let sort_key = WorldSortKey::new(entity, transform, z_index, canvas_settings.y_sort);
+            (sort_key, item)
+        })
+        .collect();
+    sorted_entities.sort_by(|(a, _), (b, _)| b.cmp(a));
+
+    let mut sorted_cameras: Vec<_> = cameras
+        .iter()
+        .filter(|(_, camera, ..)| camera.is_active)
+        .collect();
+    sorted_cameras.sort_by_key(|(entity, camera, ..)| (camera.order, *entity));
+
+    for (pointer_id, pointer_location) in &pointers {
+        let Some(location) = pointer_location.location() else {
+            continue;
+        };
+
+        // Every camera rendering the pointer's target under the pointer, in order
+        let mut views: Vec<_> = sorted_cameras
+            .iter()
+            .filter(|(_, camera, target, ..)| {
+                location.is_in_viewport(camera, target, &primary_window)
+            })
+            .filter_map(|(entity, camera, _, transform, layers)| {
+                // Convert pointer position to world space ray
+                let ray = camera
+                    .viewport_to_world(transform, location.position)
+                    .ok()?;
+                Some((*entity, ray, *transform, *layers, camera.order as f32))
+            })
+            .collect();
+        // Or the texture target the pointer of a `VelloPickingSurface` is on
+        if let NormalizedRenderTarget::Image(image) = &location.target {
+            views.extend(
+                texture_targets
+                    .iter()
+                    .filter(|(_, target, ..)| target.image.id() == image.handle.id())
+                    .filter_map(|(entity, target, transform, layers)| {
+                        let size = images.get(&target.image)?.size_f32();
+                        let ray = surface::texture_target_ray(transform, size, location.position)?;
+                        Some((entity, ray, transform, layers, 0.0))
+                    }),
+            );
+        }
+
+        for (cam_entity, ray, cam_transform, cam_layers, order) in views {
+            let cam_layers = cam_layers.unwrap_or_default();
+
+            let mut picks = Vec::new();
+            for (_, item) in &sorted_entities {
+                let (entity, shape, aabb, transform, pickable, _, mode, layers, data) = item;
+
+                // Only entities the camera renders
+                if !layers.unwrap_or_default().intersects(cam_layers) {
+                    continue;
+                }
+
+                // Transform ray to entity's local space
+                let world_to_local = transform.affine().inverse();
+                let local_ray_origin = world_to_local.transform_point3(ray.origin);
+                let local_ray_direction = world_to_local.transform_vector3(*ray.direction);
+
+                // Check ray-AABB intersection
+                if !intersects_aabb(local_ray_origin, local_ray_direction, aabb) {
+                    continue;
+                }
+                let hit_pos = ray.origin
+                    + *ray.direction
+                        * calculate_distance(ray.origin, *ray.direction, **transform, aabb);
+
+                // The hit in the item's own coordinates, y-down
+                let local = world_to_local.transform_point3(hit_pos).truncate();
+                let origin = shape.content_origin(aabb);
+                let position = Vec2::new(local.x - origin.x, origin.y - local.y);
+                if *mode == Some(&VelloPickingMode::Shape)
+                    && !shape.contains(
+                        data,
+                        &shape_param,
+                        Point::new(position.x as f64, position.y as f64),
+                    )
+                {
+                    continue;
+                }
+
+                let hit_data = HitData::new(
+                    cam_entity,
+                    calculate_depth(hit_pos, cam_transform),
+                    Some(hit_pos),
+                    None,
+                );
+
+                picks.push((*entity, hit_data));
+                vello_hits
+                    .0
+                    .entry((*pointer_id, *entity))
+                    .or_insert(VelloHit { position });
+                if pickable.should_block_lower {
+                    break;
+                }
+            }
+
+            if !picks.is_empty() {
+                pointer_hits_writer.write(PointerHits::new(*pointer_id, picks, order));
+            }
+        }
+    }
+}
+
+/// Checks if a ray intersects with an AABB using slab method
+fn intersects_aabb(ray_origin: Vec3, ray_direction: Vec3, aabb: &Aabb) -> bool {
+    let mut t_min = f32::NEG_INFINITY;
+    let mut t_max = f32::INFINITY;
+
+    for i in 0..3 {
+        let origin = ray_origin[i];
+        let dir = ray_direction[i];
+        let min = aabb.min()[i];
+        let max = aabb.max()[i];
+
+        if dir.abs() < f32::EPSILON {
+            // Ray is parallel to slab
+            if origin < min || origin > max {
+                return false;
+            }
+        } else {
+            let t1 = (min - origin) / dir;
+            let t2 = (max - origin) / dir;
+
+            let (t_near, t_far) = if t1 < t2 { (t1, t2) } else { (t2, t1) };
+
+            t_min = t_min.max(t_near);
+            t_max = t_max.min(t_far);
+
+            if t_min > t_max {
+                return false;
+            }
+        }
+    }
+
+    t_max >= 0.0
+}
+
+/// Calculates the distance along the ray to the AABB intersection point
+fn calculate_distance(
+    ray_origin: Vec3,
+    ray_direction: Vec3,
+    transform: GlobalTransform,
+    aabb: &Aabb,
+) -> f32 {
+    // Transform ray to entity's local space
+    let world_to_local = transform.affine().inverse();
+    let local_ray_origin = world_to_local.transform_point3(ray_origin);
+    let local_ray_direction = world_to_local.transform_vector3(ray_direction);
+
+    let mut t_min = f32::NEG_INFINITY;
+    let mut t_max = f32::INFINITY;
+
+    for i in 0..3 {
+        let origin = local_ray_origin[i];
+        let dir = local_ray_direction[i];
+        let min = aabb.min()[i];
+        let max = aabb.max()[i];
+
+        if dir.abs() > f32::EPSILON {
+            let t1 = (min - origin) / dir;
+            let t2 = (max - origin) / dir;
+
+            let (t_near, t_far) = if t1 < t2 { (t1, t2) } else { (t2, t1) };
+
+            t_min = t_min.max(t_near);
+            t_max = t_max.min(t_far);
+        }
+    }
+
+    // Return the nearest intersection distance (use t_min if positive, else t_max)
+    if t_min >= 0.0 { t_min } else { t_max }
+}
+
+/// Calculates the depth of a hit position in camera space
+fn calculate_depth(hit_pos: Vec3, cam_transform: &GlobalTransform) -> f32 {
+    let view_matrix = cam_transform.compute_transform().to_matrix().inverse();
+    let hit_in_view = view_matrix.transform_point3(hit_pos);
+    -hit_in_view.z
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::{
+        camera::{CameraProjection, RenderTargetInfo, Viewport},
+        ecs::system::RunSystemOnce,
+        picking::pointer::Location,
+    };
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// The size of the primary window, in pixels.
+    const WINDOW_SIZE: UVec2 = UVec2::new(200, 100);
+
+    /// A world with the primary window and the mouse at `cursor` on it.
+    fn setup(cursor: Vec2) -> World {
+        let mut world = World::new();
+        world.init_resource::<VelloPickingHits>();
+        world.init_resource::<VelloCanvasSettings>();
+        world.init_resource::<Assets<Image>>();
+        world.init_resource::<Messages<PointerHits>>();
+        let window = world.spawn((Window::default(), PrimaryWindow)).id();
+        let target = RenderTarget::default().normalize(Some(window)).unwrap();
+        world.spawn((
+            PointerId::Mouse,
+            PointerLocation::new(Location {
+                target,
+                position: cursor,
+            }),
+        ));
+        world
+    }
+
+    /// A 2D view of the window at the origin, with the values Bevy's camera systems would compute.
+    fn view(order: isize, viewport: Option<Viewport>) -> impl Bundle {
+        let size = viewport
+            .as_ref()
+            .map_or(WINDOW_SIZE, |viewport| viewport.physical_size)
+            .as_vec2();
+        let mut projection = OrthographicProjection::default_2d();
+        projection.update(size.x, size.y);
+        let mut camera = Camera {
+            order,
+            viewport,
+            ..default()
+        };
+        camera.computed.clip_from_view = projection.get_clip_from_view();
+        camera.computed.target_info = Some(RenderTargetInfo {
+            physical_size: WINDOW_SIZE,
+            scale_factor: 1.0,
+        });
+        (VelloView, camera, GlobalTransform::IDENTITY)
+    }
+
+    /// A 20x20 scene at the origin, picked by its bounds.
+    fn item() -> impl Bundle {
+        (
+            VelloScene2d::default(),
+            Aabb::from_min_max(Vec3::new(-10.0, -10.0, 0.0), Vec3::new(10.0, 10.0, 0.0)),
+            GlobalTransform::IDENTITY,
+        )
+    }
+
+    /// Runs the world backend and returns the hits it reported, by camera.
+    fn pick(world: &mut World) -> Vec<(Entity, f32, Vec<Entity>)> {
+        world
+            .run_system_once(update_aabb_hits::<VelloScene2d>)
+            .unwrap();
+        world
+            .resource::<Messages<PointerHits>>()
+            .iter_current_update_messages()
+            .map(|hits| {
+                let camera = hits.picks[0].1.camera;
+                let picks = hits.picks.iter().map(|(entity, _)| *entity).collect();
+                (camera, hits.order, picks)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn overlapping_cameras_report_their_own_hits() {
+        let mut world = setup(Vec2::new(100.0, 50.0));
+        let back = world.spawn(view(0, None)).id();
+        let front = world.spawn(view(1, None)).id();
+        let item = world.spawn(item()).id();
+
+        assert_eq!(
+            pick(&mut world),
+            [(back, 0.0, vec![item]), (front, 1.0, vec![item])]
+        );
+        // The center of the window is the origin of the item
+        let hit = world
+            .resource::<VelloPickingHits>()
+            .get(PointerId::Mouse, item);
+        assert_eq!(hit.map(|hit| hit.position), Some(Vec2::ZERO));
+    }
+
+    #[test]
+    fn pointers_outside_a_viewport_do_not_hit() {
+        // The left half of the window, centered on the origin
+        let viewport = Viewport {
+            physical_size: UVec2::new(100, 100),
+            ..default()
+        };
+        for (cursor, hit) in [
+            (Vec2::new(50.0, 50.0), true),
+            (Vec2::new(150.0, 50.0), false),
+        ] {
+            let mut world = setup(cursor);
+            let camera = world.spawn(view(0, Some(viewport.clone()))).id();
+            let item = world.spawn(item()).id();
+
+            let expected = hit.then(|| (camera, 0.0, vec![item]));
+            assert_eq!(pick(&mut world), Vec::from_iter(expected));
+        }
+    }
+
+    #[test]
+    fn cameras_only_hit_items_on_their_layers() {
+        for (layer, hit) in [(0, false), (1, true)] {
+            let mut world = setup(Vec2::new(100.0, 50.0));
+            let camera = world.spawn((view(0, None), RenderLayers::layer(1))).id();
+            let item = world.spawn((item(), RenderLayers::layer(layer))).id();
+
+            let expected = hit.then(|| (camera, 0.0, vec![item]));
+            assert_eq!(pick(&mut world), Vec::from_iter(expected));
+        }
+    }
+
+    /// How many times [`Counted`] squares were encoded.
+    static ENCODES: AtomicUsize = AtomicUsize::new(0);
+
+    /// A 10x10 square counting its encodes.
+    #[derive(Component, Clone)]
+    struct Counted;
+
+    impl VelloRenderable for Counted {
+        fn encode(&self, scene: &mut vello::Scene, affine: Affine) {
+            ENCODES.fetch_add(1, Ordering::Relaxed);
+            scene.fill(
+                vello::peniko::Fill::NonZero,
+                affine,
+                vello::peniko::Color::WHITE,
+                None,
+                &vello::kurbo::Rect::new(0.0, 0.0, 10.0, 10.0),
+            );
+        }
+    }
+
+    #[test]
+    fn renderable_shapes_are_encoded_once_until_changed() {
+        let mut world = World::new();
+        world.init_resource::<RenderableShapes<Counted>>();
+        let mut update = Schedule::default();
+        update.add_systems(update_renderable_shapes::<Counted>);
+        let item = world.spawn((Counted, VelloPickingMode::Shape)).id();
+        let bounds = world.spawn((Counted, VelloPickingMode::Bounds)).id();
+        let contains = |world: &World, entity, x| {
+            world
+                .resource::<RenderableShapes<Counted>>()
+                .contains(entity, Point::new(x, 5.0))
+        };
+
+        // Items picked by their bounds are never encoded
+        for _ in 0..3 {
+            update.run(&mut world);
+        }
+        assert_eq!(ENCODES.load(Ordering::Relaxed), 1);
+        assert!(contains(&world, item, 5.0));
+        assert!(!contains(&world, item, 15.0));
+        assert!(!contains(&world, bounds, 5.0));
+
+        world.get_mut::<Counted>(item).unwrap().set_changed();
+        update.run(&mut world);
+        assert_eq!(ENCODES.load(Ordering::Relaxed), 2);
+
+        world.entity_mut(item).insert(VelloPickingMode::Bounds);
+        update.run(&mut world);
+        assert!(!contains(&world, item, 5.0));
+        assert_eq!(ENCODES.load(Ordering::Relaxed), 2);
+    }
+}
@@ -0,0 +1,370 @@
+//! Hit tests against the geometry a [`Scene`] paints.
+//!
+//! The scene's encoding is replayed on the CPU: every painted path is tested with its fill rule or
+//! stroke width, and clipped by the layers it is drawn in. Glyph runs are resolved at render time,
+//! so they are not tested.
+
+use vello::{
+    Scene,
+    kurbo::{Affine, BezPath, ParamCurveNearest, Point, Shape},
+};
+use vello_encoding::{DrawTag, PathSegmentType, PathTag, Style};
+
+/// The distance a point on a stroke may be off, in the stroke's own units.
+const STROKE_ACCURACY: f64 = 1e-3;
+
+/// The style of the path being replayed.
+#[derive(Clone, Copy)]
+enum PathStyle {
+    Fill { even_odd: bool },
+    Stroke { half_width: f64 },
+}
+
+impl PathStyle {
+    fn contains(self, path: &BezPath, point: Point) -> bool {
+        match self {
+            PathStyle::Fill { even_odd: false } => path.winding(point) != 0,
+            PathStyle::Fill { even_odd: true } => path.winding(point) % 2 != 0,
+            PathStyle::Stroke { half_width } => path.segments().any(|segment| {
+                segment.nearest(point, STROKE_ACCURACY).distance_sq <= half_width * half_width
+            }),
+        }
+    }
+}
+
+/// Returns whether `point`, in the scene's coordinates, is on anything the scene paints.
+///
+/// Transparent solid colors and layers faded out entirely don't count as painted.
+pub(crate) fn scene_contains(scene: &Scene, point: Point) -> bool {
+    let encoding = scene.encoding();
+    // Glyph runs have a draw object but no path until they are resolved
+    let glyph_draws: Vec<usize> = encoding
+        .resources
+        .glyph_runs
+        .iter()
+        .map(|run| run.stream_offsets.draw_tags)
+        .collect();
+
+    let mut transforms = encoding.transforms.iter();
+    let mut styles = encoding.styles.iter();
+    let mut transform = Affine::IDENTITY;
+    let mut style = PathStyle::Fill { even_odd: false };
+
+    let mut path = BezPath::new();
+    let mut path_transform = None;
+    let mut path_style = style;
+    let mut subpath_start = true;
+    let mut data_ix = 0;
+
+    let mut draw_ix = 0;
+    let mut draw_data_ix = 0;
+    // Whether the point is inside each open clip layer
+    let mut clips: Vec<bool> = Vec::new();
+
+    for &tag in &encoding.path_tags {
+        match tag {
+            PathTag::TRANSFORM => {
+                if let Some(next) = transforms.next() {
+                    let [a, b, c, d] = next.matrix;
+                    let [e, f] = next.translation;
+                    transform = Affine::new([a, b, c, d, e, f].map(f64::from));
+                }
+            }
+            PathTag::STYLE => {
+                if let Some(next) = styles.next() {
+                    let flags = next.flags_and_miter_limit;
+                    style = if flags & Style::FLAGS_STYLE_BIT != 0 {
+                        PathStyle::Stroke {
+                            half_width: f64::from(next.line_width) / 2.0,
+                        }
+                    } else {
+                        PathStyle::Fill {
+                            even_odd: flags & Style::FLAGS_FILL_BIT != 0,
+                        }
+                    };
+                }
+            }
+            PathTag::PATH => {
+                while glyph_draws.contains(&draw_ix) {
+                    draw_data_ix += draw_data_size(encoding.draw_tags[draw_ix]);
+                    draw_ix += 1;
+                }
+                let Some(&draw_tag) = encoding.draw_tags.get(draw_ix) else {
+                    break;
+                };
+                let draw_data = &encoding.draw_data[draw_data_ix..];
+                draw_ix += 1;
+                draw_data_ix += draw_data_size(draw_tag);
+
+                let inside = path_transform
+                    .filter(|transform: &Affine| transform.determinant() != 0.0)
+                    .is_some_and(|transform| {
+                        path_style.contains(&path, transform.inverse() * point)
+                    });
+                match draw_tag {
+                    DrawTag::BEGIN_CLIP => {
+                        // The alpha of a `DrawBeginClip`, after its blend mode
+                        let alpha = f32::from_bits(draw_data[1]);
+                        clips.push(inside && alpha > 0.0);
+                    }
+                    DrawTag::END_CLIP => {
+                        clips.pop();
+                    }
+                    _ => {
+                        // The alpha of a `DrawColor`, premultiplied RGBA8 with alpha in the top byte
+                        let painted = draw_tag != DrawTag::COLOR || draw_data[0] >> 24 != 0;
+                        if painted && inside && clips.iter().all(|inside| *inside) {
+                            return true;
+                        }
+                    }
+                }
+
+                path.truncate(0);
+                path_transform = None;
+                subpath_start = true;
+            }
+            _ if tag.is_path_segment() => {
+                let f32_points = tag.is_f32();
+                let mut next_point = || {
+                    let point = read_point(&encoding.path_data, data_ix, f32_points);
+                    data_ix += if f32_points { 2 } else { 1 };
+                    point
+                };
+                if subpath_start {
+                    path.move_to(next_point());
+                    subpath_start = false;
+                }
+                if path_transform.is_none() {
+                    path_transform = Some(transform);
+                    path_style = style;
+                }
+                let subpath_end = tag.is_subpath_end();
+                match tag.path_segment_type() {
+                    PathSegmentType::LINE_TO => {
+                        let p1 = next_point();
+                        // Strokes end each subpath with a marker holding its cap, not geometry
+                        if !(subpath_end && matches!(path_style, PathStyle::Stroke { .. })) {
+                            path.line_to(p1);
+                        }
+                    }
+                    PathSegmentType::QUAD_TO => {
+                        let (p1, p2) = (next_point(), next_point());
+                        if !(subpath_end && matches!(path_style, PathStyle::Stroke { .. })) {
+                            path.quad_to(p1, p2);
+                        }
+                    }
+                    _ => {
+                        let (p1, p2, p3) = (next_point(), next_point(), next_point());
+                        path.curve_to(p1, p2, p3);
+                    }
+                }
+                if subpath_end {
+                    if matches!(path_style, PathStyle::Fill { .. }) {
+                        path.close_path();
+                    }
+                    subpath_start = true;
+                }
+            }
+            _ => {}
+        }
+    }
+    false
+}
+
+/// The size of a draw object's data, in words, which Vello packs into bits 2 to 4 of its tag.
+fn draw_data_size(draw_tag: DrawTag) -> usize {
+    ((draw_tag.0 >> 2) & 0x7) as usize
+}
+
+fn read_point(path_data: &[u32], ix: usize, f32_point: bool) -> Point {
+    if f32_point {
+        Point::new(
+            f64::from(f32::from_bits(path_data[ix])),
+            f64::from(f32::from_bits(path_data[ix + 1])),
+        )
+    } else {
+        let packed = path_data[ix];
+        Point::new(f64::from(packed as i16), f64::from((packed >> 16) as i16))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use vello::{
+        kurbo::{Circle, Rect, Stroke},
+        peniko::{Color, Fill},
+    };
+
+    #[test]
+    fn fills_respect_their_fill_rule() {
+        let mut scene = Scene::new();
+        // Two nested squares wound the same way
+        let mut path = BezPath::from_vec(
+            Rect::new(0.0, 0.0, 100.0, 100.0)
+                .path_elements(0.1)
+                .collect(),
+        );
+        path.extend(Rect::new(25.0, 25.0, 75.0, 75.0).path_elements(0.1));
+        scene.fill(Fill::EvenOdd, Affine::IDENTITY, Color::WHITE, None, &path);
+        assert!(scene_contains(&scene, Point::new(10.0, 10.0)));
+        assert!(!scene_contains(&scene, Point::new(50.0, 50.0)));
+
+        let mut scene = Scene::new();
+        scene.fill(Fill::NonZero, Affine::IDENTITY, Color::WHITE, None, &path);
+        assert!(scene_contains(&scene, Point::new(50.0, 50.0)));
+        assert!(!scene_contains(&scene, Point::new(150.0, 50.0)));
+    }
+
+    #[test]
+    fn circles_miss_their_corners() {
+        let mut scene = Scene::new();
+        let transform = Affine::translate((100.0, 100.0)) * Affine::scale(2.0);
+        let circle = Circle::new((0.0, 0.0), 50.0);
+        scene.fill(Fill::NonZero, transform, Color::WHITE, None, &circle);
+        assert!(scene_contains(&scene, Point::new(100.0, 100.0)));
+        assert!(scene_contains(&scene, Point::new(190.0, 100.0)));
+        // Inside the circle's bounds, outside the circle
+        assert!(!scene_contains(&scene, Point::new(10.0, 10.0)));
+    }
+
+    #[test]
+    fn strokes_hit_their_outline_only() {
+        let mut scene = Scene::new();
+        let circle = Circle::new((0.0, 0.0), 50.0);
+        scene.stroke(
+            &Stroke::new(10.0),
+            Affine::IDENTITY,
+            Color::WHITE,
+            None,
+            &circle,
+        );
+        assert!(scene_contains(&scene, Point::new(54.0, 0.0)));
+        assert!(scene_contains(&scene, Point::new(0.0, -46.0)));
+        assert!(!scene_contains(&scene, Point::new(0.0, 0.0)));
+        assert!(!scene_contains(&scene, Point::new(56.0, 0.0)));
+    }
+
+    #[test]
+    fn even_odd_fills_miss_the_center_of_a_star() {
+        // A pentagram, whose center is wound twice
+        let mut star = BezPath::new();
+        for i in 0..5 {
+            let angle = f64::from(i) * 4.0 * std::f64::consts::PI / 5.0;
+            let point = Point::new(angle.sin() * 50.0, -angle.cos() * 50.0);
+            if i == 0 {
+                star.move_to(point);
+            } else {
+                star.line_to(point);
+            }
+        }
+        star.close_path();
+        let transform = Affine::translate((100.0, 100.0)) * Affine::scale_non_uniform(2.0, 1.0);
+        let tip = Point::new(100.0, 60.0);
+
+        let mut scene = Scene::new();
+        scene.fill(Fill::EvenOdd, transform, Color::WHITE, None, &star);
+        assert!(!scene_contains(&scene, Point::new(100.0, 100.0)));
+        assert!(scene_contains(&scene, tip));
+
+        let mut scene = Scene::new();
+        scene.fill(Fill::NonZero, transform, Color::WHITE, None, &star);
+        assert!(scene_contains(&scene, Point::new(100.0, 100.0)));
+        assert!(scene_contains(&scene, tip));
+    }
+
+    #[test]
+    fn open_strokes_are_not_closed() {
+        let mut path = BezPath::new();
+        path.move_to((0.0, 0.0));
+        path.line_to((100.0, 0.0));
+        path.quad_to((100.0, 100.0), (0.0, 100.0));
+        let mut scene = Scene::new();
+        scene.stroke(
+            &Stroke::new(4.0),
+            Affine::translate((10.0, 10.0)),
+            Color::WHITE,
+            None,
+            &path,
+        );
+        assert!(scene_contains(&scene, Point::new(60.0, 11.0)));
+        // The end of the curve
+        assert!(scene_contains(&scene, Point::new(12.0, 109.0)));
+        // Where a closing segment would be, and inside the path
+        assert!(!scene_contains(&scene, Point::new(10.0, 60.0)));
+        assert!(!scene_contains(&scene, Point::new(50.0, 50.0)));
+    }
+
+    #[test]
+    fn nested_clips_intersect() {
+        let mut scene = Scene::new();
+        scene.push_clip_layer(
+            Fill::NonZero,
+            Affine::IDENTITY,
+            &Rect::new(0.0, 0.0, 60.0, 100.0),
+        );
+        scene.push_clip_layer(
+            Fill::NonZero,
+            Affine::translate((50.0, 50.0)),
+            &Circle::new((0.0, 0.0), 40.0),
+        );
+        scene.fill(
+            Fill::NonZero,
+            Affine::IDENTITY,
+            Color::WHITE,
+            None,
+            &Rect::new(0.0, 0.0, 100.0, 100.0),
+        );
+        scene.pop_layer();
+        // Clipped by the outer clip only
+        scene.fill(
+            Fill::NonZero,
+            Affine::IDENTITY,
+            Color::WHITE,
+            None,
+            &Rect::new(0.0, 0.0, 5.0, 5.0),
+        );
+        scene.pop_layer();
+
+        assert!(scene_contains(&scene, Point::new(40.0, 50.0)));
+        // Inside the circle, outside the rectangle
+        assert!(!scene_contains(&scene, Point::new(80.0, 50.0)));
+        // Inside the rectangle, outside the circle
+        assert!(!scene_contains(&scene, Point::new(30.0, 95.0)));
+        assert!(scene_contains(&scene, Point::new(2.0, 2.0)));
+    }
+
+    #[test]
+    fn transparent_and_clipped_paths_are_not_hit() {
+        let rect = Rect::new(0.0, 0.0, 100.0, 100.0);
+        let mut scene = Scene::new();
+        scene.fill(
+            Fill::NonZero,
+            Affine::IDENTITY,
+            Color::TRANSPARENT,
+            None,
+            &rect,
+        );
+        assert!(!scene_contains(&scene, Point::new(50.0, 50.0)));
+
+        let mut scene = Scene::new();
+        scene.push_clip_layer(
+            Fill::NonZero,
+            Affine::IDENTITY,
+            &Rect::new(0.0, 0.0, 50.0, 100.0),
+        );
+        scene.fill(Fill::NonZero, Affine::IDENTITY, Color::WHITE, None, &rect);
+        scene.pop_layer();
+        // Drawn after the clip is popped
+        scene.fill(
+            Fill::NonZero,
+            Affine::IDENTITY,
+            Color::WHITE,
+            None,
+            &Rect::new(90.0, 0.0, 100.0, 10.0),
+        );
+        assert!(scene_contains(&scene, Point::new(25.0, 50.0)));
+        assert!(!scene_contains(&scene, Point::new(75.0, 50.0)));
+        assert!(scene_contains(&scene, Point::new(95.0, 5.0)));
+    }
+}
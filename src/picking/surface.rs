@@ -0,0 +1,304 @@
+//! Picking through meshes textured with a [`VelloTextureTarget`].
+//!
+//! Each [`VelloPickingSurface`] has a virtual pointer on its texture. The pointers over the mesh
+//! move it to the texel under them, found by ray casting the mesh for its UV, and forward their
+//! presses to it, and their releases to the surface they pressed, even off the mesh. The world
+//! backend then picks the items rendered into the texture with it.
+
+use crate::render::VelloTextureTarget;
+use bevy::{
+    asset::uuid::Uuid,
+    camera::{ImageRenderTarget, NormalizedRenderTarget},
+    ecs::{lifecycle::HookContext, world::DeferredWorld},
+    picking::{
+        backend::ray::RayMap,
+        mesh_picking::ray_cast::{MeshRayCast, MeshRayCastSettings, RayCastVisibility},
+        pointer::{Location, PointerAction, PointerButton, PointerId, PointerInput},
+    },
+    platform::collections::HashMap,
+    prelude::*,
+};
+
+/// A namespace for the ids of the virtual pointers of surfaces.
+const SURFACE_POINTER_NAMESPACE: u64 = 0x7665_6c6c_6f5f_7576;
+/// A position outside every texture.
+const OFF_TEXTURE: Vec2 = Vec2::splat(-1.0);
+/// The distance of the near plane of texture targets from their transform.
+const TEXTURE_TARGET_NEAR: f32 = 1000.0;
+
+/// Makes the items rendered into a [`VelloTextureTarget`] pickable through a mesh showing its
+/// texture, like the `cube3d` example.
+///
+/// Pointers over the mesh drive a virtual pointer on the texture, at the texel under them. Pointer
+/// events on the items carry the id from [`VelloPickingSurface::pointer_id`], and are one frame
+/// behind the pointers driving them. The mesh needs UVs mapping the texture.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Reflect)]
+#[reflect(Component)]
+#[component(on_add = spawn_surface_pointer, on_remove = despawn_surface_pointer)]
+pub struct VelloPickingSurface {
+    /// The entity with the [`VelloTextureTarget`] rendered into the mesh's texture.
+    pub target: Entity,
+}
+
+impl VelloPickingSurface {
+    pub fn new(target: Entity) -> Self {
+        Self { target }
+    }
+
+    /// The id of the virtual pointer on the texture of the surface `entity`.
+    pub fn pointer_id(entity: Entity) -> PointerId {
+        PointerId::Custom(Uuid::from_u64_pair(
+            SURFACE_POINTER_NAMESPACE,
+            entity.to_bits(),
+        ))
+    }
+}
+
+/// Spawns the surface's virtual pointer, despawned with the surface.
+fn spawn_surface_pointer(mut world: DeferredWorld<'_>, hook_context: HookContext) {
+    let surface = hook_context.entity;
+    world
+        .commands()
+        .spawn((VelloPickingSurface::pointer_id(surface), ChildOf(surface)));
+}
+
+/// Despawns the surface's virtual pointer when it stops being a surface.
+fn despawn_surface_pointer(mut world: DeferredWorld<'_>, hook_context: HookContext) {
+    let surface = hook_context.entity;
+    let pointer_id = VelloPickingSurface::pointer_id(surface);
+    let Some(children) = world.get::<Children>(surface) else {
+        return;
+    };
+    let pointers: Vec<Entity> = children
+        .iter()
+        .filter(|child| world.get::<PointerId>(*child) == Some(&pointer_id))
+        .collect();
+    for pointer in pointers {
+        world.commands().entity(pointer).try_despawn();
+    }
+}
+
+/// The virtual pointers of surfaces.
+#[derive(Default)]
+pub(crate) struct SurfacePointers {
+    /// The position of each surface's virtual pointer on its texture.
+    positions: HashMap<Entity, Vec2>,
+    /// The surface each pointer pressed each button on, which gets its release.
+    presses: HashMap<(PointerId, PointerButton), Entity>,
+}
+
+/// Moves the virtual pointer of every surface to the texel under the pointers over it, and
+/// forwards their presses. Releases and cancels go to the surfaces pressed wherever the pointer
+/// is, so items don't stay pressed after the pointer leaves the mesh.
+pub(crate) fn update_surface_pointers(
+    mut ray_cast: MeshRayCast,
+    rays: Res<RayMap>,
+    surfaces: Query<&VelloPickingSurface>,
+    targets: Query<&VelloTextureTarget>,
+    images: Res<Assets<Image>>,
+    mut pointer_inputs: ParamSet<(MessageReader<PointerInput>, MessageWriter<PointerInput>)>,
+    mut pointers: Local<SurfacePointers>,
+) {
+    // The nearest surface under each pointer, and the texel under the pointer
+    let settings = MeshRayCastSettings {
+        visibility: RayCastVisibility::VisibleInView,
+        filter: &|entity| surfaces.contains(entity),
+        early_exit_test: &|_| true,
+    };
+    let mut surface_hits: HashMap<PointerId, (Entity, Handle<Image>, Vec2)> = HashMap::default();
+    for (ray_id, ray) in rays.iter() {
+        if surface_hits.contains_key(&ray_id.pointer) || is_surface_pointer(ray_id.pointer) {
+            continue;
+        }
+        let Some((surface, uv)) = ray_cast
+            .cast_ray(*ray, &settings)
+            .first()
+            .and_then(|(surface, hit)| Some((*surface, hit.uv?)))
+        else {
+            continue;
+        };
+        let Some(target) = surfaces
+            .get(surface)
+            .ok()
+            .and_then(|surface| targets.get(surface.target).ok())
+        else {
+            continue;
+        };
+        let Some(image) = images.get(&target.image) else {
+            continue;
+        };
+        surface_hits.insert(
+            ray_id.pointer,
+            (surface, target.image.clone(), uv * image.size_f32()),
+        );
+    }
+
+    // Presses of the pointers over each surface, and releases of the surfaces they pressed
+    let mut forwarded: Vec<(Entity, PointerAction)> = Vec::new();
+    for input in pointer_inputs.p0().read() {
+        let hit = surface_hits
+            .get(&input.pointer_id)
+            .map(|(surface, ..)| *surface);
+        match input.action {
+            PointerAction::Move { .. } => {}
+            PointerAction::Press(button) => {
+                let Some(surface) = hit else {
+                    continue;
+                };
+                pointers.presses.insert((input.pointer_id, button), surface);
+                forwarded.push((surface, input.action));
+            }
+            PointerAction::Release(button) => {
+                let pressed = pointers.presses.remove(&(input.pointer_id, button));
+                if let Some(surface) = pressed.or(hit) {
+                    forwarded.push((surface, input.action));
+                }
+            }
+            PointerAction::Cancel => {
+                let mut cancelled: Vec<Entity> = hit.into_iter().collect();
+                pointers.presses.retain(|(pointer, _), surface| {
+                    if *pointer != input.pointer_id {
+                        return true;
+                    }
+                    if !cancelled.contains(surface) {
+                        cancelled.push(*surface);
+                    }
+                    false
+                });
+                forwarded.extend(cancelled.into_iter().map(|surface| (surface, input.action)));
+            }
+            PointerAction::Scroll { .. } => {
+                if let Some(surface) = hit {
+                    forwarded.push((surface, input.action));
+                }
+            }
+        }
+    }
+
+    let mut writer = pointer_inputs.p1();
+    let mut moved = Vec::new();
+    for (surface, image, position) in surface_hits.values() {
+        if moved.contains(surface) {
+            continue;
+        }
+        moved.push(*surface);
+        let last = pointers.positions.insert(*surface, *position);
+        if last == Some(*position) {
+            continue;
+        }
+        writer.write(PointerInput::new(
+            VelloPickingSurface::pointer_id(*surface),
+            texture_location(image, *position),
+            PointerAction::Move {
+                delta: *position - last.unwrap_or(*position),
+            },
+        ));
+    }
+    for (surface, action) in forwarded {
+        // Off the texture of surfaces the pointer has left
+        let location = match surface_hits.values().find(|(hit, ..)| *hit == surface) {
+            Some((_, image, position)) => texture_location(image, *position),
+            None => {
+                let Some(target) = surfaces
+                    .get(surface)
+                    .ok()
+                    .and_then(|surface| targets.get(surface.target).ok())
+                else {
+                    continue;
+                };
+                texture_location(&target.image, OFF_TEXTURE)
+            }
+        };
+        writer.write(PointerInput::new(
+            VelloPickingSurface::pointer_id(surface),
+            location,
+            action,
+        ));
+    }
+
+    // Move the pointers of surfaces nothing is over off their textures, so nothing is hit
+    pointers.positions.retain(|surface, _| {
+        if moved.contains(surface) {
+            return true;
+        }
+        if let Some(target) = surfaces
+            .get(*surface)
+            .ok()
+            .and_then(|surface| targets.get(surface.target).ok())
+        {
+            writer.write(PointerInput::new(
+                VelloPickingSurface::pointer_id(*surface),
+                texture_location(&target.image, OFF_TEXTURE),
+                PointerAction::Move { delta: Vec2::ZERO },
+            ));
+        }
+        false
+    });
+}
+
+fn texture_location(image: &Handle<Image>, position: Vec2) -> Location {
+    Location {
+        target: NormalizedRenderTarget::Image(ImageRenderTarget {
+            handle: image.clone(),
+            scale_factor: 1.0,
+        }),
+        position,
+    }
+}
+
+fn is_surface_pointer(pointer: PointerId) -> bool {
+    matches!(pointer, PointerId::Custom(uuid) if uuid.as_u64_pair().0 == SURFACE_POINTER_NAMESPACE)
+}
+
+/// The ray of a pointer at `position` on a texture target of `size` at `transform`, in pixels from
+/// the texture's top-left corner. Texture targets act like orthographic 2D cameras with one world
+/// unit per pixel.
+pub(crate) fn texture_target_ray(
+    transform: &GlobalTransform,
+    size: Vec2,
+    position: Vec2,
+) -> Option<Ray3d> {
+    if position.cmplt(Vec2::ZERO).any() || position.cmpgt(size).any() {
+        return None;
+    }
+    let view = Vec2::new(position.x - size.x / 2.0, size.y / 2.0 - position.y);
+    // From the near plane of the target's projection
+    let origin = transform.transform_point(view.extend(TEXTURE_TARGET_NEAR));
+    let direction = Dir3::new(transform.affine().transform_vector3(Vec3::NEG_Z)).ok()?;
+    Some(Ray3d::new(origin, direction))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn texture_target_rays_start_at_their_texel() {
+        let transform = GlobalTransform::from(Transform::from_xyz(10.0, 20.0, 0.0));
+        let size = Vec2::new(200.0, 100.0);
+        let ray = texture_target_ray(&transform, size, Vec2::new(150.0, 25.0)).unwrap();
+        assert_eq!(ray.origin.truncate(), Vec2::new(60.0, 45.0));
+        assert_eq!(*ray.direction, Vec3::NEG_Z);
+        assert!(texture_target_ray(&transform, size, OFF_TEXTURE).is_none());
+    }
+
+    #[test]
+    fn surface_pointers_despawn_with_the_surface() {
+        let mut world = World::new();
+        let target = world.spawn_empty().id();
+        let surface = world.spawn(VelloPickingSurface::new(target)).id();
+        let pointer_id = VelloPickingSurface::pointer_id(surface);
+        let pointers = |world: &mut World| {
+            world
+                .query::<&PointerId>()
+                .iter(world)
+                .filter(|pointer| **pointer == pointer_id)
+                .count()
+        };
+        assert_eq!(pointers(&mut world), 1);
+
+        world.entity_mut(surface).remove::<VelloPickingSurface>();
+        assert_eq!(pointers(&mut world), 0);
+        assert!(world.get_entity(surface).is_ok());
+    }
+}
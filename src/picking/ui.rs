@@ -0,0 +1,556 @@
+//! Picking for Vello UI nodes.
+//!
+//! Bevy's UI backend hits every node by its rect. The Vello UI nodes under each pointer are
+//! tested here first, then Bevy's hits are corrected: nodes missed with
+//! [`VelloPickingMode::Shape`] are dropped, and the nodes below them are picked like Bevy would.
+
+use super::{RenderableShapes, VelloHit, VelloPickingHits, VelloPickingMode, scene_contains};
+use crate::{integrations::scene::UiVelloScene, render::VelloRenderable};
+use bevy::{
+    camera::RenderTarget,
+    ecs::{
+        query::{QueryItem, ReadOnlyQueryData},
+        system::{ReadOnlySystemParam, StaticSystemParam, SystemParamItem},
+    },
+    picking::{
+        PickingSystems,
+        backend::{HitData, PointerHits},
+        pointer::{PointerId, PointerLocation},
+    },
+    platform::collections::HashMap,
+    prelude::*,
+    text::{ComputedTextBlock, TextLayoutInfo},
+    ui::{ComputedUiTargetCamera, UiGlobalTransform, UiStack, clip_check_recursive},
+    window::PrimaryWindow,
+};
+use std::marker::PhantomData;
+use tracing::debug;
+use vello::kurbo::Point;
+
+/// The hit depth between nodes of a camera, like Bevy's UI backend.
+const UI_DEPTH_STEP: f32 = 0.00001;
+
+/// The geometry of a UI item type, for precise picking.
+pub(crate) trait VelloUiPickingShape: Component {
+    /// Per-entity data needed to test the item, like a Lottie's playhead.
+    type Data: ReadOnlyQueryData;
+    /// Data shared by all items, like their assets.
+    type Param: ReadOnlySystemParam;
+
+    /// Maps `point`, in physical pixels from the node's center, to the item's own coordinates.
+    /// Defaults to logical pixels from the node's top-left corner. Returns `None` while the item
+    /// is not ready to be tested.
+    fn content_position(
+        &self,
+        _data: &QueryItem<'_, '_, Self::Data>,
+        _param: &SystemParamItem<'_, '_, Self::Param>,
+        node: &ComputedNode,
+        point: Vec2,
+    ) -> Option<Vec2> {
+        Some((point + node.size() / 2.0) * node.inverse_scale_factor())
+    }
+
+    /// Returns whether the item paints at `point`, in its own coordinates. Defaults to anywhere
+    /// inside the node.
+    fn contains(
+        &self,
+        _data: &QueryItem<'_, '_, Self::Data>,
+        _param: &SystemParamItem<'_, '_, Self::Param>,
+        _point: Point,
+    ) -> bool {
+        true
+    }
+}
+
+impl VelloUiPickingShape for UiVelloScene {
+    type Data = ();
+    type Param = ();
+
+    fn contains(&self, _data: &(), _param: &(), point: Point) -> bool {
+        scene_contains(self, point)
+    }
+}
+
+impl<T: VelloRenderable + Component + Clone> VelloUiPickingShape for T {
+    type Data = Entity;
+    type Param = Res<'static, RenderableShapes<T>>;
+
+    fn contains(&self, entity: &Entity, shapes: &Res<RenderableShapes<T>>, point: Point) -> bool {
+        shapes.contains(*entity, point)
+    }
+}
+
+/// Maps `point`, in physical pixels from a node's center, into content of `size` fit inside the
+/// node like SVGs and Lotties are: scaled to fit, keeping its aspect ratio, and centered.
+#[cfg(any(feature = "svg", feature = "lottie"))]
+pub(crate) fn fit_content_position(node: &ComputedNode, size: Vec2, point: Vec2) -> Option<Vec2> {
+    let scale = (node.size() / size).min_element();
+    (scale > 0.0 && scale.is_finite()).then(|| point / scale + size / 2.0)
+}
+
+/// Returns whether `point` is inside content of `size`, and not in the letterboxing around it.
+#[cfg(any(feature = "svg", feature = "lottie"))]
+pub(crate) fn in_content(size: Vec2, point: Point) -> bool {
+    (0.0..=f64::from(size.x)).contains(&point.x) && (0.0..=f64::from(size.y)).contains(&point.y)
+}
+
+/// The results of testing Vello UI nodes under each pointer this frame: the hit position in the
+/// item's own coordinates, or `None` if a [`VelloPickingMode::Shape`] item was missed.
+#[derive(Resource, Default)]
+pub(crate) struct VelloUiHitTests(pub HashMap<(PointerId, Entity), Option<Vec2>>);
+
+/// The position of each pointer on each camera's viewport, in physical pixels, like Bevy's UI
+/// backend sees it.
+fn pointer_positions<'a>(
+    pointers: impl Iterator<Item = (&'a PointerId, &'a PointerLocation)>,
+    cameras: &Query<(Entity, &Camera, &RenderTarget)>,
+    primary_window: Option<Entity>,
+) -> Vec<(PointerId, Entity, Vec2)> {
+    let mut positions = Vec::new();
+    for (pointer_id, pointer_location) in pointers {
+        let Some(location) = pointer_location.location() else {
+            continue;
+        };
+        for (camera_entity, camera, target) in cameras.iter() {
+            if target
+                .normalize(primary_window)
+                .is_none_or(|target| target != location.target)
+            {
+                continue;
+            }
+            let mut position = location.position * camera.target_scaling_factor().unwrap_or(1.0);
+            if let Some(viewport) = camera.physical_viewport_rect() {
+                if !viewport.as_rect().contains(position) {
+                    continue;
+                }
+                position -= viewport.min.as_vec2();
+            }
+            positions.push((*pointer_id, camera_entity, position));
+        }
+    }
+    positions
+}
+
+/// Tests the `C` nodes under each pointer.
+pub(crate) fn update_ui_hit_tests<C: VelloUiPickingShape>(
+    pointers: Query<(&PointerId, &PointerLocation)>,
+    cameras: Query<(Entity, &Camera, &RenderTarget)>,
+    primary_window: Query<Entity, With<PrimaryWindow>>,
+    nodes: Query<(
+        Entity,
+        &C,
+        &ComputedNode,
+        &UiGlobalTransform,
+        &ComputedUiTargetCamera,
+        Option<&VelloPickingMode>,
+        C::Data,
+    )>,
+    shape_param: StaticSystemParam<C::Param>,
+    mut tests: ResMut<VelloUiHitTests>,
+) {
+    let positions = pointer_positions(pointers.iter(), &cameras, primary_window.single().ok());
+    for (entity, shape, node, transform, target_camera, mode, data) in nodes.iter() {
+        let Some(local_from_ui) = transform.try_inverse() else {
+            continue;
+        };
+        for (pointer_id, camera, cursor) in positions.iter() {
+            if target_camera.get() != Some(*camera) || !node.contains_point(*transform, *cursor) {
+                continue;
+            }
+            let local = local_from_ui.transform_point2(*cursor);
+            let Some(position) = shape.content_position(&data, &shape_param, node, local) else {
+                continue;
+            };
+            let hit = mode != Some(&VelloPickingMode::Shape)
+                || shape.contains(
+                    &data,
+                    &shape_param,
+                    Point::new(position.x as f64, position.y as f64),
+                );
+            tests
+                .0
+                .insert((*pointer_id, entity), hit.then_some(position));
+        }
+    }
+}
+
+/// Corrects the UI hits of every pointer with the Vello UI hit tests, and records the hits of
+/// Vello UI nodes.
+#[allow(clippy::too_many_arguments, reason = "Many system parameters")]
+pub(crate) fn update_ui_hits(
+    mut pointer_hits: MessageMutator<PointerHits>,
+    tests: Res<VelloUiHitTests>,
+    mut vello_hits: ResMut<VelloPickingHits>,
+    pointers: Query<(&PointerId, &PointerLocation)>,
+    cameras: Query<(Entity, &Camera, &RenderTarget)>,
+    primary_window: Query<Entity, With<PrimaryWindow>>,
+    ui_stack: Option<Res<UiStack>>,
+    settings: Option<Res<UiPickingSettings>>,
+    nodes: Query<(
+        &ComputedNode,
+        &UiGlobalTransform,
+        &ComputedUiTargetCamera,
+        Option<&Pickable>,
+        Option<&InheritedVisibility>,
+        Option<(&TextLayoutInfo, &ComputedTextBlock)>,
+    )>,
+    clipping_query: Query<(&ComputedNode, &UiGlobalTransform, &Node)>,
+    child_of_query: Query<&ChildOf, Without<OverrideClip>>,
+    pickable_query: Query<&Pickable>,
+) {
+    if tests.0.is_empty() {
+        return;
+    }
+    let require_markers = settings.is_some_and(|settings| settings.require_markers);
+    let blocks = |entity: Entity| {
+        pickable_query
+            .get(entity)
+            .map_or(true, |pickable| pickable.should_block_lower)
+    };
+
+    for hits in pointer_hits.read() {
+        let pointer = hits.pointer;
+        let mut picks = Vec::with_capacity(hits.picks.len());
+        let mut rewalk_below = None;
+        for (index, (entity, hit)) in hits.picks.iter().enumerate() {
+            match tests.0.get(&(pointer, *entity)) {
+                Some(Some(position)) => {
+                    vello_hits.0.insert(
+                        (pointer, *entity),
+                        VelloHit {
+                            position: *position,
+                        },
+                    );
+                    picks.push((*entity, hit.clone()));
+                }
+                Some(None) => {
+                    // A missed node that blocked hides the nodes below it from Bevy's backend
+                    if index + 1 == hits.picks.len() && blocks(*entity) {
+                        rewalk_below = Some((*entity, hit.camera, hit.depth));
+                    }
+                }
+                None => picks.push((*entity, hit.clone())),
+            }
+        }
+
+        let rewalk = rewalk_below.zip(ui_stack.as_ref());
+        if let Some(((missed, camera, mut depth), ui_stack)) = rewalk {
+            let cursor = pointer_positions(
+                pointers.iter().filter(|(id, _)| **id == pointer),
+                &cameras,
+                primary_window.single().ok(),
+            )
+            .into_iter()
+            .find_map(|(_, cursor_camera, cursor)| (cursor_camera == camera).then_some(cursor));
+            let below = nodes
+                .get(missed)
+                .map_or(0, |(node, ..)| node.stack_index() as usize)
+                .min(ui_stack.uinodes.len());
+            let below = if cursor.is_some() { below } else { 0 };
+            let cursor = cursor.unwrap_or_default();
+
+            // Bevy's UI backend, from the node below the missed one down
+            for &entity in ui_stack.uinodes[..below].iter().rev() {
+                let Ok((node, transform, target_camera, pickable, visibility, text)) =
+                    nodes.get(entity)
+                else {
+                    continue;
+                };
+                if target_camera.get() != Some(camera)
+                    || node.size() == Vec2::ZERO
+                    || visibility.map(|visibility| visibility.get()) != Some(true)
+                    || (text.is_none() && require_markers && pickable.is_none())
+                {
+                    continue;
+                }
+
+                let picked = if let Some((layout, block)) = text {
+                    pick_text_section(node, transform, cursor, layout, block)
+                        .filter(|section| !require_markers || pickable_query.contains(*section))
+                } else if node.contains_point(*transform, cursor)
+                    && clip_check_recursive(cursor, entity, &clipping_query, &child_of_query)
+                {
+                    Some(entity)
+                } else {
+                    None
+                };
+                let Some(picked) = picked else {
+                    continue;
+                };
+                match tests.0.get(&(pointer, picked)) {
+                    Some(None) => continue,
+                    Some(Some(position)) => {
+                        vello_hits.0.insert(
+                            (pointer, picked),
+                            VelloHit {
+                                position: *position,
+                            },
+                        );
+                    }
+                    None => {}
+                }
+
+                depth += UI_DEPTH_STEP;
+                let position = transform.inverse().transform_point2(cursor) / node.size();
+                picks.push((
+                    picked,
+                    HitData::new(camera, depth, Some(position.extend(0.0)), None),
+                ));
+                if pickable.is_none_or(|pickable| pickable.should_block_lower) {
+                    break;
+                }
+            }
+        }
+
+        hits.picks = picks;
+    }
+}
+
+/// The text section of a UI text node under `point`, like Bevy's UI backend picks it.
+fn pick_text_section(
+    node: &ComputedNode,
+    transform: &UiGlobalTransform,
+    point: Vec2,
+    layout: &TextLayoutInfo,
+    block: &ComputedTextBlock,
+) -> Option<Entity> {
+    let local = transform
+        .try_inverse()
+        .map(|transform| transform.transform_point2(point) + 0.5 * node.size())?;
+    layout
+        .run_geometry
+        .iter()
+        .find(|run| run.bounds.contains(local))
+        .and_then(|run| block.entities().get(run.span_index))
+        .map(|section| section.entity)
+}
+
+pub struct UiPickingPlugin<C: VelloUiPickingShape> {
+    _type: PhantomData<C>,
+}
+
+impl<C: VelloUiPickingShape> Default for UiPickingPlugin<C> {
+    fn default() -> Self {
+        Self { _type: PhantomData }
+    }
+}
+
+impl<C: VelloUiPickingShape> Plugin for UiPickingPlugin<C> {
+    fn build(&self, app: &mut App) {
+        debug!(
+            "Adding UI picking support for {}",
+            std::any::type_name::<C>()
+        );
+        super::init_picking(app);
+        app.add_systems(
+            PreUpdate,
+            update_ui_hit_tests::<C>.in_set(PickingSystems::Backend),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::{
+        ecs::system::RunSystemOnce, math::Rect, picking::pointer::Location, reflect::PartialReflect,
+    };
+    use vello::{
+        kurbo::{self, Affine},
+        peniko::{Color, Fill},
+    };
+
+    /// The camera of a UI node, which Bevy's UI systems would find.
+    fn target_camera(camera: Entity) -> ComputedUiTargetCamera {
+        let mut target_camera = ComputedUiTargetCamera::default();
+        target_camera
+            .reflect_mut()
+            .as_struct()
+            .unwrap()
+            .field_mut("camera")
+            .unwrap()
+            .apply(&camera);
+        target_camera
+    }
+
+    /// A world with a camera on the primary window, and the mouse at `cursor`.
+    fn setup(cursor: Vec2) -> (World, Entity) {
+        let mut world = World::new();
+        world.init_resource::<VelloUiHitTests>();
+        world.init_resource::<VelloPickingHits>();
+        world.init_resource::<Messages<PointerHits>>();
+        let window = world.spawn((Window::default(), PrimaryWindow)).id();
+        let camera = world
+            .spawn((Camera::default(), RenderTarget::default()))
+            .id();
+        let target = RenderTarget::default().normalize(Some(window)).unwrap();
+        world.spawn((
+            PointerId::Mouse,
+            PointerLocation::new(Location {
+                target,
+                position: cursor,
+            }),
+        ));
+        (world, camera)
+    }
+
+    /// A UI node of the camera covering `rect` in logical pixels.
+    fn node(camera: Entity, stack_index: u32, rect: Rect) -> impl Bundle {
+        (
+            ComputedNode {
+                size: rect.size(),
+                stack_index,
+                ..default()
+            },
+            UiGlobalTransform::from_translation(rect.center()),
+            target_camera(camera),
+            InheritedVisibility::VISIBLE,
+        )
+    }
+
+    /// A Vello UI scene picked by its shape, painting `rect` only.
+    fn scene(rect: kurbo::Rect) -> impl Bundle {
+        let mut scene = UiVelloScene::new();
+        scene.fill(Fill::NonZero, Affine::IDENTITY, Color::WHITE, None, &rect);
+        (Node::default(), scene, VelloPickingMode::Shape)
+    }
+
+    /// Corrects the nodes Bevy's UI backend picked, from the top down, and returns the picks.
+    fn pick(world: &mut World, camera: Entity, bevy_picks: &[Entity]) -> Vec<Entity> {
+        let picks = bevy_picks
+            .iter()
+            .enumerate()
+            .map(|(index, entity)| {
+                let depth = index as f32 * UI_DEPTH_STEP;
+                (*entity, HitData::new(camera, depth, None, None))
+            })
+            .collect();
+        world.write_message(PointerHits::new(PointerId::Mouse, picks, 0.0));
+        world
+            .run_system_once(update_ui_hit_tests::<UiVelloScene>)
+            .unwrap();
+        world.run_system_once(update_ui_hits).unwrap();
+        world
+            .resource::<Messages<PointerHits>>()
+            .iter_current_update_messages()
+            .last()
+            .unwrap()
+            .picks
+            .iter()
+            .map(|(entity, _)| *entity)
+            .collect()
+    }
+
+    #[test]
+    fn shape_misses_fall_through_to_the_node_below() {
+        let rect = Rect::new(0.0, 0.0, 100.0, 100.0);
+        for (cursor, picked_top) in [(Vec2::new(50.0, 50.0), false), (Vec2::new(5.0, 5.0), true)] {
+            let (mut world, camera) = setup(cursor);
+            let below = world
+                .spawn((node(camera, 0, rect), Node::default(), Pickable::default()))
+                .id();
+            let top = world
+                .spawn((
+                    node(camera, 1, rect),
+                    scene(kurbo::Rect::new(0.0, 0.0, 10.0, 10.0)),
+                ))
+                .id();
+            world.insert_resource(UiStack {
+                uinodes: vec![below, top],
+                ..default()
+            });
+
+            // Bevy's UI backend stops at the top node, which blocks the nodes below it
+            let picks = pick(&mut world, camera, &[top]);
+            let hit = world
+                .resource::<VelloPickingHits>()
+                .get(PointerId::Mouse, top);
+            if picked_top {
+                assert_eq!(picks, [top]);
+                assert_eq!(hit.map(|hit| hit.position), Some(cursor));
+            } else {
+                assert_eq!(picks, [below]);
+                assert!(hit.is_none());
+            }
+        }
+    }
+
+    #[test]
+    fn clipped_nodes_below_a_miss_are_not_picked() {
+        for (cursor, picked_below) in [(Vec2::new(5.0, 5.0), true), (Vec2::new(50.0, 50.0), false)]
+        {
+            let (mut world, camera) = setup(cursor);
+            // Clips its child to its top-left corner
+            let clip = world
+                .spawn((
+                    node(camera, 0, Rect::new(0.0, 0.0, 20.0, 20.0)),
+                    Node {
+                        overflow: Overflow::clip(),
+                        ..default()
+                    },
+                    Pickable::IGNORE,
+                ))
+                .id();
+            let rect = Rect::new(0.0, 0.0, 100.0, 100.0);
+            let below = world
+                .spawn((
+                    node(camera, 1, rect),
+                    Node::default(),
+                    Pickable::default(),
+                    ChildOf(clip),
+                ))
+                .id();
+            let top = world
+                .spawn((
+                    node(camera, 2, rect),
+                    scene(kurbo::Rect::new(90.0, 90.0, 100.0, 100.0)),
+                ))
+                .id();
+            world.insert_resource(UiStack {
+                uinodes: vec![clip, below, top],
+                ..default()
+            });
+
+            let picks = pick(&mut world, camera, &[top]);
+            if picked_below {
+                assert_eq!(picks, [below]);
+            } else {
+                assert!(picks.is_empty());
+            }
+        }
+    }
+
+    #[cfg(any(feature = "svg", feature = "lottie"))]
+    #[test]
+    fn content_is_fit_inside_its_node() {
+        let node = ComputedNode {
+            size: Vec2::new(200.0, 100.0),
+            ..default()
+        };
+        // Square content is scaled to the node's height, and centered
+        let size = Vec2::new(50.0, 50.0);
+        assert_eq!(
+            fit_content_position(&node, size, Vec2::ZERO),
+            Some(Vec2::new(25.0, 25.0))
+        );
+        assert_eq!(
+            fit_content_position(&node, size, Vec2::new(-50.0, -50.0)),
+            Some(Vec2::ZERO)
+        );
+        // The letterboxing beside the content
+        let beside = fit_content_position(&node, size, Vec2::new(-90.0, 0.0)).unwrap();
+        assert_eq!(beside, Vec2::new(-20.0, 25.0));
+        assert!(!in_content(
+            size,
+            Point::new(beside.x.into(), beside.y.into())
+        ));
+        assert!(in_content(size, Point::new(0.0, 50.0)));
+
+        // Empty nodes and content have nothing to hit
+        assert_eq!(
+            fit_content_position(&ComputedNode::default(), size, Vec2::ZERO),
+            None
+        );
+        assert_eq!(fit_content_position(&node, Vec2::ZERO, Vec2::ZERO), None);
+    }
+}
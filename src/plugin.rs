@@ -1,18 +1,60 @@
+use std::path::PathBuf;
+
 use bevy::{camera::visibility::RenderLayers, prelude::*};
 use vello::AaConfig;
 
-use crate::render::{VelloCanvasSettings, VelloRenderPlugin, VelloRenderSettings};
+use crate::render::{
+    VelloCanvasSettings, VelloRecoveryPolicy, VelloRenderPlugin, VelloRenderSettings,
+};
 
 #[derive(Clone)]
 pub struct VelloPlugin {
     /// The render layers that will be used for the Vello canvas mesh.
     pub canvas_render_layers: RenderLayers,
 
+    /// Z boundaries at which world items are split into separate canvases. Each canvas is sorted
+    /// with Bevy's 2D sprites and meshes, so a sprite at a boundary z draws above the Vello items
+    /// below it and below the Vello items at or above it.
+    ///
+    /// Each boundary costs another canvas texture per view. When empty, all world items share a
+    /// single canvas drawn before Bevy's transparent 2D items. UI items are drawn into the top
+    /// canvas, unless [`interleave_ui`](Self::interleave_ui) is set.
+    pub canvas_z_layers: Vec<f32>,
+
+    /// Draw UI items in Bevy's UI pass instead of the top canvas, so they are stacked with native
+    /// UI nodes: a Bevy `Text` can sit above one Vello node and below another.
+    ///
+    /// Sorted UI items are split into stacking ranges wherever a native UI node is drawn between
+    /// them, and each range costs another viewport-sized texture and render per view. UI canvases
+    /// always hold 8-bit sRGB color, ignoring [`VelloViewSettings::canvas_format`] and
+    /// [`VelloViewSettings::intensity`].
+    ///
+    /// [`VelloViewSettings::canvas_format`]: crate::render::VelloViewSettings::canvas_format
+    /// [`VelloViewSettings::intensity`]: crate::render::VelloViewSettings::intensity
+    pub interleave_ui: bool,
+
+    /// Sort world items with the same z from the highest y down, so items lower on screen are
+    /// drawn above, as in top-down games. Use [`VelloZIndex`](crate::render::VelloZIndex) to sort
+    /// items independently of their transform.
+    pub y_sort: bool,
+
     /// Use CPU instead of GPU
     pub use_cpu: bool,
 
     /// Which antialiasing strategy to use
     pub antialiasing: AaConfig,
+
+    /// A directory to persist compiled GPU pipelines in, so later launches skip most shader
+    /// compilation. See [`VelloRenderSettings::pipeline_cache_dir`].
+    pub pipeline_cache_dir: Option<PathBuf>,
+
+    /// Create the renderer in the background instead of blocking the first frames. Nothing is
+    /// rendered until it's ready, see [`VelloRendererReady`](crate::render::VelloRendererReady).
+    pub background_init: bool,
+
+    /// How the renderer recovers from initialization and rendering failures. Failures are sent
+    /// as [`VelloRenderError`](crate::render::VelloRenderError) messages.
+    pub recovery_policy: VelloRecoveryPolicy,
 }
 
 impl Default for VelloPlugin {
@@ -21,22 +63,38 @@ impl Default for VelloPlugin {
         let default_render_settings = VelloRenderSettings::default();
         Self {
             canvas_render_layers: default_canvas_settings.render_layers,
+            canvas_z_layers: default_canvas_settings.z_layers,
+            interleave_ui: default_canvas_settings.interleave_ui,
+            y_sort: default_canvas_settings.y_sort,
             use_cpu: default_render_settings.use_cpu,
             antialiasing: default_render_settings.antialiasing,
+            pipeline_cache_dir: default_render_settings.pipeline_cache_dir,
+            background_init: default_render_settings.background_init,
+            recovery_policy: VelloRecoveryPolicy::default(),
         }
     }
 }
 
 impl Plugin for VelloPlugin {
     fn build(&self, app: &mut App) {
+        let mut z_layers = self.canvas_z_layers.clone();
+        z_layers.retain(|z| !z.is_nan());
+        z_layers.sort_by(f32::total_cmp);
+        z_layers.dedup();
         app.add_plugins(VelloRenderPlugin {
             canvas_settings: VelloCanvasSettings {
                 render_layers: self.canvas_render_layers.clone(),
+                z_layers,
+                interleave_ui: self.interleave_ui,
+                y_sort: self.y_sort,
             },
             render_settings: VelloRenderSettings {
                 use_cpu: self.use_cpu,
                 antialiasing: self.antialiasing,
+                pipeline_cache_dir: self.pipeline_cache_dir.clone(),
+                background_init: self.background_init,
             },
+            recovery_policy: self.recovery_policy.clone(),
         });
         app.add_plugins(crate::integrations::scene::SceneIntegrationPlugin);
         #[cfg(feature = "svg")]
@@ -30,6 +30,8 @@ pub const OPEN_CLIPS_COUNT: DiagnosticPath = DiagnosticPath::const_new("vello_op
 pub const GLYPH_COUNT: DiagnosticPath = DiagnosticPath::const_new("vello_glyphs");
 #[cfg(feature = "text")]
 pub const GLYPH_RUN_COUNT: DiagnosticPath = DiagnosticPath::const_new("vello_glyph_runs");
+/// Total number of frames in which Vello skipped rendering, as nothing changed.
+pub const SKIPPED_FRAMES_COUNT: DiagnosticPath = DiagnosticPath::const_new("vello_skipped_frames");
 
 /// Adds Vello render diagnostics reporting.
 #[derive(Default)]
@@ -40,7 +42,10 @@ impl Plugin for VelloRenderDiagnosticsPlugin {
         app.register_diagnostic(Diagnostic::new(PATH_COUNT).with_suffix(" paths"))
             .register_diagnostic(Diagnostic::new(PATH_SEGMENTS_COUNT).with_suffix(" path segments"))
             .register_diagnostic(Diagnostic::new(CLIPS_COUNT).with_suffix(" clips"))
-            .register_diagnostic(Diagnostic::new(OPEN_CLIPS_COUNT).with_suffix(" open clips"));
+            .register_diagnostic(Diagnostic::new(OPEN_CLIPS_COUNT).with_suffix(" open clips"))
+            .register_diagnostic(
+                Diagnostic::new(SKIPPED_FRAMES_COUNT).with_suffix(" skipped frames"),
+            );
         #[cfg(feature = "text")]
         app.register_diagnostic(Diagnostic::new(GLYPH_COUNT).with_suffix(" glyphs"))
             .register_diagnostic(Diagnostic::new(GLYPH_RUN_COUNT).with_suffix(" glyph runs"));
@@ -126,6 +131,7 @@ fn sync_frame_profile(
     let n_path_segs = render_data.n_path_segs as f64;
     let n_clips = render_data.n_clips as f64;
     let n_open_clips = render_data.n_open_clips as f64;
+    let n_skipped_frames = render_data.n_skipped_frames as f64;
     #[cfg(feature = "text")]
     let n_glyphs = render_data.n_glyphs as f64;
     #[cfg(feature = "text")]
@@ -136,6 +142,7 @@ fn sync_frame_profile(
             diagnostics.add_measurement(&PATH_SEGMENTS_COUNT, || n_path_segs);
             diagnostics.add_measurement(&CLIPS_COUNT, || n_clips);
             diagnostics.add_measurement(&OPEN_CLIPS_COUNT, || n_open_clips);
+            diagnostics.add_measurement(&SKIPPED_FRAMES_COUNT, || n_skipped_frames);
             #[cfg(feature = "text")]
             {
                 diagnostics.add_measurement(&GLYPH_COUNT, || n_glyphs);
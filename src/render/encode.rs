@@ -0,0 +1,668 @@
+//! Encoding sorted render items into scenes, in parallel on the compute task pool.
+
+use std::marker::PhantomData;
+
+#[cfg(feature = "text")]
+use bevy::render::render_asset::RenderAssets;
+use bevy::tasks::ComputeTaskPool;
+use vello::{
+    Scene,
+    kurbo::{Affine, Rect},
+    peniko::{Compose, Mix},
+};
+
+use super::{
+    PreparedClip, VelloBlendMode, VelloGroup, VelloMaskMode, VelloUiRenderItem,
+    VelloWorldRenderItem,
+    extract::{ExtractedUiVelloGroup, ExtractedVelloGroup},
+    renderable::encode_renderable,
+};
+#[cfg(feature = "lottie")]
+use crate::integrations::lottie::render::{
+    ExtractedUiVelloLottie, ExtractedVelloLottie2d, append_lottie,
+};
+use crate::integrations::scene::render::{ExtractedUiVelloScene, ExtractedVelloScene2d};
+#[cfg(feature = "svg")]
+use crate::integrations::svg::render::{ExtractedUiVelloSvg, ExtractedVelloSvg2d, append_svg};
+#[cfg(feature = "text")]
+use crate::integrations::text::{
+    VelloFont,
+    render::{ExtractedUiVelloText, ExtractedVelloText2d},
+};
+
+/// The fewest items worth encoding on their own task. Below this, spawning a task costs more than
+/// it saves.
+const MIN_ITEMS_PER_TASK: usize = 4;
+
+/// Bounds the layers of unbounded items, well beyond any real viewport.
+const UNBOUNDED: Rect = Rect::new(-1e7, -1e7, 1e7, 1e7);
+
+/// Encodes the render items of a single target.
+#[derive(Clone, Copy)]
+pub(crate) struct ItemEncoder<'a> {
+    #[cfg(feature = "text")]
+    pub fonts: &'a RenderAssets<VelloFont>,
+    pub _lifetime: PhantomData<&'a ()>,
+}
+
+impl ItemEncoder<'_> {
+    /// Encodes world items, then UI items on top, into one scene scaled by `scale`.
+    ///
+    /// Items are split into chunks encoded in parallel, each into its own scene, and the scenes
+    /// are appended in order, so the result matches encoding the items one after another.
+    pub fn encode(
+        self,
+        world_items: &[VelloWorldRenderItem],
+        ui_items: &[VelloUiRenderItem],
+        scale: f64,
+    ) -> Scene {
+        let n_items = world_items.len() + ui_items.len();
+        let pool = ComputeTaskPool::get_or_init(Default::default);
+        let n_tasks = pool.thread_num().min(n_items / MIN_ITEMS_PER_TASK);
+
+        let scenes = if n_tasks > 1 {
+            let chunk_size = n_items.div_ceil(n_tasks);
+            pool.scope(|scope| {
+                for chunk in world_items.chunks(chunk_size) {
+                    scope.spawn(async move {
+                        let mut scene = Scene::new();
+                        for item in chunk {
+                            self.world_item(&mut scene, item);
+                        }
+                        scene
+                    });
+                }
+                for chunk in ui_items.chunks(chunk_size) {
+                    scope.spawn(async move {
+                        let mut scene = Scene::new();
+                        for item in chunk {
+                            self.ui_item(&mut scene, item);
+                        }
+                        scene
+                    });
+                }
+            })
+        } else {
+            let mut scene = Scene::new();
+            for item in world_items {
+                self.world_item(&mut scene, item);
+            }
+            for item in ui_items {
+                self.ui_item(&mut scene, item);
+            }
+            vec![scene]
+        };
+
+        // Items are placed in viewport pixels, scale them to the canvas resolution
+        match <[Scene; 1]>::try_from(scenes) {
+            Ok([scene]) if scale == 1.0 => scene,
+            Ok(scenes) => compose(&scenes, scale),
+            Err(scenes) => compose(&scenes, scale),
+        }
+    }
+
+    /// Encodes a single world item.
+    pub fn world_item(self, scene: &mut Scene, render_item: &VelloWorldRenderItem) {
+        match render_item {
+            VelloWorldRenderItem::Scene {
+                affine,
+                item:
+                    ExtractedVelloScene2d {
+                        scene: item,
+                        blend_mode,
+                        alpha,
+                        ..
+                    },
+            } => {
+                if *alpha <= 0.0 {
+                    return;
+                }
+                let layer = push_item_layer(scene, *blend_mode, *alpha, *affine, None);
+                scene.append(item, Some(*affine));
+                if layer {
+                    scene.pop_layer();
+                }
+            }
+            #[cfg(feature = "lottie")]
+            VelloWorldRenderItem::Lottie {
+                affine,
+                item:
+                    ExtractedVelloLottie2d {
+                        asset,
+                        alpha,
+                        playhead,
+                        blend_mode,
+                        ..
+                    },
+            } => {
+                append_lottie(scene, asset, *playhead, *alpha, *blend_mode, *affine);
+            }
+            #[cfg(feature = "svg")]
+            VelloWorldRenderItem::Svg {
+                affine,
+                item:
+                    ExtractedVelloSvg2d {
+                        asset,
+                        alpha,
+                        blend_mode,
+                        ..
+                    },
+            } => {
+                append_svg(scene, asset, *alpha, *blend_mode, *affine);
+            }
+            #[cfg(feature = "text")]
+            VelloWorldRenderItem::Text {
+                affine,
+                item:
+                    ExtractedVelloText2d {
+                        text,
+                        text_anchor,
+                        blend_mode,
+                        alpha,
+                        ..
+                    },
+            } => {
+                if *alpha <= 0.0 {
+                    return;
+                }
+                if let Some(font) = self.fonts.get(text.style.font.id()) {
+                    let layer = push_item_layer(scene, *blend_mode, *alpha, Affine::IDENTITY, None);
+                    font.render(
+                        scene,
+                        *affine,
+                        &text.value,
+                        &text.style,
+                        text.text_align,
+                        text.max_advance,
+                        *text_anchor,
+                        None,
+                        None,
+                    );
+                    if layer {
+                        scene.pop_layer();
+                    }
+                }
+            }
+            VelloWorldRenderItem::Custom {
+                affine,
+                blend_mode,
+                opacity,
+                item,
+            } => {
+                encode_renderable(scene, item.as_ref(), *affine, *blend_mode, *opacity);
+            }
+            VelloWorldRenderItem::Group {
+                affine,
+                item:
+                    ExtractedVelloGroup {
+                        group,
+                        blend_mode,
+                        alpha,
+                        ..
+                    },
+                items,
+            } => {
+                if *alpha <= 0.0 {
+                    return;
+                }
+                push_group_layer(scene, group, *blend_mode, *alpha, *affine);
+                for item in items {
+                    self.world_item(scene, item);
+                }
+                scene.pop_layer();
+            }
+            VelloWorldRenderItem::Clipped { clips, item } => {
+                for clip in clips.iter() {
+                    push_clip(scene, clip);
+                }
+                self.world_item(scene, item);
+                for clip in clips.iter().rev() {
+                    pop_clip(scene, clip);
+                }
+            }
+        }
+    }
+
+    /// Encodes a single UI item, clipped to its clip rect.
+    pub fn ui_item(self, scene: &mut Scene, render_item: &VelloUiRenderItem) {
+        // Skip fully transparent items before pushing any layers, so that
+        // clip push/pop balance is never a concern for early returns.
+        let skip = match render_item {
+            VelloUiRenderItem::Scene { item, .. } => item.alpha <= 0.0,
+            #[cfg(feature = "lottie")]
+            VelloUiRenderItem::Lottie { item, .. } => item.alpha <= 0.0,
+            #[cfg(feature = "svg")]
+            VelloUiRenderItem::Svg { item, .. } => item.alpha <= 0.0,
+            #[cfg(feature = "text")]
+            VelloUiRenderItem::Text { item, .. } => item.alpha <= 0.0,
+            VelloUiRenderItem::Custom { opacity, .. } => *opacity <= 0.0,
+            VelloUiRenderItem::Group { item, .. } => item.alpha <= 0.0,
+        };
+        if skip {
+            return;
+        }
+
+        // Extract the clip rect (pre-scaled to physical pixels in sort_render_items)
+        let clip = match render_item {
+            VelloUiRenderItem::Scene { clip, .. } => clip,
+            #[cfg(feature = "lottie")]
+            VelloUiRenderItem::Lottie { clip, .. } => clip,
+            #[cfg(feature = "svg")]
+            VelloUiRenderItem::Svg { clip, .. } => clip,
+            #[cfg(feature = "text")]
+            VelloUiRenderItem::Text { clip, .. } => clip,
+            VelloUiRenderItem::Custom { clip, .. } => clip,
+            VelloUiRenderItem::Group { clip, .. } => clip,
+        };
+
+        // Rounded or rotated nodes clip to their exact shape within the clip rect
+        let clip_shapes = match render_item {
+            VelloUiRenderItem::Scene { item, .. } => &item.clip_shapes,
+            #[cfg(feature = "lottie")]
+            VelloUiRenderItem::Lottie { item, .. } => &item.clip_shapes,
+            #[cfg(feature = "svg")]
+            VelloUiRenderItem::Svg { item, .. } => &item.clip_shapes,
+            #[cfg(feature = "text")]
+            VelloUiRenderItem::Text { item, .. } => &item.clip_shapes,
+            VelloUiRenderItem::Custom { clip_shapes, .. } => clip_shapes,
+            VelloUiRenderItem::Group { item, .. } => &item.clip_shapes,
+        };
+
+        if let Some(clip_rect) = clip {
+            scene.push_clip_layer(vello::peniko::Fill::NonZero, Affine::IDENTITY, clip_rect);
+        }
+        for clip_shape in clip_shapes.iter() {
+            scene.push_clip_layer(
+                vello::peniko::Fill::NonZero,
+                clip_shape.affine,
+                &clip_shape.shape,
+            );
+        }
+
+        match render_item {
+            VelloUiRenderItem::Scene {
+                affine,
+                item:
+                    ExtractedUiVelloScene {
+                        scene: item,
+                        blend_mode,
+                        alpha,
+                        ..
+                    },
+                ..
+            } => {
+                let layer = push_item_layer(scene, *blend_mode, *alpha, *affine, None);
+                scene.append(item, Some(*affine));
+                if layer {
+                    scene.pop_layer();
+                }
+            }
+            #[cfg(feature = "lottie")]
+            VelloUiRenderItem::Lottie {
+                affine,
+                item:
+                    ExtractedUiVelloLottie {
+                        asset,
+                        alpha,
+                        playhead,
+                        blend_mode,
+                        ..
+                    },
+                ..
+            } => {
+                append_lottie(scene, asset, *playhead, *alpha, *blend_mode, *affine);
+            }
+            #[cfg(feature = "svg")]
+            VelloUiRenderItem::Svg {
+                affine,
+                item:
+                    ExtractedUiVelloSvg {
+                        asset,
+                        alpha,
+                        blend_mode,
+                        ..
+                    },
+                ..
+            } => {
+                append_svg(scene, asset, *alpha, *blend_mode, *affine);
+            }
+            #[cfg(feature = "text")]
+            VelloUiRenderItem::Text {
+                affine,
+                clip,
+                item:
+                    ExtractedUiVelloText {
+                        text,
+                        text_anchor,
+                        ui_node,
+                        ui_render_target,
+                        blend_mode,
+                        alpha,
+                        ..
+                    },
+            } => {
+                if let Some(font) = self.fonts.get(text.style.font.id()) {
+                    let logical_size = ui_node.size() / ui_render_target.scale_factor();
+                    let layer = push_item_layer(scene, *blend_mode, *alpha, Affine::IDENTITY, None);
+                    font.render(
+                        scene,
+                        *affine,
+                        &text.value,
+                        &text.style,
+                        text.text_align,
+                        text.max_advance,
+                        *text_anchor,
+                        Some(logical_size),
+                        *clip,
+                    );
+                    if layer {
+                        scene.pop_layer();
+                    }
+                }
+            }
+            VelloUiRenderItem::Custom {
+                affine,
+                blend_mode,
+                opacity,
+                item,
+                ..
+            } => {
+                encode_renderable(scene, item.as_ref(), *affine, *blend_mode, *opacity);
+            }
+            VelloUiRenderItem::Group {
+                affine,
+                item:
+                    ExtractedUiVelloGroup {
+                        group,
+                        blend_mode,
+                        alpha,
+                        ..
+                    },
+                items,
+                ..
+            } => {
+                push_group_layer(scene, group, *blend_mode, *alpha, *affine);
+                for item in items {
+                    self.ui_item(scene, item);
+                }
+                scene.pop_layer();
+            }
+        }
+
+        for _ in clip_shapes.iter() {
+            scene.pop_layer();
+        }
+        if clip.is_some() {
+            scene.pop_layer();
+        }
+    }
+}
+
+/// Pushes the layer an item is drawn through to apply its opacity and blend mode, if it needs one.
+/// `bounds` are in item space, and default to unbounded.
+///
+/// Returns whether a layer was pushed, which must be popped after encoding the item.
+pub(crate) fn push_item_layer(
+    scene: &mut Scene,
+    blend_mode: VelloBlendMode,
+    alpha: f32,
+    affine: Affine,
+    bounds: Option<Rect>,
+) -> bool {
+    if alpha >= 1.0 && blend_mode.is_normal() {
+        return false;
+    }
+    scene.push_layer(
+        vello::peniko::Fill::NonZero,
+        blend_mode.0,
+        alpha.min(1.0),
+        affine,
+        &bounds.unwrap_or(UNBOUNDED),
+    );
+    true
+}
+
+/// Pushes the layer the items of a group are drawn into, clipped to the group's shape if it has
+/// one. Groups always get a layer, even when opaque and unclipped, so blend modes of their items
+/// only blend with the other items of the group.
+///
+/// The layer must be popped after encoding the group's items.
+fn push_group_layer(
+    scene: &mut Scene,
+    group: &VelloGroup,
+    blend_mode: VelloBlendMode,
+    alpha: f32,
+    affine: Affine,
+) {
+    match &group.clip {
+        Some(clip) => scene.push_layer(
+            vello::peniko::Fill::NonZero,
+            blend_mode.0,
+            alpha.min(1.0),
+            affine,
+            clip,
+        ),
+        None => scene.push_layer(
+            vello::peniko::Fill::NonZero,
+            blend_mode.0,
+            alpha.min(1.0),
+            affine,
+            &UNBOUNDED,
+        ),
+    }
+}
+
+/// Starts clipping by a [`PreparedClip`]. Masked content is drawn into its own layer, so the mask
+/// only affects the content.
+fn push_clip(scene: &mut Scene, clip: &PreparedClip) {
+    match clip {
+        PreparedClip::Shape { affine, shape } => {
+            scene.push_clip_layer(vello::peniko::Fill::NonZero, *affine, shape);
+        }
+        PreparedClip::Mask { .. } => {
+            scene.push_layer(
+                vello::peniko::Fill::NonZero,
+                VelloBlendMode::NORMAL.0,
+                1.0,
+                Affine::IDENTITY,
+                &UNBOUNDED,
+            );
+        }
+    }
+}
+
+/// Stops clipping by a [`PreparedClip`], applying masks to the content drawn since [`push_clip`].
+fn pop_clip(scene: &mut Scene, clip: &PreparedClip) {
+    if let PreparedClip::Mask {
+        affine,
+        scene: mask,
+        mode,
+    } = clip
+    {
+        match mode {
+            // Keeps the content where the mask is opaque
+            VelloMaskMode::Alpha => scene.push_layer(
+                vello::peniko::Fill::NonZero,
+                VelloBlendMode::new(Mix::Normal, Compose::DestIn).0,
+                1.0,
+                Affine::IDENTITY,
+                &UNBOUNDED,
+            ),
+            VelloMaskMode::Luminance => scene.push_luminance_mask_layer(
+                vello::peniko::Fill::NonZero,
+                1.0,
+                Affine::IDENTITY,
+                &UNBOUNDED,
+            ),
+        }
+        scene.append(mask, Some(*affine));
+        scene.pop_layer();
+    }
+    scene.pop_layer();
+}
+
+/// Appends encoded chunks in order, scaled by `scale`.
+fn compose(scenes: &[Scene], scale: f64) -> Scene {
+    let transform = (scale != 1.0).then(|| Affine::scale(scale));
+    let mut composed = Scene::new();
+    for scene in scenes {
+        composed.append(scene, transform);
+    }
+    composed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::{
+        VelloRenderable, WorldSortKey,
+        extract::UiClipShape,
+        renderable::ErasedRenderable,
+        test_utils::{Square, with_encoder},
+    };
+    use bevy::prelude::{Entity, GlobalTransform};
+    use std::sync::Arc;
+    use vello::kurbo::Shape;
+
+    #[test]
+    fn parallel_encoding_keeps_item_order() {
+        let square = |i: usize| -> ErasedRenderable { Arc::new(Square::new(i as f32 / 100.0)) };
+        let world: Vec<_> = (0..64)
+            .map(|i| VelloWorldRenderItem::Custom {
+                affine: Affine::translate((i as f64, 0.0)),
+                blend_mode: VelloBlendMode::default(),
+                opacity: 1.0,
+                item: square(i),
+            })
+            .collect();
+        let ui: Vec<_> = (64..96)
+            .map(|i| VelloUiRenderItem::Custom {
+                affine: Affine::IDENTITY,
+                clip: Some(vello::kurbo::Rect::new(0.0, 0.0, 5.0, 5.0)),
+                clip_shapes: Arc::new([UiClipShape {
+                    affine: Affine::rotate(0.3),
+                    shape: vello::kurbo::RoundedRect::new(-5.0, -5.0, 5.0, 5.0, 2.0),
+                }]),
+                blend_mode: VelloBlendMode::MULTIPLY,
+                opacity: 0.5,
+                item: square(i),
+            })
+            .collect();
+        let (sequential, parallel) = with_encoder(|encoder| {
+            let mut sequential = vello::Scene::new();
+            for item in &world {
+                encoder.world_item(&mut sequential, item);
+            }
+            for item in &ui {
+                encoder.ui_item(&mut sequential, item);
+            }
+            (sequential, encoder.encode(&world, &ui, 1.0))
+        });
+
+        let (sequential, parallel) = (sequential.encoding(), parallel.encoding());
+        assert_eq!(parallel.n_paths, sequential.n_paths);
+        assert_eq!(parallel.n_clips, sequential.n_clips);
+        assert_eq!(parallel.n_open_clips, 0);
+        // Draw data holds the colors, in drawing order
+        assert_eq!(parallel.draw_data, sequential.draw_data);
+    }
+
+    /// A group draws its items into a layer of their own even when it is opaque, unclipped and
+    /// normal, so a blending child blends with its siblings instead of what is below the group.
+    #[test]
+    fn groups_always_push_a_layer() {
+        let entity = Entity::from_raw_u32(1).unwrap();
+        let item = VelloWorldRenderItem::Group {
+            affine: Affine::IDENTITY,
+            item: ExtractedVelloGroup {
+                entity,
+                parent: None,
+                depth: 0,
+                group: VelloGroup::default(),
+                transform: GlobalTransform::IDENTITY,
+                sort_key: WorldSortKey::new(entity, &GlobalTransform::IDENTITY, None, false),
+                blend_mode: VelloBlendMode::default(),
+                alpha: 1.0,
+            },
+            items: vec![VelloWorldRenderItem::Custom {
+                affine: Affine::IDENTITY,
+                blend_mode: VelloBlendMode::MULTIPLY,
+                opacity: 1.0,
+                item: Arc::new(Square::new(1.0)),
+            }],
+        };
+
+        let mut scene = vello::Scene::new();
+        with_encoder(|encoder| encoder.world_item(&mut scene, &item));
+        let encoding = scene.encoding();
+        // The start and end of the group's layer and the child's blend layer
+        assert_eq!(encoding.n_clips, 4);
+        assert_eq!(encoding.n_open_clips, 0);
+    }
+
+    #[test]
+    fn clipped_items_pop_every_layer() {
+        let mut mask = vello::Scene::new();
+        Square::new(1.0).encode(&mut mask, Affine::IDENTITY);
+        let item = VelloWorldRenderItem::Clipped {
+            clips: Arc::new([
+                PreparedClip::Shape {
+                    affine: Affine::IDENTITY,
+                    shape: vello::kurbo::Circle::new((5.0, 5.0), 5.0).into_path(0.1),
+                },
+                PreparedClip::Mask {
+                    affine: Affine::translate((5.0, 0.0)),
+                    scene: Arc::new(mask),
+                    mode: VelloMaskMode::Luminance,
+                },
+            ]),
+            item: Box::new(VelloWorldRenderItem::Custom {
+                affine: Affine::IDENTITY,
+                blend_mode: VelloBlendMode::default(),
+                opacity: 1.0,
+                item: Arc::new(Square::new(1.0)),
+            }),
+        };
+
+        let mut scene = vello::Scene::new();
+        with_encoder(|encoder| encoder.world_item(&mut scene, &item));
+        let encoding = scene.encoding();
+        assert_eq!(encoding.n_open_clips, 0);
+        // The item and the mask, plus the start and end of the clip, mask content and mask layers
+        assert_eq!(encoding.n_paths, 8);
+    }
+
+    #[test]
+    fn blend_modes_draw_items_through_a_layer() {
+        // The blend mode and alpha of the layer pushed for an opaque item, if any
+        let push = |blend_mode: VelloBlendMode| {
+            let mut scene = vello::Scene::new();
+            let pushed = push_item_layer(&mut scene, blend_mode, 1.0, Affine::IDENTITY, None);
+            let draw_data = &scene.encoding().draw_data;
+            pushed.then(|| (draw_data[0], f32::from_bits(draw_data[1])))
+        };
+        // Like vello encodes blend modes in the layer's draw data
+        let encoded = |mode: VelloBlendMode| ((mode.0.mix as u32) << 8) | mode.0.compose as u32;
+
+        assert_eq!(push(VelloBlendMode::NORMAL), None);
+        for mode in [
+            VelloBlendMode::MULTIPLY,
+            VelloBlendMode::SCREEN,
+            VelloBlendMode::ADD,
+        ] {
+            assert_eq!(push(mode), Some((encoded(mode), 1.0)));
+        }
+
+        // And renderables pop the layer after the item
+        let mut scene = vello::Scene::new();
+        encode_renderable(
+            &mut scene,
+            &Square::new(1.0),
+            Affine::IDENTITY,
+            VelloBlendMode::MULTIPLY,
+            1.0,
+        );
+        assert_eq!(scene.encoding().n_clips, 2);
+        assert_eq!(scene.encoding().n_open_clips, 0);
+    }
+}
@@ -1,13 +1,965 @@
-use bevy::{prelude::*, render::extract_component::ExtractComponent};
+use std::sync::Arc;
+
+use bevy::{
+    camera::visibility::RenderLayers,
+    ecs::system::SystemParam,
+    prelude::*,
+    render::{Extract, sync_world::TemporaryRenderEntity},
+};
+use vello::kurbo::{Affine, BezPath, RoundedRect, RoundedRectRadii};
+
+use super::{
+    VelloBlendMode, VelloCanvasSettings, VelloClip, VelloClipSource, VelloFrameChanges, VelloGroup,
+    VelloMaskMode, VelloOpacity, VelloTextureTarget, VelloView, VelloViewSettings, VelloZIndex,
+    WorldSortKey, systems::to_kurbo_clip,
+};
+use crate::integrations::scene::VelloScene2d;
+#[cfg(feature = "svg")]
+use crate::integrations::svg::{VelloSvg, VelloSvg2d, VelloSvgAnchor};
 
 #[derive(SystemSet, Debug, Hash, PartialEq, Eq, Clone)]
 pub enum VelloExtractStep {
+    // Extract the views Vello renders into, e.g. cameras and texture targets
+    ExtractViews,
     // Extract renderable types, e.g. SVG, Lottie, Text, Scenes
     ExtractAssets,
     // Measure frame
     RunDiagnostics,
 }
 
-/// A screenspace render target. We use a resizable fullscreen quad.
-#[derive(Component, Default, Clone, ExtractComponent)]
-pub struct VelloRenderTarget(pub Handle<Image>);
+/// The canvas textures a [`VelloView`] renders into, one per canvas z layer, lowest first.
+/// Inserted on the camera entity.
+#[derive(Component, Default, Clone)]
+pub struct VelloRenderTarget(pub Vec<Handle<Image>>);
+
+/// A screenspace canvas for a single [`VelloView`]. We use a resizable fullscreen quad, only drawn
+/// by the camera it belongs to.
+#[derive(Component, Clone, Copy)]
+pub struct VelloCanvas {
+    /// The camera entity this canvas belongs to.
+    pub view: Entity,
+    /// The canvas z layer this canvas draws.
+    pub layer: usize,
+}
+
+/// A view Vello renders into this frame: either a [`VelloView`] camera, or a
+/// [`VelloTextureTarget`].
+#[derive(Component, Clone)]
+pub struct ExtractedVelloView {
+    /// The main world camera entity, or `None` for a texture target.
+    pub camera: Option<Entity>,
+    /// The images rendered into, one per canvas z layer, lowest first.
+    pub targets: Vec<Handle<Image>>,
+    /// The size of the viewport in physical pixels. Items are placed in this space, then scaled
+    /// by the render scale.
+    pub size: UVec2,
+    pub world_from_view: GlobalTransform,
+    pub clip_from_view: Mat4,
+    pub render_layers: RenderLayers,
+    pub settings: VelloViewSettings,
+    /// The ratio of target pixels to viewport pixels.
+    pub render_scale: f32,
+    /// Whether the view itself changed since the last frame, e.g. it moved, so its targets must
+    /// be redrawn even if no item changed.
+    pub changed: bool,
+}
+
+pub fn extract_views(
+    mut commands: Commands,
+    cameras: Extract<
+        Query<
+            (
+                Entity,
+                Ref<Camera>,
+                Ref<GlobalTransform>,
+                Ref<VelloRenderTarget>,
+                Option<Ref<RenderLayers>>,
+                Option<Ref<VelloViewSettings>>,
+            ),
+            With<VelloView>,
+        >,
+    >,
+    texture_targets: Extract<
+        Query<(
+            Ref<VelloTextureTarget>,
+            Ref<GlobalTransform>,
+            Option<Ref<RenderLayers>>,
+            Option<Ref<VelloViewSettings>>,
+        )>,
+    >,
+    images: Extract<Res<Assets<Image>>>,
+    mut frame_changes: ResMut<VelloFrameChanges>,
+) {
+    // Item extractors flag changes after this
+    frame_changes.items_changed = false;
+
+    for (camera_entity, camera, transform, render_target, render_layers, settings) in cameras.iter()
+    {
+        if !camera.is_active {
+            continue;
+        }
+        let Some(size) = camera.physical_viewport_size() else {
+            continue;
+        };
+        let view_settings = settings.as_deref().cloned().unwrap_or_default();
+        let render_scale = view_settings.effective_render_scale();
+        commands
+            .spawn(ExtractedVelloView {
+                camera: Some(camera_entity),
+                targets: render_target.0.clone(),
+                size,
+                world_from_view: *transform,
+                clip_from_view: camera.clip_from_view(),
+                render_layers: render_layers.as_deref().cloned().unwrap_or_default(),
+                settings: view_settings,
+                render_scale,
+                changed: camera.is_changed()
+                    || transform.is_changed()
+                    || render_target.is_changed()
+                    || render_layers.is_some_and(|layers| layers.is_changed())
+                    || settings.is_some_and(|settings| settings.is_changed()),
+            })
+            .insert(TemporaryRenderEntity);
+    }
+
+    for (texture_target, transform, render_layers, settings) in texture_targets.iter() {
+        if !texture_target.render_this_frame {
+            continue;
+        }
+        let Some(image) = images.get(texture_target.image.id()) else {
+            continue;
+        };
+        let size = image.size();
+        let (half_width, half_height) = (size.x as f32 / 2.0, size.y as f32 / 2.0);
+        commands
+            .spawn(ExtractedVelloView {
+                camera: None,
+                targets: vec![texture_target.image.clone()],
+                size,
+                world_from_view: *transform,
+                clip_from_view: Mat4::orthographic_rh(
+                    -half_width,
+                    half_width,
+                    -half_height,
+                    half_height,
+                    1000.0,
+                    -1000.0,
+                ),
+                render_layers: render_layers.as_deref().cloned().unwrap_or_default(),
+                settings: settings.as_deref().cloned().unwrap_or_default(),
+                render_scale: 1.0,
+                changed: texture_target.is_changed()
+                    || texture_target.redraw_this_frame
+                    || transform.is_changed()
+                    || render_layers.is_some_and(|layers| layers.is_changed())
+                    || settings.is_some_and(|settings| settings.is_changed()),
+            })
+            .insert(TemporaryRenderEntity);
+    }
+}
+
+/// The render layers of every view that may draw an item this frame, used to skip extracting
+/// items nothing will draw.
+#[derive(SystemParam)]
+pub struct VelloViewLayers<'w, 's> {
+    cameras: Extract<
+        'w,
+        's,
+        Query<'static, 'static, (&'static Camera, Option<&'static RenderLayers>), With<VelloView>>,
+    >,
+    texture_targets: Extract<
+        'w,
+        's,
+        Query<'static, 'static, (&'static VelloTextureTarget, Option<&'static RenderLayers>)>,
+    >,
+}
+
+/// Where items sit in the hierarchy: the opacity they inherit, the [`VelloGroup`] they are drawn
+/// in, and where world items sort.
+#[derive(SystemParam)]
+pub struct VelloHierarchy<'w, 's> {
+    entities: Extract<
+        'w,
+        's,
+        Query<
+            'static,
+            'static,
+            (
+                Option<&'static VelloOpacity>,
+                Option<&'static ChildOf>,
+                Has<VelloGroup>,
+                Has<VelloClip>,
+                Option<&'static VelloZIndex>,
+            ),
+        >,
+    >,
+    settings: Res<'w, VelloCanvasSettings>,
+}
+
+impl VelloHierarchy<'_, '_> {
+    /// The opacity of `entity`, the product of its own and its ancestors' [`VelloOpacity`] up to
+    /// its group, which applies its own opacity to the group as a whole.
+    pub fn opacity(&self, entity: Entity) -> f32 {
+        let mut opacity = 1.0;
+        let mut next = Some(entity);
+        while let Some(entity) = next {
+            let Ok((own, parent, ..)) = self.entities.get(entity) else {
+                break;
+            };
+            opacity *= own.copied().map_or(1.0, VelloOpacity::effective);
+            if opacity <= 0.0 {
+                return 0.0;
+            }
+            next = parent
+                .map(ChildOf::parent)
+                .filter(|parent| !self.is_group(*parent));
+        }
+        opacity
+    }
+
+    /// The nearest [`VelloGroup`] ancestor of `entity`, which it is drawn in.
+    pub fn group(&self, entity: Entity) -> Option<Entity> {
+        let mut next = self.parent(entity);
+        while let Some(entity) = next {
+            if self.is_group(entity) {
+                return Some(entity);
+            }
+            next = self.parent(entity);
+        }
+        None
+    }
+
+    /// The nearest [`VelloClip`] on `entity` or its ancestors, which it is clipped by.
+    pub fn clip(&self, entity: Entity) -> Option<Entity> {
+        let mut next = Some(entity);
+        while let Some(entity) = next {
+            if self
+                .entities
+                .get(entity)
+                .is_ok_and(|(_, _, _, is_clip, _)| is_clip)
+            {
+                return Some(entity);
+            }
+            next = self.parent(entity);
+        }
+        None
+    }
+
+    /// The key world item `entity` at `transform` is sorted by, see [`VelloZIndex`].
+    pub fn sort_key(&self, entity: Entity, transform: &GlobalTransform) -> WorldSortKey {
+        let z_index = self
+            .entities
+            .get(entity)
+            .ok()
+            .and_then(|(.., z_index)| z_index);
+        WorldSortKey::new(entity, transform, z_index, self.settings.y_sort)
+    }
+
+    /// The number of [`VelloGroup`] ancestors of `entity`.
+    fn group_depth(&self, entity: Entity) -> u32 {
+        std::iter::successors(self.group(entity), |group| self.group(*group)).count() as u32
+    }
+
+    fn parent(&self, entity: Entity) -> Option<Entity> {
+        let (_, parent, ..) = self.entities.get(entity).ok()?;
+        parent.map(ChildOf::parent)
+    }
+
+    fn is_group(&self, entity: Entity) -> bool {
+        self.entities
+            .get(entity)
+            .is_ok_and(|(_, _, is_group, ..)| is_group)
+    }
+}
+
+/// Redraws when the hierarchy changes, as items don't see their ancestors' opacity, groups or
+/// clips changing. Also redraws when z indices change.
+#[allow(clippy::type_complexity)]
+pub fn extract_hierarchy_changes(
+    changed: Extract<
+        Query<
+            (),
+            Or<(
+                Changed<VelloOpacity>,
+                Changed<VelloGroup>,
+                Changed<VelloClip>,
+                Changed<VelloZIndex>,
+                Changed<ChildOf>,
+            )>,
+        >,
+    >,
+    mut removed_opacities: Extract<RemovedComponents<VelloOpacity>>,
+    mut removed_groups: Extract<RemovedComponents<VelloGroup>>,
+    mut removed_clips: Extract<RemovedComponents<VelloClip>>,
+    mut removed_z_indices: Extract<RemovedComponents<VelloZIndex>>,
+    changed_nodes: Extract<Query<&Node, Changed<ComputedNode>>>,
+    mut frame_changes: ResMut<VelloFrameChanges>,
+) {
+    let n_removed = removed_opacities.read().count()
+        + removed_groups.read().count()
+        + removed_clips.read().count()
+        + removed_z_indices.read().count();
+    // Items don't see the shape of the nodes clipping them changing
+    let clips_changed = changed_nodes.iter().any(|node| !node.overflow.is_visible());
+    if !changed.is_empty() || n_removed > 0 || clips_changed {
+        frame_changes.items_changed = true;
+    }
+}
+
+/// The exact clip shape of a rounded or rotated UI node.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct UiClipShape {
+    /// Places the shape in physical pixels.
+    pub affine: Affine,
+    /// The clip rect of the node, centered on it.
+    pub shape: RoundedRect,
+}
+
+/// The clip shapes of a UI item, outermost last.
+pub type UiClipShapes = Arc<[UiClipShape]>;
+
+/// How UI items are clipped by their ancestors' [`Overflow`].
+#[derive(SystemParam)]
+pub struct VelloUiClips<'w, 's> {
+    nodes: Extract<
+        'w,
+        's,
+        Query<
+            'static,
+            'static,
+            (
+                &'static Node,
+                &'static ComputedNode,
+                &'static UiGlobalTransform,
+                Has<OverrideClip>,
+                Option<&'static ChildOf>,
+            ),
+        >,
+    >,
+}
+
+impl VelloUiClips<'_, '_> {
+    /// The clip rect of a UI item, and the shapes it is clipped to within it.
+    ///
+    /// [`CalculatedClip`] only holds axis-aligned rects, so items inside nodes with rounded
+    /// corners or rotation are clipped to the exact shape of those nodes instead, like native UI
+    /// content.
+    pub fn get(
+        &self,
+        entity: Entity,
+        calculated: Option<&CalculatedClip>,
+    ) -> (Option<Rect>, UiClipShapes) {
+        let clip = calculated.map(|calculated| calculated.clip);
+        // Nothing is drawn inside hidden nodes, which are clipped to an empty rect
+        if clip.is_none_or(|clip| clip.is_empty())
+            || !self
+                .clipping_ancestors(entity)
+                .any(|(node, computed_node, transform)| {
+                    is_rotated(transform) || has_rounded_clip(node, computed_node)
+                })
+        {
+            return (clip, UiClipShapes::default());
+        }
+
+        let mut clip = None;
+        let mut shapes = Vec::new();
+        for (node, computed_node, transform) in self.clipping_ancestors(entity) {
+            let node_clip = computed_node
+                .resolve_clip_rect(node.overflow, node.overflow_clip_margin)
+                .inflate(
+                    node.overflow_clip_margin.margin.max(0.0)
+                        / computed_node.inverse_scale_factor(),
+                );
+            if is_rotated(transform) || has_rounded_clip(node, computed_node) {
+                let Some(rect) = to_kurbo_clip(Some(node_clip)) else {
+                    continue;
+                };
+                let [a, b, c, d] = transform.matrix2.to_cols_array().map(f64::from);
+                let translation = transform.translation.as_dvec2();
+                shapes.push(UiClipShape {
+                    affine: Affine::new([a, b, c, d, translation.x, translation.y]),
+                    shape: RoundedRect::from_rect(rect, clip_radii(node, computed_node)),
+                });
+            }
+            // Rotated nodes are only clipped by their shape, as their bounding rect would cut
+            // their corners
+            if !is_rotated(transform) {
+                let node_clip = Rect {
+                    min: node_clip.min + transform.translation,
+                    max: node_clip.max + transform.translation,
+                };
+                clip = Some(clip.map_or(node_clip, |clip: Rect| clip.intersect(node_clip)));
+            }
+        }
+        (clip, shapes.into())
+    }
+
+    /// The ancestors of a UI item whose [`Overflow`] clips it, innermost first.
+    fn clipping_ancestors(
+        &self,
+        entity: Entity,
+    ) -> impl Iterator<Item = (&Node, &ComputedNode, &UiGlobalTransform)> {
+        let parent = |entity: Entity| {
+            let (.., override_clip, parent) = self.nodes.get(entity).ok()?;
+            // Nodes with `OverrideClip` ignore the clips of their ancestors
+            (!override_clip).then_some(parent?.parent())
+        };
+        std::iter::successors(parent(entity), move |entity| parent(*entity))
+            .filter_map(|entity| self.nodes.get(entity).ok())
+            .filter(|(node, ..)| !node.overflow.is_visible())
+            .map(|(node, computed_node, transform, ..)| (node, computed_node, transform))
+    }
+}
+
+fn is_rotated(transform: &UiGlobalTransform) -> bool {
+    transform.matrix2.x_axis.y != 0.0 || transform.matrix2.y_axis.x != 0.0
+}
+
+/// Whether a node clips both axes and has rounded corners. Nodes clipping a single axis are
+/// unbounded along the other, leaving no corners to round.
+fn has_rounded_clip(node: &Node, computed_node: &ComputedNode) -> bool {
+    let radius = computed_node.border_radius();
+    node.overflow.x != OverflowAxis::Visible
+        && node.overflow.y != OverflowAxis::Visible
+        && [
+            radius.top_left,
+            radius.top_right,
+            radius.bottom_right,
+            radius.bottom_left,
+        ]
+        .iter()
+        .any(|radius| *radius > 0.0)
+}
+
+/// The corner radii of a node's clip rect, reduced by the border and padding inside the clip
+/// box, like CSS inner radii.
+pub(crate) fn clip_radii(node: &Node, computed_node: &ComputedNode) -> RoundedRectRadii {
+    if !has_rounded_clip(node, computed_node) {
+        return RoundedRectRadii::from_single_radius(0.0);
+    }
+    let inset = match node.overflow_clip_margin.visual_box {
+        OverflowClipBox::BorderBox => BorderRect::ZERO,
+        OverflowClipBox::PaddingBox => computed_node.border(),
+        OverflowClipBox::ContentBox => computed_node.content_inset(),
+    };
+    let (min, max) = (inset.min_inset, inset.max_inset);
+    let radius = computed_node.border_radius();
+    let corner = |radius: f32, x: f32, y: f32| (radius - x.max(y)).max(0.0) as f64;
+    RoundedRectRadii::new(
+        corner(radius.top_left, min.x, min.y),
+        corner(radius.top_right, max.x, min.y),
+        corner(radius.bottom_right, max.x, max.y),
+        corner(radius.bottom_left, min.x, max.y),
+    )
+}
+
+/// A world [`VelloGroup`], extracted for one frame.
+#[derive(Component, Clone)]
+pub struct ExtractedVelloGroup {
+    /// The main world group entity, which its items refer to.
+    pub entity: Entity,
+    /// The group this group is drawn in.
+    pub parent: Option<Entity>,
+    /// The number of groups this group is nested in, as nested groups are folded first.
+    pub depth: u32,
+    pub group: VelloGroup,
+    pub transform: GlobalTransform,
+    pub sort_key: WorldSortKey,
+    pub blend_mode: VelloBlendMode,
+    pub alpha: f32,
+}
+
+/// A UI [`VelloGroup`], extracted for one frame.
+#[derive(Component, Clone)]
+pub struct ExtractedUiVelloGroup {
+    /// The main world group entity, which its items refer to.
+    pub entity: Entity,
+    /// The group this group is drawn in.
+    pub parent: Option<Entity>,
+    /// The number of groups this group is nested in, as nested groups are folded first.
+    pub depth: u32,
+    pub group: VelloGroup,
+    pub ui_transform: UiGlobalTransform,
+    pub ui_node: ComputedNode,
+    pub ui_render_target: ComputedUiRenderTargetInfo,
+    pub blend_mode: VelloBlendMode,
+    pub alpha: f32,
+    pub clip: Option<Rect>,
+    pub clip_shapes: UiClipShapes,
+}
+
+#[allow(clippy::type_complexity)]
+pub fn extract_groups(
+    mut commands: Commands,
+    hierarchy: VelloHierarchy,
+    ui_clips: VelloUiClips,
+    world_groups: Extract<
+        Query<
+            (
+                Entity,
+                &VelloGroup,
+                &GlobalTransform,
+                &InheritedVisibility,
+                Option<&VelloBlendMode>,
+            ),
+            Without<Node>,
+        >,
+    >,
+    ui_groups: Extract<
+        Query<(
+            Entity,
+            &VelloGroup,
+            &ComputedNode,
+            &ComputedUiRenderTargetInfo,
+            &UiGlobalTransform,
+            &InheritedVisibility,
+            Option<&CalculatedClip>,
+            Option<&VelloBlendMode>,
+        )>,
+    >,
+) {
+    // Groups are drawn by whichever views draw their items, so only visibility is checked here
+    for (entity, group, transform, inherited_visibility, blend_mode) in world_groups.iter() {
+        if !inherited_visibility.get() {
+            continue;
+        }
+        commands.spawn((
+            ExtractedVelloGroup {
+                entity,
+                parent: hierarchy.group(entity),
+                depth: hierarchy.group_depth(entity),
+                group: group.clone(),
+                transform: *transform,
+                sort_key: hierarchy.sort_key(entity, transform),
+                blend_mode: blend_mode.copied().unwrap_or_default(),
+                alpha: hierarchy.opacity(entity),
+            },
+            TemporaryRenderEntity,
+        ));
+    }
+
+    for (
+        entity,
+        group,
+        ui_node,
+        ui_render_target,
+        ui_transform,
+        inherited_visibility,
+        calc_clip,
+        blend_mode,
+    ) in ui_groups.iter()
+    {
+        if !inherited_visibility.get() {
+            continue;
+        }
+        let (clip, clip_shapes) = ui_clips.get(entity, calc_clip);
+        commands.spawn((
+            ExtractedUiVelloGroup {
+                entity,
+                parent: hierarchy.group(entity),
+                depth: hierarchy.group_depth(entity),
+                group: group.clone(),
+                ui_transform: *ui_transform,
+                ui_node: *ui_node,
+                ui_render_target: *ui_render_target,
+                blend_mode: blend_mode.copied().unwrap_or_default(),
+                alpha: hierarchy.opacity(entity),
+                clip,
+                clip_shapes,
+            },
+            TemporaryRenderEntity,
+        ));
+    }
+}
+
+/// A [`VelloClip`], extracted for one frame.
+#[derive(Component, Clone)]
+pub struct ExtractedVelloClip {
+    /// The main world clipped entity, which items refer to.
+    pub entity: Entity,
+    /// The clip this clip is nested in.
+    pub parent: Option<Entity>,
+    pub transform: GlobalTransform,
+    pub source: ExtractedClipSource,
+}
+
+/// The shape or mask content of an [`ExtractedVelloClip`].
+#[derive(Clone)]
+pub enum ExtractedClipSource {
+    Shape(BezPath),
+    Mask {
+        scene: Arc<vello::Scene>,
+        /// Where the mask entity is.
+        transform: GlobalTransform,
+        /// Places the mask content relative to the mask entity, e.g. for SVG anchors.
+        offset: Affine,
+        mode: VelloMaskMode,
+    },
+}
+
+#[allow(clippy::type_complexity)]
+pub fn extract_world_clips(
+    mut commands: Commands,
+    hierarchy: VelloHierarchy,
+    clips: Extract<Query<(Entity, &VelloClip, &GlobalTransform), Without<Node>>>,
+    scene_masks: Extract<Query<(&VelloScene2d, &GlobalTransform)>>,
+    #[cfg(feature = "svg")] svg_masks: Extract<
+        Query<(&VelloSvg2d, &VelloSvgAnchor, &GlobalTransform)>,
+    >,
+    #[cfg(feature = "svg")] svgs: Extract<Res<Assets<VelloSvg>>>,
+) {
+    // Changes to mask entities are picked up by their own extraction, even while hidden
+    for (entity, clip, transform) in clips.iter() {
+        let source = match &clip.source {
+            VelloClipSource::Shape(shape) => ExtractedClipSource::Shape(shape.clone()),
+            VelloClipSource::Entity(mask) => {
+                // Items masked by content that isn't available yet are not drawn
+                let content = scene_masks
+                    .get(*mask)
+                    .ok()
+                    .map(|(scene, transform)| (scene.0.clone(), *transform, Affine::IDENTITY));
+                #[cfg(feature = "svg")]
+                let content = content.or_else(|| {
+                    let (svg, anchor, transform) = svg_masks.get(*mask).ok()?;
+                    let asset = svgs.get(svg.id())?;
+                    let anchor = anchor.content_position(asset.width, asset.height);
+                    let offset = Affine::translate((-anchor.x as f64, -anchor.y as f64));
+                    Some((asset.scene.clone(), *transform, offset))
+                });
+                let (scene, mask_transform, offset) = content.unwrap_or_default();
+                ExtractedClipSource::Mask {
+                    scene,
+                    transform: mask_transform,
+                    offset,
+                    mode: clip.mask_mode,
+                }
+            }
+        };
+        commands.spawn((
+            ExtractedVelloClip {
+                entity,
+                parent: hierarchy
+                    .parent(entity)
+                    .and_then(|parent| hierarchy.clip(parent)),
+                transform: *transform,
+                source,
+            },
+            TemporaryRenderEntity,
+        ));
+    }
+}
+
+impl VelloViewLayers<'_, '_> {
+    /// Whether a world item is drawn by any view. Texture targets ignore view visibility, as it is
+    /// only computed for cameras.
+    pub fn draws_world_item(
+        &self,
+        view_visibility: &ViewVisibility,
+        render_layers: &RenderLayers,
+    ) -> bool {
+        (view_visibility.get() && self.cameras_draw(render_layers))
+            || self.texture_targets.iter().any(|(target, target_layers)| {
+                target.render_this_frame
+                    && render_layers.intersects(target_layers.unwrap_or_default())
+            })
+    }
+
+    /// Whether a world item is drawn by any view, counting texture targets on the frames they
+    /// skip. Extractors count these items to detect added and removed items, so texture targets
+    /// that only render on some frames don't change the counts and redraw every other view.
+    pub fn may_draw_world_item(
+        &self,
+        view_visibility: &ViewVisibility,
+        render_layers: &RenderLayers,
+    ) -> bool {
+        (view_visibility.get() && self.cameras_draw(render_layers))
+            || self.texture_targets.iter().any(|(_, target_layers)| {
+                render_layers.intersects(target_layers.unwrap_or_default())
+            })
+    }
+
+    /// Whether a UI item is drawn by any view.
+    pub fn draws_ui_item(&self, render_layers: &RenderLayers) -> bool {
+        self.cameras_draw(render_layers)
+    }
+
+    fn cameras_draw(&self, render_layers: &RenderLayers) -> bool {
+        self.cameras.iter().any(|(camera, camera_layers)| {
+            camera.is_active && render_layers.intersects(camera_layers.unwrap_or_default())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::test_utils;
+    use bevy::{ecs::system::RunSystemOnce, render::MainWorld};
+
+    #[test]
+    fn ui_clip_radii_shrink_inside_borders() {
+        let node = Node {
+            overflow: Overflow::clip(),
+            ..default()
+        };
+        let computed_node = ComputedNode {
+            size: Vec2::new(100.0, 50.0),
+            border: BorderRect {
+                min_inset: Vec2::new(4.0, 2.0),
+                max_inset: Vec2::new(4.0, 12.0),
+            },
+            border_radius: ResolvedBorderRadius {
+                top_left: 10.0,
+                top_right: 3.0,
+                bottom_right: 10.0,
+                bottom_left: 0.0,
+            },
+            ..default()
+        };
+        // Clipped at the padding box by default, inside the border
+        let radii = clip_radii(&node, &computed_node);
+        assert_eq!(radii.top_left, 6.0);
+        assert_eq!(radii.top_right, 0.0);
+        assert_eq!(radii.bottom_right, 0.0);
+        assert_eq!(radii.bottom_left, 0.0);
+
+        let border_box = Node {
+            overflow_clip_margin: OverflowClipMargin::border_box(),
+            ..node.clone()
+        };
+        assert_eq!(clip_radii(&border_box, &computed_node).top_left, 10.0);
+
+        // A single clipped axis has no corners to round
+        let clip_x = Node {
+            overflow: Overflow::clip_x(),
+            ..node
+        };
+        assert_eq!(clip_radii(&clip_x, &computed_node).top_left, 0.0);
+    }
+
+    #[test]
+    fn opacity_multiplies_down_to_the_group() {
+        let mut main_world = MainWorld::default();
+        let group = main_world
+            .spawn((VelloGroup::default(), VelloOpacity(0.1)))
+            .id();
+        let parent = main_world.spawn((VelloOpacity(0.5), ChildOf(group))).id();
+        let child = main_world.spawn((VelloOpacity(0.5), ChildOf(parent))).id();
+        let unset = main_world.spawn(ChildOf(parent)).id();
+        let nan = main_world
+            .spawn((VelloOpacity(f32::NAN), ChildOf(parent)))
+            .id();
+        let negative = main_world
+            .spawn((VelloOpacity(f32::NEG_INFINITY), ChildOf(parent)))
+            .id();
+        let mut render_world = test_utils::render_world(main_world);
+
+        let opacities = render_world
+            .run_system_once(move |hierarchy: VelloHierarchy| {
+                [child, unset, nan, negative, parent, group].map(|entity| hierarchy.opacity(entity))
+            })
+            .unwrap();
+        // The group's own opacity applies to the group as a whole, not to its items.
+        assert_eq!(opacities, [0.25, 0.5, 0.5, 0.0, 0.5, 0.1]);
+    }
+
+    /// Runs `extract` after applying `change` to the main world, like a frame of the app, and
+    /// returns whether items and views were flagged as changed.
+    fn extract_frame(
+        render_world: &mut World,
+        extract: &mut Schedule,
+        change: &dyn Fn(&mut World),
+    ) -> (bool, bool) {
+        let mut main_world = render_world.resource_mut::<MainWorld>();
+        main_world.increment_change_tick();
+        change(&mut main_world);
+        extract.run(render_world);
+        let views_changed = render_world
+            .query::<&ExtractedVelloView>()
+            .iter(render_world)
+            .any(|view| view.changed);
+        // Like the render world's temporary entities at the end of a frame
+        render_world.clear_entities();
+        (
+            render_world.resource::<VelloFrameChanges>().items_changed,
+            views_changed,
+        )
+    }
+
+    #[test]
+    fn extraction_only_flags_redraws_for_changes() {
+        let mut main_world = MainWorld::default();
+        let mut images = Assets::<Image>::default();
+        let image = images.add(VelloTextureTarget::new_image(64, 64));
+        main_world.insert_resource(images);
+        // Renders on some frames only, like a timer or on demand target
+        let texture_target = main_world.spawn(VelloTextureTarget::new(image)).id();
+        let camera = test_utils::camera(UVec2::splat(100));
+        let view = main_world
+            .spawn((VelloView, camera, VelloRenderTarget::default()))
+            .id();
+        let scene = main_world
+            .spawn((VelloScene2d::new(), InheritedVisibility::VISIBLE))
+            .id();
+        #[cfg(feature = "lottie")]
+        let lottie = {
+            use crate::prelude::{Playhead, VelloLottie, VelloLottie2d};
+            main_world.init_resource::<Assets<VelloLottie>>();
+            main_world.init_resource::<Messages<AssetEvent<VelloLottie>>>();
+            main_world
+                .spawn((VelloLottie2d::default(), Playhead::new(0.0)))
+                .id()
+        };
+        #[cfg(feature = "text")]
+        let text = {
+            use crate::prelude::{VelloFont, VelloText2d};
+            main_world.init_resource::<Assets<VelloFont>>();
+            main_world.init_resource::<Messages<AssetEvent<VelloFont>>>();
+            main_world.spawn(VelloText2d::default()).id()
+        };
+
+        let mut render_world = test_utils::render_world(main_world);
+        let mut extract = Schedule::default();
+        extract.add_systems(
+            (
+                extract_views,
+                extract_hierarchy_changes,
+                crate::integrations::scene::render::extract_world_scenes,
+            )
+                .chain(),
+        );
+        #[cfg(feature = "lottie")]
+        {
+            render_world.init_resource::<crate::integrations::lottie::render::RecoloredLotties>();
+            extract.add_systems(
+                crate::integrations::lottie::render::extract_world_lottie_assets
+                    .after(extract_views),
+            );
+        }
+        #[cfg(feature = "text")]
+        extract.add_systems(
+            crate::integrations::text::render::extract_world_text.after(extract_views),
+        );
+
+        // Everything is new on the first frame
+        assert_eq!(
+            extract_frame(&mut render_world, &mut extract, &|_| {}),
+            (true, true)
+        );
+
+        // Each change, with whether it redraws items or only its view
+        #[cfg_attr(not(any(feature = "lottie", feature = "text")), allow(unused_mut))]
+        let mut changes: Vec<(&str, Box<dyn Fn(&mut World)>, (bool, bool))> = vec![
+            (
+                "transform",
+                Box::new(move |world| {
+                    *world.get_mut::<GlobalTransform>(scene).unwrap() =
+                        GlobalTransform::from_xyz(1.0, 0.0, 0.0);
+                }),
+                (true, false),
+            ),
+            (
+                "scene",
+                Box::new(move |world| world.get_mut::<VelloScene2d>(scene).unwrap().reset()),
+                (true, false),
+            ),
+            (
+                "opacity",
+                Box::new(move |world| {
+                    world.entity_mut(scene).insert(VelloOpacity(0.5));
+                }),
+                (true, false),
+            ),
+            (
+                "view settings",
+                Box::new(move |world| {
+                    world.entity_mut(view).insert(VelloViewSettings {
+                        base_color: Color::BLACK,
+                        ..default()
+                    });
+                }),
+                (false, true),
+            ),
+            (
+                "texture target rendering",
+                Box::new(move |world| {
+                    world
+                        .get_mut::<VelloTextureTarget>(texture_target)
+                        .unwrap()
+                        .render_this_frame = true;
+                }),
+                (false, true),
+            ),
+            (
+                "texture target skipping",
+                Box::new(move |world| {
+                    world
+                        .get_mut::<VelloTextureTarget>(texture_target)
+                        .unwrap()
+                        .render_this_frame = false;
+                }),
+                (false, false),
+            ),
+        ];
+        #[cfg(feature = "lottie")]
+        changes.push((
+            "playhead",
+            Box::new(move |world| {
+                use crate::prelude::Playhead;
+                world.get_mut::<Playhead>(lottie).unwrap().seek(1.0);
+            }),
+            (true, false),
+        ));
+        #[cfg(feature = "text")]
+        changes.push((
+            "text",
+            Box::new(move |world| {
+                use crate::prelude::VelloText2d;
+                world.get_mut::<VelloText2d>(text).unwrap().value = "Changed".to_string();
+            }),
+            (true, false),
+        ));
+
+        for (name, change, redraws) in changes {
+            let unchanged = extract_frame(&mut render_world, &mut extract, &|_| {});
+            assert_eq!(unchanged, (false, false), "before changing the {name}");
+            let changed = extract_frame(&mut render_world, &mut extract, &change);
+            assert_eq!(changed, redraws, "after changing the {name}");
+        }
+    }
+
+    #[test]
+    fn texture_target_views_match_their_image() {
+        let mut main_world = MainWorld::default();
+        let mut images = Assets::<Image>::default();
+        let image = images.add(VelloTextureTarget::new_image(64, 32));
+        main_world.insert_resource(images);
+        let mut target = VelloTextureTarget::new(image.clone());
+        target.render_this_frame = true;
+        main_world.spawn((target, GlobalTransform::from_xyz(10.0, 20.0, 0.0)));
+        // Not rendered this frame
+        main_world.spawn(VelloTextureTarget::new(image.clone()));
+        let mut render_world = test_utils::render_world(main_world);
+        render_world.run_system_once(extract_views).unwrap();
+
+        let views: Vec<ExtractedVelloView> = render_world
+            .query::<&ExtractedVelloView>()
+            .iter(&render_world)
+            .cloned()
+            .collect();
+        let [view] = views.as_slice() else {
+            panic!("expected one view, got {}", views.len());
+        };
+        assert_eq!(view.camera, None);
+        assert_eq!(view.targets, [image]);
+        assert_eq!(view.size, UVec2::new(64, 32));
+        // One world unit per pixel, centered on the target
+        let corner = view.clip_from_view.project_point3(
+            view.world_from_view
+                .affine()
+                .inverse()
+                .transform_point3(Vec3::new(42.0, 36.0, 0.0)),
+        );
+        assert_eq!(corner.truncate(), Vec2::ONE);
+    }
+}
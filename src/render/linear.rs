@@ -0,0 +1,219 @@
+//! Rendering into float targets, which Vello can't render into directly.
+
+use bevy::{
+    asset::AssetId, image::Image, platform::collections::HashMap, prelude::*,
+    render::renderer::RenderDevice,
+};
+use vello::wgpu;
+
+/// Converts Vello's 8-bit sRGB output into float targets holding linear light.
+///
+/// Vello renders each float target into an intermediate 8-bit texture first, which is then drawn
+/// into the target with `vello_linear_convert.wgsl`.
+#[derive(Resource)]
+pub struct VelloLinearConverter {
+    pipelines: HashMap<wgpu::TextureFormat, wgpu::RenderPipeline>,
+    shader: wgpu::ShaderModule,
+    pipeline_layout: wgpu::PipelineLayout,
+    bind_group_layout: wgpu::BindGroupLayout,
+    intermediates: HashMap<AssetId<Image>, Intermediate>,
+}
+
+/// The intermediate texture of a float target.
+struct Intermediate {
+    view: wgpu::TextureView,
+    size: wgpu::Extent3d,
+    intensity: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+}
+
+impl FromWorld for VelloLinearConverter {
+    fn from_world(world: &mut World) -> Self {
+        let device = world.resource::<RenderDevice>().wgpu_device();
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("vello_linear_convert"),
+            source: wgpu::ShaderSource::Wgsl(
+                include_str!("../../shaders/vello_linear_convert.wgsl").into(),
+            ),
+        });
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("vello_linear_convert"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("vello_linear_convert"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        Self {
+            pipelines: HashMap::default(),
+            shader,
+            pipeline_layout,
+            bind_group_layout,
+            intermediates: HashMap::default(),
+        }
+    }
+}
+
+impl VelloLinearConverter {
+    /// Whether Vello can't render into a texture of this format directly.
+    pub fn needs_conversion(format: wgpu::TextureFormat) -> bool {
+        format != wgpu::TextureFormat::Rgba8Unorm
+    }
+
+    /// Returns the intermediate texture Vello should render `target` into, (re)creating it if the
+    /// target was resized.
+    pub fn intermediate(
+        &mut self,
+        device: &wgpu::Device,
+        target: AssetId<Image>,
+        size: wgpu::Extent3d,
+    ) -> &wgpu::TextureView {
+        if self
+            .intermediates
+            .get(&target)
+            .is_none_or(|intermediate| intermediate.size != size)
+        {
+            let texture = device.create_texture(&wgpu::TextureDescriptor {
+                label: Some("vello_linear_intermediate"),
+                size,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Rgba8Unorm,
+                usage: wgpu::TextureUsages::STORAGE_BINDING | wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            });
+            let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+            let intensity = device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("vello_linear_intensity"),
+                // A vec4, for uniform buffer alignment
+                size: 16,
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            });
+            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("vello_linear_convert"),
+                layout: &self.bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: intensity.as_entire_binding(),
+                    },
+                ],
+            });
+            self.intermediates.insert(
+                target,
+                Intermediate {
+                    view,
+                    size,
+                    intensity,
+                    bind_group,
+                },
+            );
+        }
+        &self.intermediates[&target].view
+    }
+
+    /// Draws the intermediate texture of `target` into it, converting to linear light and scaling
+    /// by `intensity`.
+    pub fn convert(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        target: AssetId<Image>,
+        target_view: &wgpu::TextureView,
+        target_format: wgpu::TextureFormat,
+        intensity: f32,
+    ) {
+        let Some(intermediate) = self.intermediates.get(&target) else {
+            return;
+        };
+        let pipeline = self.pipelines.entry(target_format).or_insert_with(|| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("vello_linear_convert"),
+                layout: Some(&self.pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &self.shader,
+                    entry_point: Some("vertex"),
+                    compilation_options: default(),
+                    buffers: &[],
+                },
+                primitive: default(),
+                depth_stencil: None,
+                multisample: default(),
+                fragment: Some(wgpu::FragmentState {
+                    module: &self.shader,
+                    entry_point: Some("fragment"),
+                    compilation_options: default(),
+                    targets: &[Some(target_format.into())],
+                }),
+                multiview: None,
+                cache: None,
+            })
+        });
+
+        queue.write_buffer(
+            &intermediate.intensity,
+            0,
+            &[intensity, 0.0, 0.0, 0.0]
+                .into_iter()
+                .flat_map(f32::to_le_bytes)
+                .collect::<Vec<_>>(),
+        );
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("vello_linear_convert"),
+        });
+        {
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("vello_linear_convert"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: target_view,
+                    depth_slice: None,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            pass.set_pipeline(pipeline);
+            pass.set_bind_group(0, &intermediate.bind_group, &[]);
+            pass.draw(0..3, 0..1);
+        }
+        queue.submit([encoder.finish()]);
+    }
+
+    /// Drops the intermediate textures of targets that no longer exist.
+    pub fn retain_targets(&mut self, mut keep: impl FnMut(AssetId<Image>) -> bool) {
+        self.intermediates.retain(|target, _| keep(*target));
+    }
+}
@@ -1,49 +1,488 @@
 //! Components and logic for rendering.
 
-use std::sync::{Arc, Mutex};
+use std::{
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
 
 use bevy::{
     asset::uuid_handle,
     camera::visibility::RenderLayers,
+    ecs::entity::EntityHashMap,
     mesh::{MeshVertexBufferLayoutRef, VertexBufferLayout},
+    platform::collections::HashSet,
     prelude::*,
     render::{
         extract_component::ExtractComponent,
         render_resource::{
-            AsBindGroup, RenderPipelineDescriptor, SpecializedMeshPipelineError, VertexFormat,
-            VertexStepMode,
+            AsBindGroup, RenderPipelineDescriptor, SpecializedMeshPipelineError, TextureFormat,
+            VertexFormat, VertexStepMode,
         },
-        renderer::RenderDevice,
+        renderer::{RenderAdapterInfo, RenderDevice},
     },
     shader::ShaderRef,
-    sprite_render::{Material2d, Material2dKey},
+    sprite_render::{AlphaMode2d, Material2d, Material2dKey},
+    tasks::{AsyncComputeTaskPool, Task},
+};
+use pipeline_cache::VelloPipelineCache;
+use thiserror::Error;
+use vello::{
+    AaConfig, AaSupport, RenderParams,
+    kurbo::{Affine, BezPath, Shape},
+    peniko::{BlendMode, Compose, Mix},
 };
-use vello::{AaConfig, AaSupport, kurbo::Affine};
 
 mod plugin;
 mod systems;
 
+pub(crate) mod encode;
 pub(crate) mod extract;
+pub(crate) mod linear;
+mod pipeline_cache;
 pub(crate) mod prepare;
+pub(crate) mod renderable;
+pub(crate) mod ui_canvas;
+
+#[cfg(test)]
+pub(crate) mod test_utils;
 
 pub(crate) use plugin::VelloRenderPlugin;
+pub use renderable::{VelloRenderable, VelloRenderableAppExt};
 
 pub mod diagnostics;
 
 /// A handle to the screen space render target shader.
 pub const RT_SHADER_HANDLE: Handle<Shader> = uuid_handle!("e7235b72-1181-4e18-a9f2-93b32026a820");
 
+/// A handle to the shader drawing UI canvases interleaved with native UI.
+pub const UI_CANVAS_SHADER_HANDLE: Handle<Shader> =
+    uuid_handle!("5b0c8a2e-3f61-4d7a-9c2e-8e4f1a6b7d93");
+
 /// A component that should be added to the camera that will render Vello assets.
+///
+/// Each camera with this component gets its own canvas texture, sized to the camera's viewport.
+/// Multiple views may be used at the same time, e.g. for split-screen or a minimap.
 #[derive(Component, Debug, Clone, Copy, ExtractComponent)]
 #[require(Camera2d)]
 pub struct VelloView;
 
+/// Render parameters for a single [`VelloView`] or [`VelloTextureTarget`], taking precedence over
+/// the global [`VelloRenderSettings`].
+///
+/// Unlike changing [`VelloRenderSettings`], changing these does not re-initialize the renderer.
+#[derive(Component, Debug, Clone, PartialEq)]
+pub struct VelloViewSettings {
+    /// The color the view is cleared to before rendering. A non-transparent color hides anything
+    /// drawn behind the canvas. When the canvas is split into z layers, only the lowest layer is
+    /// cleared to this color.
+    pub base_color: Color,
+    /// The antialiasing method, or `None` to use [`VelloRenderSettings::antialiasing`].
+    pub antialiasing: Option<AaConfig>,
+    /// The resolution of the canvas relative to the viewport, e.g. `0.5` renders at half
+    /// resolution and `2.0` supersamples. The canvas is resampled to the viewport when drawn.
+    ///
+    /// Non-positive or non-finite values are treated as `1.0`. Ignored by texture targets, whose
+    /// image size is the render resolution.
+    pub render_scale: f32,
+    /// The texture format of the view's canvas. Use [`VelloCanvasFormat::Rgba16Float`] with a
+    /// [`Hdr`](bevy::render::view::Hdr) camera to feed Vello content into tonemapping and bloom.
+    ///
+    /// Ignored by texture targets, which render in the format of their image.
+    pub canvas_format: VelloCanvasFormat,
+    /// Scales the linear-light color of float canvases and texture targets, e.g. above `1.0` to
+    /// make content glow with bloom. Ignored for [`VelloCanvasFormat::Rgba8Unorm`], which can't
+    /// hold values above `1.0`.
+    pub intensity: f32,
+}
+
+impl Default for VelloViewSettings {
+    fn default() -> Self {
+        Self {
+            base_color: Color::NONE,
+            antialiasing: None,
+            render_scale: 1.0,
+            canvas_format: VelloCanvasFormat::default(),
+            intensity: 1.0,
+        }
+    }
+}
+
+impl VelloViewSettings {
+    /// The render scale, falling back to `1.0` for unusable values.
+    pub(crate) fn effective_render_scale(&self) -> f32 {
+        if self.render_scale.is_finite() && self.render_scale > 0.0 {
+            self.render_scale
+        } else {
+            1.0
+        }
+    }
+
+    /// The parameters to render one of the view's targets of `width` by `height` with. Targets
+    /// are cleared to the base color if `clear` is set, and transparent otherwise.
+    pub(crate) fn render_params(
+        &self,
+        clear: bool,
+        width: u32,
+        height: u32,
+        render_settings: &VelloRenderSettings,
+    ) -> RenderParams {
+        let base_color = if clear {
+            let base_color = self.base_color.to_srgba();
+            vello::peniko::Color::new([
+                base_color.red,
+                base_color.green,
+                base_color.blue,
+                base_color.alpha,
+            ])
+        } else {
+            vello::peniko::Color::TRANSPARENT
+        };
+        RenderParams {
+            base_color,
+            width,
+            height,
+            antialiasing_method: self.antialiasing.unwrap_or(render_settings.antialiasing),
+        }
+    }
+}
+
+/// The texture format a canvas is rendered into.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum VelloCanvasFormat {
+    /// 8-bit sRGB-encoded color, which Vello renders into directly.
+    #[default]
+    Rgba8Unorm,
+    /// 16-bit float linear-light color. Vello renders into an intermediate 8-bit texture, which is
+    /// then converted to linear light and scaled by [`VelloViewSettings::intensity`].
+    Rgba16Float,
+}
+
+impl VelloCanvasFormat {
+    pub fn texture_format(self) -> TextureFormat {
+        match self {
+            Self::Rgba8Unorm => TextureFormat::Rgba8Unorm,
+            Self::Rgba16Float => TextureFormat::Rgba16Float,
+        }
+    }
+}
+
+/// Renders Vello world entities into a user-owned [`Image`], e.g. to put animated vector graphics
+/// on a 3D mesh.
+///
+/// The target acts like an orthographic 2D camera centered on its [`Transform`], with one world
+/// unit per pixel; scale the transform to zoom. World entities whose [`RenderLayers`] intersect the
+/// target's are drawn into it, even if no [`VelloView`] camera sees them. UI entities are never
+/// drawn into texture targets.
+///
+/// The image size is the render resolution. The image must either use [`TextureFormat::Rgba8Unorm`]
+/// and [`TextureUsages::STORAGE_BINDING`], receiving sRGB-encoded color, or
+/// [`TextureFormat::Rgba16Float`] and [`TextureUsages::RENDER_ATTACHMENT`], receiving linear-light
+/// color. See [`VelloTextureTarget::new_image`] and [`VelloTextureTarget::new_image_with_format`].
+///
+/// [`TextureUsages::STORAGE_BINDING`]: bevy::render::render_resource::TextureUsages::STORAGE_BINDING
+/// [`TextureUsages::RENDER_ATTACHMENT`]: bevy::render::render_resource::TextureUsages::RENDER_ATTACHMENT
+#[derive(Component, Clone, Debug)]
+#[require(Transform)]
+pub struct VelloTextureTarget {
+    /// The image to render into.
+    pub image: Handle<Image>,
+    /// When the image is re-rendered.
+    pub refresh: VelloTextureRefresh,
+    /// Whether a render was requested for the next frame.
+    requested: bool,
+    /// Whether the image is rendered this frame.
+    pub(crate) render_this_frame: bool,
+    /// Whether the image is redrawn this frame, even if nothing it shows changed.
+    pub(crate) redraw_this_frame: bool,
+}
+
+impl VelloTextureTarget {
+    /// Render into `image` every frame.
+    pub fn new(image: Handle<Image>) -> Self {
+        Self {
+            image,
+            refresh: VelloTextureRefresh::default(),
+            // Always render the first frame, regardless of the refresh mode.
+            requested: true,
+            render_this_frame: false,
+            redraw_this_frame: false,
+        }
+    }
+
+    pub fn with_refresh(mut self, refresh: VelloTextureRefresh) -> Self {
+        self.refresh = refresh;
+        self
+    }
+
+    /// Render the image on the next frame, regardless of the refresh mode and of whether anything
+    /// it shows changed.
+    pub fn request_render(&mut self) {
+        self.requested = true;
+    }
+
+    /// Creates an image suitable for a texture target.
+    pub fn new_image(width: u32, height: u32) -> Image {
+        Self::new_image_with_format(width, height, VelloCanvasFormat::Rgba8Unorm)
+    }
+
+    /// Creates an image suitable for a texture target, in the given format.
+    pub fn new_image_with_format(width: u32, height: u32, format: VelloCanvasFormat) -> Image {
+        systems::canvas_image(width, height, format)
+    }
+}
+
+/// When a [`VelloTextureTarget`] is re-rendered.
+#[derive(Clone, Debug, Default)]
+pub enum VelloTextureRefresh {
+    /// Render every frame.
+    #[default]
+    EveryFrame,
+    /// Render every time the timer finishes. Use a repeating timer.
+    Timer(Timer),
+    /// Only render when requested with [`VelloTextureTarget::request_render`].
+    OnDemand,
+}
+
+/// How an item is blended with the items drawn below it.
+///
+/// Works on scenes, SVGs, Lotties, text, their UI variants and [`VelloRenderable`] types. Items
+/// with a blend mode other than the default are drawn through their own layer, bounded by the
+/// asset size for SVGs, Lotties and [`VelloRenderable::bounds`], and unbounded otherwise. Keep
+/// in mind that compose modes such as [`Compose::Copy`] affect everything below the item within
+/// those bounds.
+#[derive(Component, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct VelloBlendMode(pub BlendMode);
+
+impl VelloBlendMode {
+    /// Blends colors normally and composes with source-over, the default.
+    pub const NORMAL: Self = Self(BlendMode::new(Mix::Normal, Compose::SrcOver));
+    /// Multiplies colors, darkening what is below.
+    pub const MULTIPLY: Self = Self(BlendMode::new(Mix::Multiply, Compose::SrcOver));
+    /// Inverts, multiplies and inverts colors, lightening what is below.
+    pub const SCREEN: Self = Self(BlendMode::new(Mix::Screen, Compose::SrcOver));
+    /// Adds colors, for glows and light effects.
+    pub const ADD: Self = Self(BlendMode::new(Mix::Normal, Compose::Plus));
+
+    pub const fn new(mix: Mix, compose: Compose) -> Self {
+        Self(BlendMode::new(mix, compose))
+    }
+
+    pub(crate) fn is_normal(self) -> bool {
+        self == Self::NORMAL
+    }
+}
+
+impl From<Mix> for VelloBlendMode {
+    fn from(mix: Mix) -> Self {
+        Self(mix.into())
+    }
+}
+
+impl From<Compose> for VelloBlendMode {
+    fn from(compose: Compose) -> Self {
+        Self(compose.into())
+    }
+}
+
+/// The opacity of an item and all its descendants, from `0.0` (invisible) to `1.0` (opaque).
+/// Other values are clamped into that range, and NaN counts as opaque.
+///
+/// Works on every item type, like [`VelloBlendMode`]. Opacities are multiplied down through the
+/// hierarchy, so a [`VelloOpacity`] on a UI panel fades everything inside it. Each item is faded on
+/// its own, so overlapping children of a faded parent show through each other, unless the parent
+/// is a [`VelloGroup`].
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct VelloOpacity(pub f32);
+
+impl Default for VelloOpacity {
+    fn default() -> Self {
+        Self(1.0)
+    }
+}
+
+impl VelloOpacity {
+    /// The opacity clamped to `0.0..=1.0`. NaN counts as opaque, like no opacity at all.
+    pub(crate) fn effective(self) -> f32 {
+        if self.0.is_nan() {
+            1.0
+        } else {
+            self.0.clamp(0.0, 1.0)
+        }
+    }
+}
+
+/// Sorts a world item at this z instead of its translation's, e.g. to keep an item above others
+/// however it moves. Also decides the canvas z layer the item is drawn into.
+///
+/// World items are sorted by z, then from the highest y down if [`VelloPlugin::y_sort`] is set,
+/// then by entity index, so items with equal keys never swap places between frames. UI items are
+/// sorted by Bevy's stack index instead.
+///
+/// [`VelloPlugin::y_sort`]: crate::VelloPlugin::y_sort
+#[derive(Component, Debug, Clone, Copy, Default, PartialEq)]
+pub struct VelloZIndex(pub f32);
+
+/// The draw order of a world item, see [`VelloZIndex`].
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct WorldSortKey {
+    pub z: f32,
+    /// The negated y when y-sorting, drawing lower items above higher ones, otherwise `0.0`.
+    pub y: f32,
+    /// The main world entity, breaking ties.
+    pub entity: Entity,
+}
+
+impl WorldSortKey {
+    pub fn new(
+        entity: Entity,
+        transform: &GlobalTransform,
+        z_index: Option<&VelloZIndex>,
+        y_sort: bool,
+    ) -> Self {
+        let translation = transform.translation();
+        Self {
+            z: z_index.map_or(translation.z, |z_index| z_index.0),
+            y: if y_sort { -translation.y } else { 0.0 },
+            entity,
+        }
+    }
+}
+
+impl Ord for WorldSortKey {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.z
+            .total_cmp(&other.z)
+            .then(self.y.total_cmp(&other.y))
+            .then(self.entity.index_u32().cmp(&other.entity.index_u32()))
+            .then(self.entity.cmp(&other.entity))
+    }
+}
+
+impl PartialOrd for WorldSortKey {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for WorldSortKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other).is_eq()
+    }
+}
+
+impl Eq for WorldSortKey {}
+
+/// Draws the descendants of an entity together, as one isolated layer.
+///
+/// Descendants sort as a unit at the group's z (world) or stack index (UI), and among each other
+/// by their own. The group's [`VelloOpacity`] and [`VelloBlendMode`] apply to the layer as a
+/// whole, so a fading group fades its children together instead of each on its own. The entity's
+/// own item, if it has one, is drawn as usual outside the layer.
+///
+/// A group only gathers items of its own kind: world items for a world entity, UI items for a UI
+/// node. Groups may be nested.
+#[derive(Component, Debug, Clone, Default)]
+pub struct VelloGroup {
+    /// Clips the group to a shape, like the entity's own content: y-down around the entity's
+    /// origin, or from the UI node's top-left corner in logical pixels.
+    pub clip: Option<BezPath>,
+}
+
+impl VelloGroup {
+    /// A group clipped to `shape`.
+    pub fn with_clip(shape: impl Shape) -> Self {
+        Self {
+            clip: Some(shape.into_path(0.1)),
+        }
+    }
+}
+
+/// Clips a world entity and its descendants to a shape, or masks them with another entity's
+/// content.
+///
+/// Clips nest: an item inside several clipped entities is clipped by all of them. Has no effect in
+/// UI nodes, which are clipped by [`Overflow`] instead.
+#[derive(Component, Debug, Clone)]
+pub struct VelloClip {
+    pub source: VelloClipSource,
+    /// How an entity source masks, ignored for shapes.
+    pub mask_mode: VelloMaskMode,
+}
+
+impl VelloClip {
+    /// Clips to `shape`, y-down around the entity's origin like its own content.
+    pub fn shape(shape: impl Shape) -> Self {
+        Self {
+            source: VelloClipSource::Shape(shape.into_path(0.1)),
+            mask_mode: VelloMaskMode::default(),
+        }
+    }
+
+    /// Masks with the alpha of `entity`'s [`VelloScene2d`](crate::prelude::VelloScene2d) or
+    /// `VelloSvg2d`, wherever that entity is.
+    pub fn mask(entity: Entity) -> Self {
+        Self {
+            source: VelloClipSource::Entity(entity),
+            mask_mode: VelloMaskMode::Alpha,
+        }
+    }
+
+    /// Masks with the luminance of `entity`'s [`VelloScene2d`](crate::prelude::VelloScene2d) or
+    /// `VelloSvg2d`, wherever that entity is.
+    pub fn luminance_mask(entity: Entity) -> Self {
+        Self {
+            source: VelloClipSource::Entity(entity),
+            mask_mode: VelloMaskMode::Luminance,
+        }
+    }
+}
+
+/// What a [`VelloClip`] clips to.
+#[derive(Debug, Clone)]
+pub enum VelloClipSource {
+    /// A shape, y-down around the clipped entity's origin.
+    Shape(BezPath),
+    /// The content of another entity's [`VelloScene2d`](crate::prelude::VelloScene2d) or
+    /// `VelloSvg2d`, drawn where that entity is. Hide the entity if it should only be used as a
+    /// mask. Masked items are not drawn until its content is available.
+    Entity(Entity),
+}
+
+/// How the content of an entity masks a [`VelloClip`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum VelloMaskMode {
+    /// Keeps what is below opaque parts of the mask.
+    #[default]
+    Alpha,
+    /// Keeps what is below bright parts of the mask, e.g. white for a spotlight.
+    Luminance,
+}
+
 /// A canvas material, with a shader that samples a texture with view-independent UV coordinates.
 #[derive(AsBindGroup, TypePath, Asset, Clone)]
+#[bind_group_data(VelloCanvasMaterialKey)]
 pub struct VelloCanvasMaterial {
     #[texture(0)]
     #[sampler(1)]
     pub texture: Handle<Image>,
+    /// Canvases split into z layers are blended in the transparent 2D phase, so they sort with
+    /// Bevy's sprites and meshes.
+    pub alpha_mode: AlphaMode2d,
+    /// The format of the texture, which decides whether it holds sRGB-encoded or linear color.
+    pub format: VelloCanvasFormat,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct VelloCanvasMaterialKey {
+    format: VelloCanvasFormat,
+}
+
+impl From<&VelloCanvasMaterial> for VelloCanvasMaterialKey {
+    fn from(material: &VelloCanvasMaterial) -> Self {
+        Self {
+            format: material.format,
+        }
+    }
 }
 
 impl Material2d for VelloCanvasMaterial {
@@ -51,6 +490,10 @@ impl Material2d for VelloCanvasMaterial {
         RT_SHADER_HANDLE.into()
     }
 
+    fn alpha_mode(&self) -> AlphaMode2d {
+        self.alpha_mode
+    }
+
     fn fragment_shader() -> ShaderRef {
         RT_SHADER_HANDLE.into()
     }
@@ -58,8 +501,14 @@ impl Material2d for VelloCanvasMaterial {
     fn specialize(
         descriptor: &mut RenderPipelineDescriptor,
         _layout: &MeshVertexBufferLayoutRef,
-        _key: Material2dKey<Self>,
+        key: Material2dKey<Self>,
     ) -> Result<(), SpecializedMeshPipelineError> {
+        if key.bind_group_data.format == VelloCanvasFormat::Rgba16Float
+            && let Some(fragment) = descriptor.fragment.as_mut()
+        {
+            fragment.shader_defs.push("LINEAR_CANVAS".into());
+        }
+
         // FIXME: Vello isn't obeying transparency on render_to_surface call.
         // See https://github.com/linebender/vello/issues/549
         if let Some(target) = descriptor.fragment.as_mut() {
@@ -91,6 +540,14 @@ impl VelloRenderer {
     pub fn try_new(
         device: &vello::wgpu::Device,
         settings: &VelloRenderSettings,
+    ) -> Result<Self, vello::Error> {
+        Self::try_new_with_cache(device, settings, None)
+    }
+
+    fn try_new_with_cache(
+        device: &vello::wgpu::Device,
+        settings: &VelloRenderSettings,
+        pipeline_cache: Option<&VelloPipelineCache>,
     ) -> Result<Self, vello::Error> {
         vello::Renderer::new(
             device,
@@ -100,46 +557,178 @@ impl VelloRenderer {
                 // initialization, so we need to use all support modes here instead.
                 antialiasing_support: AaSupport::all(),
                 num_init_threads: None,
-                pipeline_cache: None,
+                pipeline_cache: pipeline_cache.map(|cache| cache.cache().clone()),
             },
         )
         .map(Mutex::new)
         .map(Arc::new)
         .map(VelloRenderer)
     }
-}
 
-impl FromWorld for VelloRenderer {
-    fn from_world(world: &mut World) -> Self {
-        match VelloRenderer::try_new(
-            world.get_resource::<RenderDevice>().unwrap().wgpu_device(),
-            world.get_resource::<VelloRenderSettings>().unwrap(),
-        ) {
-            Ok(r) => r,
-            Err(e) => {
-                tracing::error!(
-                    "Attempting safe-mode fallback, failed to initialize renderer: {e:}"
-                );
-                {
-                    let mut settings = world.get_resource_mut::<VelloRenderSettings>().unwrap();
-                    settings.use_cpu = true;
-                    settings.antialiasing = AaConfig::Area;
-                }
-                match VelloRenderer::try_new(
-                    world.get_resource::<RenderDevice>().unwrap().wgpu_device(),
-                    world.get_resource::<VelloRenderSettings>().unwrap(),
-                ) {
-                    Ok(r) => r,
-                    Err(e) => panic!("Failed to start vello: {e}"),
-                }
-            }
+    /// Creates a renderer, falling back to a CPU renderer if the GPU renderer fails to initialize
+    /// and the policy allows it.
+    fn new_with_fallback(
+        device: &vello::wgpu::Device,
+        mut settings: VelloRenderSettings,
+        pipeline_cache: Option<&VelloPipelineCache>,
+        policy: &VelloRecoveryPolicy,
+    ) -> RendererInit {
+        let mut errors = Vec::new();
+        let mut try_new = |settings: &VelloRenderSettings| {
+            Self::try_new_with_cache(device, settings, pipeline_cache)
+                .inspect_err(|e| tracing::error!("Failed to initialize renderer: {e}"))
+                .map_err(|e| {
+                    errors.push(VelloRenderError::Init {
+                        use_cpu: settings.use_cpu,
+                        error: Arc::new(e),
+                    });
+                })
+                .ok()
+        };
+        let mut renderer = try_new(&settings);
+        if renderer.is_none() && policy.cpu_fallback && !settings.use_cpu {
+            tracing::warn!("Attempting safe-mode fallback to CPU rendering");
+            settings.use_cpu = true;
+            settings.antialiasing = AaConfig::Area;
+            renderer = try_new(&settings);
+        }
+        match (&renderer, pipeline_cache) {
+            (Some(_), Some(pipeline_cache)) => pipeline_cache.save(),
+            (None, _) => tracing::error!("Vello is disabled, no renderer could be initialized"),
+            _ => {}
         }
+        RendererInit {
+            renderer,
+            settings,
+            errors,
+        }
+    }
+}
+
+/// The outcome of creating a [`VelloRenderer`].
+pub(crate) struct RendererInit {
+    renderer: Option<VelloRenderer>,
+    /// The settings the renderer was created with, after any fallback.
+    settings: VelloRenderSettings,
+    errors: Vec<VelloRenderError>,
+}
+
+/// Starts creating the renderer for the current [`VelloRenderSettings`], in the background if
+/// [`VelloRenderSettings::background_init`] is set. Nothing is rendered until it's ready.
+pub(crate) fn init_renderer(world: &mut World) {
+    world.remove_resource::<VelloRenderer>();
+    let device = world.resource::<RenderDevice>().wgpu_device().clone();
+    let settings = world.resource::<VelloRenderSettings>().clone();
+    let policy = world.resource::<VelloRecoveryPolicy>().clone();
+    let pipeline_cache = settings.pipeline_cache_dir.as_deref().and_then(|dir| {
+        VelloPipelineCache::load(&device, &world.resource::<RenderAdapterInfo>().0, dir)
+    });
+
+    if settings.background_init {
+        let task = AsyncComputeTaskPool::get().spawn(async move {
+            VelloRenderer::new_with_fallback(&device, settings, pipeline_cache.as_ref(), &policy)
+        });
+        world.insert_resource(VelloRendererTask(task));
+    } else {
+        world.remove_resource::<VelloRendererTask>();
+        let init =
+            VelloRenderer::new_with_fallback(&device, settings, pipeline_cache.as_ref(), &policy);
+        insert_renderer(world, init);
+    }
+}
+
+/// Inserts a newly created renderer, keeping the settings it fell back to.
+pub(crate) fn insert_renderer(world: &mut World, init: RendererInit) {
+    let mut messages = world.resource_mut::<VelloRendererMessages>();
+    messages.errors.extend(init.errors);
+    let Some(renderer) = init.renderer else {
+        return;
+    };
+    messages.ready = true;
+    let mut settings = world.resource_mut::<VelloRenderSettings>();
+    if settings.use_cpu != init.settings.use_cpu {
+        // Don't re-initialize the renderer for its own fallback
+        *settings.bypass_change_detection() = init.settings;
     }
+    world.insert_resource(renderer);
 }
 
-#[derive(Resource, Deref, DerefMut, Default)]
-#[cfg(feature = "lottie")]
-pub struct VelatoRenderer(velato::Renderer);
+/// A [`VelloRenderer`] being created in the background.
+#[derive(Resource)]
+pub(crate) struct VelloRendererTask(pub Task<RendererInit>);
+
+/// Messages for the main world, sent during the next extraction.
+#[derive(Resource, Default)]
+pub(crate) struct VelloRendererMessages {
+    /// Whether a new renderer became available.
+    pub ready: bool,
+    pub errors: Vec<VelloRenderError>,
+}
+
+/// How many times in a row the renderer was re-created after failed frames.
+#[derive(Resource, Default)]
+pub(crate) struct VelloRecoveryState {
+    pub reinit_attempts: u32,
+}
+
+/// Sent when the Vello renderer becomes available, either at startup or after it was
+/// re-initialized for changed [`VelloRenderSettings`]. Nothing is rendered before.
+#[derive(Message, Debug, Clone, Copy)]
+pub struct VelloRendererReady;
+
+/// Sent when the Vello renderer fails. Rendering never panics; instead, targets that failed to
+/// render keep showing their last good frame and are retried on the next frame.
+#[derive(Message, Debug, Clone, Error)]
+pub enum VelloRenderError {
+    /// The renderer failed to initialize. Nothing is rendered until it's re-initialized, e.g. by
+    /// changing [`VelloRenderSettings`].
+    #[error("Failed to initialize the Vello renderer (use_cpu: {use_cpu}): {error}")]
+    Init {
+        /// Whether the failed renderer used the CPU.
+        use_cpu: bool,
+        error: Arc<vello::Error>,
+    },
+    /// A target failed to render.
+    #[error("Failed to render a Vello target: {error}")]
+    Render {
+        /// The camera of the view that failed, or `None` for a [`VelloTextureTarget`].
+        camera: Option<Entity>,
+        /// The image that failed to render. UI canvases interleaved with native UI report the top
+        /// canvas of their view.
+        target: AssetId<Image>,
+        error: Arc<vello::Error>,
+    },
+    /// A target wasn't rendered because the renderer panicked while rendering it or an earlier
+    /// one. The panic is caught, and the renderer is re-created as allowed by the
+    /// [`VelloRecoveryPolicy`], like after a failed frame.
+    #[error("The Vello renderer panicked while rendering this or an earlier target")]
+    Poisoned {
+        /// The camera of the view that wasn't rendered, or `None` for a [`VelloTextureTarget`].
+        camera: Option<Entity>,
+        /// The image that wasn't rendered.
+        target: AssetId<Image>,
+    },
+}
+
+/// How the renderer recovers from failures, see [`VelloRenderError`].
+#[derive(Resource, Clone, Debug)]
+pub struct VelloRecoveryPolicy {
+    /// Fall back to a CPU renderer with area antialiasing if the GPU renderer fails to
+    /// initialize.
+    pub cpu_fallback: bool,
+    /// How many times in a row the renderer is re-created after a frame fails to render. A
+    /// successfully rendered frame resets the count.
+    pub max_reinit_attempts: u32,
+}
+
+impl Default for VelloRecoveryPolicy {
+    fn default() -> Self {
+        Self {
+            cpu_fallback: true,
+            max_reinit_attempts: 3,
+        }
+    }
+}
 
 /// Render settings for Vello.
 #[derive(Resource, Clone)]
@@ -149,6 +738,17 @@ pub struct VelloRenderSettings {
 
     /// Which antialiasing strategy to use
     pub antialiasing: AaConfig,
+
+    /// A directory to persist compiled GPU pipelines in, so later launches skip most shader
+    /// compilation. Only used on devices supporting [`PIPELINE_CACHE`], e.g. on Vulkan, and never
+    /// on the web.
+    ///
+    /// [`PIPELINE_CACHE`]: vello::wgpu::Features::PIPELINE_CACHE
+    pub pipeline_cache_dir: Option<PathBuf>,
+
+    /// Create the renderer in the background instead of blocking the first frames. Nothing is
+    /// rendered until it's ready, see [`VelloRendererReady`].
+    pub background_init: bool,
 }
 
 impl Default for VelloRenderSettings {
@@ -156,6 +756,8 @@ impl Default for VelloRenderSettings {
         Self {
             use_cpu: false,
             antialiasing: AaConfig::Area,
+            pipeline_cache_dir: None,
+            background_init: false,
         }
     }
 }
@@ -165,6 +767,12 @@ impl Default for VelloRenderSettings {
 pub(crate) struct VelloCanvasSettings {
     /// The render layers that will be used for the Vello canvas mesh.
     pub render_layers: RenderLayers,
+    /// Sorted z boundaries at which world items are split into separate canvases.
+    pub z_layers: Vec<f32>,
+    /// Draw UI items in Bevy's UI pass, interleaved with native UI nodes.
+    pub interleave_ui: bool,
+    /// Sort world items with the same z from the highest y down.
+    pub y_sort: bool,
 }
 
 /// Internally used as a prepared render asset.
@@ -190,6 +798,37 @@ pub(crate) enum VelloWorldRenderItem {
         affine: Affine,
         item: crate::integrations::text::render::ExtractedVelloText2d,
     },
+    Custom {
+        affine: Affine,
+        blend_mode: VelloBlendMode,
+        /// The entity's [`VelloOpacity`], multiplied by the item's own alpha when encoding.
+        opacity: f32,
+        item: renderable::ErasedRenderable,
+    },
+    /// The items of a [`VelloGroup`], drawn into its layer.
+    Group {
+        affine: Affine,
+        item: extract::ExtractedVelloGroup,
+        items: Vec<VelloWorldRenderItem>,
+    },
+    /// An item clipped by every [`VelloClip`] it is in, outermost first.
+    Clipped {
+        clips: Arc<[PreparedClip]>,
+        item: Box<VelloWorldRenderItem>,
+    },
+}
+
+/// A [`VelloClip`] in the pixel space of a view.
+pub(crate) enum PreparedClip {
+    Shape {
+        affine: Affine,
+        shape: BezPath,
+    },
+    Mask {
+        affine: Affine,
+        scene: Arc<vello::Scene>,
+        mode: VelloMaskMode,
+    },
 }
 
 /// Internally used as a prepared render asset.
@@ -220,13 +859,48 @@ pub(crate) enum VelloUiRenderItem {
         clip: Option<vello::kurbo::Rect>,
         item: crate::integrations::text::render::ExtractedUiVelloText,
     },
+    Custom {
+        affine: Affine,
+        clip: Option<vello::kurbo::Rect>,
+        clip_shapes: extract::UiClipShapes,
+        blend_mode: VelloBlendMode,
+        /// The entity's [`VelloOpacity`], multiplied by the item's own alpha when encoding.
+        opacity: f32,
+        item: renderable::ErasedRenderable,
+    },
+    /// The items of a [`VelloGroup`], drawn into its layer.
+    Group {
+        affine: Affine,
+        clip: Option<vello::kurbo::Rect>,
+        item: extract::ExtractedUiVelloGroup,
+        items: Vec<VelloUiRenderItem>,
+    },
 }
 
-/// Internally used to buffer sorted assets prepared for the next frame.
-#[derive(Resource, Default)]
-pub(crate) struct VelloRenderQueue {
-    world: Vec<VelloWorldRenderItem>,
+/// Internally used to buffer sorted assets prepared for the next frame, per view.
+#[derive(Resource, Default, Deref, DerefMut)]
+pub(crate) struct VelloRenderQueue(EntityHashMap<VelloViewRenderQueue>);
+
+/// The sorted assets a single [`VelloView`] will render.
+#[derive(Default)]
+pub(crate) struct VelloViewRenderQueue {
+    /// World items per canvas z layer, lowest first.
+    world: Vec<Vec<VelloWorldRenderItem>>,
+    /// Sorted UI items, drawn into the top canvas layer unless interleaved with native UI.
     ui: Vec<VelloUiRenderItem>,
+    /// The stack index of each UI item.
+    ui_stack_indices: Vec<u32>,
+    /// Whether the UI items are drawn into UI canvases, see [`ui_canvas`].
+    ui_interleaved: bool,
+}
+
+/// Internally used to skip re-rendering targets when nothing they show changed.
+#[derive(Resource, Default)]
+pub(crate) struct VelloFrameChanges {
+    /// Whether any extracted item was added, removed or changed since the last frame.
+    pub items_changed: bool,
+    /// Targets rendered since the last change, whose contents can be reused as is.
+    pub up_to_date: HashSet<AssetId<Image>>,
 }
 
 /// Internally used for diagnostics.
@@ -273,17 +947,40 @@ pub(crate) struct VelloFrameProfileData {
     /// Total number of glyph runs rendered last frame.
     #[cfg(feature = "text")]
     pub n_glyph_runs: u32,
+    /// Number of frames so far in which all rendering was skipped, as nothing changed.
+    pub n_skipped_frames: u32,
+}
+
+impl VelloFrameProfileData {
+    /// Adds the counts of a scene encoded this frame.
+    pub(crate) fn add_scene(&mut self, scene: &vello::Scene) {
+        let encoding = scene.encoding();
+        self.n_paths += encoding.n_paths;
+        self.n_path_segs += encoding.n_path_segments;
+        self.n_clips += encoding.n_clips;
+        self.n_open_clips += encoding.n_open_clips;
+        #[cfg(feature = "text")]
+        {
+            self.n_glyphs += encoding.resources.glyphs.len() as u32;
+            self.n_glyph_runs += encoding.resources.glyph_runs.len() as u32;
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use bevy::math::Rect;
+    use bevy::{
+        ecs::system::RunSystemOnce,
+        math::Rect,
+        render::{MainWorld, render_resource::TextureUsages},
+    };
+    use extract::{ExtractedVelloView, VelloRenderTarget};
     use systems::to_kurbo_clip;
 
     /// CalculatedClip is already in physical pixels (Bevy resolves layout
     /// against `physical_size`). to_kurbo_clip converts the type without
-    /// scaling, matching PreparedAffine's output coordinate space.
+    /// scaling, matching PreparedAffines' output coordinate space.
     #[test]
     fn clip_converts_to_kurbo_without_scaling() {
         let bevy_clip = Rect::new(10.0, 20.0, 100.0, 200.0);
@@ -349,4 +1046,156 @@ mod tests {
         let order: Vec<char> = sorted.iter().map(|(_, c)| *c).collect();
         assert_eq!(order, vec!['A', 'B', 'C', 'D', 'E']);
     }
+
+    #[test]
+    fn float_canvases_are_drawn_by_the_conversion_pass() {
+        let image = VelloTextureTarget::new_image_with_format(4, 4, VelloCanvasFormat::Rgba16Float);
+        assert_eq!(image.texture_descriptor.format, TextureFormat::Rgba16Float);
+        assert!(
+            image
+                .texture_descriptor
+                .usage
+                .contains(TextureUsages::RENDER_ATTACHMENT)
+        );
+        assert!(linear::VelloLinearConverter::needs_conversion(
+            image.texture_descriptor.format
+        ));
+
+        // Vello renders into 8-bit images directly.
+        let image = VelloTextureTarget::new_image(4, 4);
+        assert!(
+            image
+                .texture_descriptor
+                .usage
+                .contains(TextureUsages::STORAGE_BINDING)
+        );
+        assert!(!linear::VelloLinearConverter::needs_conversion(
+            image.texture_descriptor.format
+        ));
+    }
+
+    #[test]
+    fn failed_renderer_init_is_reported_instead_of_panicking() {
+        let mut world = World::new();
+        world.init_resource::<VelloRendererMessages>();
+        world.insert_resource(VelloRenderSettings::default());
+        insert_renderer(
+            &mut world,
+            RendererInit {
+                renderer: None,
+                settings: VelloRenderSettings::default(),
+                errors: vec![VelloRenderError::Init {
+                    use_cpu: false,
+                    error: Arc::new(vello::Error::NoCompatibleDevice),
+                }],
+            },
+        );
+
+        // Nothing renders, and the main world is told why.
+        assert!(!world.contains_resource::<VelloRenderer>());
+        let messages = world.resource::<VelloRendererMessages>();
+        assert!(!messages.ready);
+        assert!(matches!(
+            messages.errors.as_slice(),
+            [VelloRenderError::Init { use_cpu: false, .. }]
+        ));
+    }
+
+    #[test]
+    fn world_sort_keys_are_total() {
+        let key = |index: u32, translation: Vec3, z_index: Option<f32>, y_sort: bool| {
+            WorldSortKey::new(
+                Entity::from_raw_u32(index).unwrap(),
+                &GlobalTransform::from_translation(translation),
+                z_index.map(VelloZIndex).as_ref(),
+                y_sort,
+            )
+        };
+
+        // Equal z is broken by entity, whatever the order items were queued in.
+        let mut items = [
+            (key(2, Vec3::ZERO, None, false), 'b'),
+            (key(1, Vec3::ZERO, None, false), 'a'),
+            (key(3, Vec3::new(0.0, 0.0, -1.0), None, false), 'c'),
+        ];
+        items.sort_by_key(|(key, _)| *key);
+        assert_eq!(items.map(|(_, item)| item), ['c', 'a', 'b']);
+
+        // A z index replaces the translation's z.
+        assert!(
+            key(1, Vec3::new(0.0, 0.0, 10.0), Some(0.0), false) < key(2, Vec3::ZERO, None, false)
+        );
+
+        // Y-sorting draws lower items above higher ones at the same z, but never across z.
+        let high = key(1, Vec3::new(0.0, 50.0, 0.0), None, true);
+        let low = key(2, Vec3::new(0.0, -50.0, 0.0), None, true);
+        assert!(high < low);
+        assert!(low < key(3, Vec3::new(0.0, 100.0, 1.0), None, true));
+        assert!(
+            key(2, Vec3::new(0.0, -50.0, 0.0), None, false)
+                > key(1, Vec3::new(0.0, 50.0, 0.0), None, false)
+        );
+    }
+
+    #[test]
+    fn unusable_render_scale_falls_back_to_one() {
+        for render_scale in [0.0, -1.0, f32::NAN, f32::INFINITY] {
+            let settings = VelloViewSettings {
+                render_scale,
+                ..default()
+            };
+            assert_eq!(settings.effective_render_scale(), 1.0);
+        }
+    }
+
+    #[test]
+    fn view_settings_override_the_render_settings_per_view() {
+        let mut main_world = MainWorld::default();
+        main_world.init_resource::<Assets<Image>>();
+        let camera = test_utils::camera(UVec2::splat(100));
+        let plain = main_world
+            .spawn((VelloView, camera.clone(), VelloRenderTarget::default()))
+            .id();
+        let minimap = main_world
+            .spawn((
+                VelloView,
+                camera,
+                VelloRenderTarget::default(),
+                VelloViewSettings {
+                    base_color: Color::srgb(1.0, 0.5, 0.0),
+                    antialiasing: Some(AaConfig::Msaa16),
+                    ..default()
+                },
+            ))
+            .id();
+        let mut render_world = test_utils::render_world(main_world);
+        render_world
+            .run_system_once(extract::extract_views)
+            .unwrap();
+
+        let render_settings = VelloRenderSettings {
+            antialiasing: AaConfig::Area,
+            ..default()
+        };
+        let mut views = render_world.query::<&ExtractedVelloView>();
+        let mut render_params = |camera: Entity, clear: bool| {
+            let view = views
+                .iter(&render_world)
+                .find(|view| view.camera == Some(camera))
+                .unwrap();
+            view.settings
+                .render_params(clear, 100, 100, &render_settings)
+        };
+
+        let params = render_params(plain, true);
+        assert_eq!(params.base_color.components, [0.0; 4]);
+        assert_eq!(params.antialiasing_method, AaConfig::Area);
+        let params = render_params(minimap, true);
+        assert_eq!(params.base_color.components, [1.0, 0.5, 0.0, 1.0]);
+        assert_eq!(params.antialiasing_method, AaConfig::Msaa16);
+        // Canvas z layers above the lowest are not cleared
+        let params = render_params(minimap, false);
+        assert_eq!(params.base_color.components, [0.0; 4]);
+        assert_eq!(params.antialiasing_method, AaConfig::Msaa16);
+    }
 }
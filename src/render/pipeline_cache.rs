@@ -0,0 +1,70 @@
+//! Persisting Vello's compiled GPU pipelines across launches.
+
+use std::path::{Path, PathBuf};
+
+use vello::wgpu;
+
+/// A pipeline cache loaded from, and saved to, a file keyed by the GPU adapter.
+#[derive(Clone)]
+pub(crate) struct VelloPipelineCache {
+    cache: wgpu::PipelineCache,
+    path: PathBuf,
+}
+
+impl VelloPipelineCache {
+    /// Loads the cache for this adapter from `dir`, or starts an empty one. Returns `None` if the
+    /// device doesn't support pipeline caches.
+    pub fn load(
+        device: &wgpu::Device,
+        adapter_info: &wgpu::AdapterInfo,
+        dir: &Path,
+    ) -> Option<Self> {
+        if cfg!(target_arch = "wasm32")
+            || !device.features().contains(wgpu::Features::PIPELINE_CACHE)
+        {
+            tracing::debug!("Pipeline caches are not supported by this device, not caching");
+            return None;
+        }
+        let path = dir.join(wgpu::util::pipeline_cache_key(adapter_info)?);
+        // A missing or unreadable cache is rebuilt from scratch.
+        let data = std::fs::read(&path).ok();
+        // SAFETY: The data was written by `save` from a cache of an adapter with the same
+        // pipeline cache key, which is part of the file name. Data from another wgpu version or
+        // driver is rejected, and `fallback` starts an empty cache in that case.
+        let cache = unsafe {
+            device.create_pipeline_cache(&wgpu::PipelineCacheDescriptor {
+                label: Some("vello_pipeline_cache"),
+                data: data.as_deref(),
+                fallback: true,
+            })
+        };
+        Some(Self { cache, path })
+    }
+
+    pub fn cache(&self) -> &wgpu::PipelineCache {
+        &self.cache
+    }
+
+    /// Writes the cache to disk. Failures are logged, as the cache is only an optimization.
+    pub fn save(&self) {
+        let Some(data) = self.cache.get_data() else {
+            return;
+        };
+        let write = || -> std::io::Result<()> {
+            if let Some(dir) = self.path.parent() {
+                std::fs::create_dir_all(dir)?;
+            }
+            // Write to a temporary file first, so a crash never leaves a truncated cache behind.
+            let temp_path = self.path.with_extension("temp");
+            std::fs::write(&temp_path, &data)?;
+            std::fs::rename(&temp_path, &self.path)
+        };
+        match write() {
+            Ok(()) => tracing::debug!("Saved Vello pipeline cache to {}", self.path.display()),
+            Err(e) => tracing::warn!(
+                "Failed to save Vello pipeline cache to {}: {e}",
+                self.path.display()
+            ),
+        }
+    }
+}
@@ -3,17 +3,28 @@ use bevy::{
     camera::{CameraUpdateSystems, visibility::VisibilitySystems},
     prelude::*,
     render::{
-        Render, RenderApp, RenderSystems, extract_component::ExtractComponentPlugin,
+        Render, RenderApp, RenderStartup, RenderSystems,
+        extract_component::ExtractComponentPlugin,
+        render_phase::{AddRenderCommand, DrawFunctions, sort_phase_system},
+        render_resource::SpecializedRenderPipelines,
         renderer::RenderDevice,
     },
     sprite_render::Material2dPlugin,
+    ui_render::TransparentUi,
 };
 
-use super::{VelloCanvasSettings, VelloRenderSettings, extract::VelloRenderTarget, systems};
+use super::{VelloCanvasSettings, VelloRenderSettings, systems};
 use crate::render::{
-    RT_SHADER_HANDLE, VelloCanvasMaterial, VelloEntityCountData, VelloFrameProfileData,
-    VelloRenderQueue, VelloRenderer, VelloView, diagnostics::VelloRenderDiagnosticsPlugin,
-    extract::VelloExtractStep,
+    RT_SHADER_HANDLE, UI_CANVAS_SHADER_HANDLE, VelloCanvasMaterial, VelloEntityCountData,
+    VelloFrameChanges, VelloFrameProfileData, VelloRecoveryPolicy, VelloRecoveryState,
+    VelloRenderError, VelloRenderQueue, VelloRenderer, VelloRendererMessages, VelloRendererReady,
+    VelloView,
+    diagnostics::VelloRenderDiagnosticsPlugin,
+    extract::{self, VelloExtractStep},
+    init_renderer,
+    linear::VelloLinearConverter,
+    renderable::VelloCustomRenderQueue,
+    ui_canvas::{self, DrawVelloUiCanvas, VelloUiCanvasPipeline, VelloUiCanvases},
 };
 
 #[derive(Default)]
@@ -23,6 +34,9 @@ pub struct VelloRenderPlugin {
 
     /// Settings used for rendering with Vello
     pub render_settings: VelloRenderSettings,
+
+    /// How the renderer recovers from failures
+    pub recovery_policy: VelloRecoveryPolicy,
 }
 
 impl Plugin for VelloRenderPlugin {
@@ -33,33 +47,73 @@ impl Plugin for VelloRenderPlugin {
             "../../shaders/vello_rendertarget.wgsl",
             Shader::from_wgsl
         );
+        load_internal_asset!(
+            app,
+            UI_CANVAS_SHADER_HANDLE,
+            "../../shaders/vello_ui_canvas.wgsl",
+            Shader::from_wgsl
+        );
 
         // Diagnostics
         app.add_plugins(VelloRenderDiagnosticsPlugin);
 
+        app.add_message::<VelloRendererReady>()
+            .add_message::<VelloRenderError>();
+
         let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
             return;
         };
 
         render_app
             .insert_resource(self.render_settings.clone())
+            .insert_resource(self.canvas_settings.clone())
             .init_resource::<VelloEntityCountData>()
             .init_resource::<VelloFrameProfileData>()
             .init_resource::<VelloRenderQueue>()
+            .init_resource::<VelloFrameChanges>()
+            .init_resource::<VelloCustomRenderQueue>()
+            .init_resource::<VelloUiCanvases>()
+            .insert_resource(self.recovery_policy.clone())
+            .init_resource::<VelloRecoveryState>()
+            .init_resource::<VelloRendererMessages>()
             .configure_sets(
                 ExtractSchedule,
                 (
+                    VelloExtractStep::ExtractViews,
                     VelloExtractStep::ExtractAssets,
                     VelloExtractStep::RunDiagnostics,
                 )
                     .chain()
                     .after(VisibilitySystems::CheckVisibility),
             )
+            .add_systems(
+                ExtractSchedule,
+                (
+                    extract::extract_views.in_set(VelloExtractStep::ExtractViews),
+                    (
+                        extract::extract_hierarchy_changes,
+                        extract::extract_groups,
+                        extract::extract_world_clips,
+                    )
+                        .in_set(VelloExtractStep::ExtractAssets),
+                    systems::send_renderer_messages,
+                ),
+            )
             .add_systems(
                 Render,
-                (systems::sort_render_items, systems::render_frame)
-                    .chain()
-                    .in_set(RenderSystems::Render)
+                systems::poll_renderer_task.in_set(RenderSystems::PrepareResources),
+            )
+            .add_systems(
+                Render,
+                (
+                    // Sorted before the UI phase, which UI canvases are queued into
+                    systems::sort_render_items
+                        .in_set(RenderSystems::PhaseSort)
+                        .before(sort_phase_system::<TransparentUi>),
+                    systems::render_frame
+                        .run_if(resource_exists::<VelloRenderer>)
+                        .in_set(RenderSystems::Render),
+                )
                     .run_if(resource_exists::<RenderDevice>),
             )
             .add_systems(
@@ -70,14 +124,20 @@ impl Plugin for VelloRenderPlugin {
         app.add_plugins(ExtractComponentPlugin::<VelloView>::default());
 
         app.insert_resource(self.canvas_settings.clone())
-            .add_plugins((
-                Material2dPlugin::<VelloCanvasMaterial>::default(),
-                ExtractComponentPlugin::<VelloRenderTarget>::default(),
-            ))
-            .add_systems(Startup, systems::setup_rendertarget)
+            .add_plugins(Material2dPlugin::<VelloCanvasMaterial>::default())
             .add_systems(
                 PostUpdate,
-                (systems::resize_rendertargets.after(CameraUpdateSystems),),
+                (
+                    (
+                        systems::cleanup_rendertargets,
+                        systems::setup_rendertarget,
+                        systems::resize_rendertargets,
+                    )
+                        .chain()
+                        .after(CameraUpdateSystems),
+                    systems::restrict_canvas_visibility.after(VisibilitySystems::CheckVisibility),
+                    systems::update_texture_targets,
+                ),
             );
     }
 
@@ -85,6 +145,30 @@ impl Plugin for VelloRenderPlugin {
         let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
             return;
         };
-        render_app.init_resource::<VelloRenderer>();
+        init_renderer(render_app.world_mut());
+        render_app.init_resource::<VelloLinearConverter>();
+
+        // UI canvases are drawn in the UI pass, which must exist by now
+        if self.canvas_settings.interleave_ui {
+            if !render_app
+                .world()
+                .contains_resource::<DrawFunctions<TransparentUi>>()
+            {
+                tracing::warn!("Interleaving Vello UI requires Bevy's UI rendering");
+                return;
+            }
+            render_app
+                .init_resource::<SpecializedRenderPipelines<VelloUiCanvasPipeline>>()
+                .add_render_command::<TransparentUi, DrawVelloUiCanvas>()
+                .add_systems(RenderStartup, ui_canvas::init_ui_canvas_pipeline)
+                .add_systems(
+                    Render,
+                    ui_canvas::queue_ui_canvases
+                        .in_set(RenderSystems::PhaseSort)
+                        .after(systems::sort_render_items)
+                        .before(sort_phase_system::<TransparentUi>)
+                        .run_if(resource_exists::<VelloUiCanvasPipeline>),
+                );
+        }
     }
 }
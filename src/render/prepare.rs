@@ -1,5 +1,169 @@
-use bevy::prelude::*;
+use bevy::{camera::visibility::RenderLayers, ecs::entity::EntityHashMap, prelude::*};
 use vello::kurbo::Affine;
 
-#[derive(Component, Copy, Clone, Deref, DerefMut)]
-pub struct PreparedAffine(pub Affine);
+use super::extract::ExtractedVelloView;
+
+/// The affines of an extracted item in the pixel space of every view that renders it, keyed by
+/// the render-world view entity. Views that should not render the item have no entry.
+#[derive(Component, Clone, Default, Deref, DerefMut)]
+pub struct PreparedAffines(pub EntityHashMap<Affine>);
+
+/// Returns the render-world views that should draw a UI node, given `(view, main world camera,
+/// camera render layers)` for every [`VelloView`](crate::render::VelloView).
+///
+/// A node is drawn by the view it targets. Nodes targeting a camera that isn't a Vello view are
+/// drawn by every view whose render layers intersect the node's.
+pub(crate) fn ui_node_views<'a>(
+    views: &'a [(Entity, Entity, RenderLayers)],
+    target_camera: Option<Entity>,
+    render_layers: &'a RenderLayers,
+) -> impl Iterator<Item = Entity> + 'a {
+    let targets_vello_view = views
+        .iter()
+        .any(|(_, camera, _)| Some(*camera) == target_camera);
+    views
+        .iter()
+        .filter(move |(_, camera, camera_layers)| {
+            if targets_vello_view {
+                Some(*camera) == target_camera
+            } else {
+                render_layers.intersects(camera_layers)
+            }
+        })
+        .map(|(view, _, _)| *view)
+}
+
+/// The affine of a world item at `world_transform`, in the pixel space of `view`. Item content is
+/// y-down around the item's origin.
+pub fn world_affine(view: &ExtractedVelloView, world_transform: &GlobalTransform) -> Affine {
+    // A transposed (flipped over its diagonal) PostScript matrix
+    // | a c e |
+    // | b d f |
+    // | 0 0 1 |
+    //
+    // Components
+    // | scale_x skew_x translate_x |
+    // | skew_y scale_y translate_y |
+    // | skew_z skew_z scale_z |
+    //
+    // rotate (z)
+    // | cos(θ) -sin(θ) translate_x |
+    // | sin(θ) cos(θ) translate_y |
+    // | skew_z skew_z scale_z |
+    //
+    // The order of operations is important, as it affects the final transformation matrix.
+    //
+    // Order of operations:
+    // 1. Scale
+    // 2. Rotate
+    // 3. Translate
+    let transform: [f64; 6] = {
+        let ndc_to_pixels_matrix = {
+            let size_pixels: UVec2 = view.size;
+            let (pixels_x, pixels_y) = (size_pixels.x as f32, size_pixels.y as f32);
+            Mat4::from_cols_array_2d(&[
+                [pixels_x / 2.0, 0.0, 0.0, pixels_x / 2.0],
+                [0.0, pixels_y / 2.0, 0.0, pixels_y / 2.0],
+                [0.0, 0.0, 1.0, 0.0],
+                [0.0, 0.0, 0.0, 1.0],
+            ])
+            .transpose()
+        };
+        let view_proj_matrix = {
+            let mut view_mat = view.world_from_view.to_matrix();
+            // Flip Y-axis to match Vello's y-down coordinate space
+            view_mat.w_axis.y *= -1.0;
+            let proj_mat = view.clip_from_view;
+            proj_mat * view_mat.inverse()
+        };
+        let model_matrix = {
+            let mut model_matrix = world_transform.to_matrix();
+            // Flip Y-axis to match Vello's y-down coordinate space
+            model_matrix.w_axis.y *= -1.0;
+            model_matrix
+        };
+
+        // Transform chain: world → view → projection → NDC → pixels → pixel_scale
+        let raw_transform = ndc_to_pixels_matrix * view_proj_matrix * model_matrix;
+        let transform = raw_transform.to_cols_array();
+
+        // Negate skew_x and skew_y to match rotation of the Bevy's y-up world
+        [
+            transform[0] as f64,  // a // scale_x
+            -transform[1] as f64, // b // skew_y
+            -transform[4] as f64, // c // skew_x
+            transform[5] as f64,  // d // scale_y
+            transform[12] as f64, // e // translate_x
+            transform[13] as f64, // f // translate_y
+        ]
+    };
+
+    Affine::new(transform)
+}
+
+/// The affine of a UI node's content in physical pixels. Content is y-down from the node's
+/// top-left corner, in logical pixels.
+pub fn ui_affine(
+    ui_transform: &UiGlobalTransform,
+    ui_node: &ComputedNode,
+    pixel_scale: f32,
+) -> Affine {
+    let pixel_scale_matrix = Mat4::from_scale(Vec3::new(pixel_scale, pixel_scale, 1.0));
+
+    // A transposed (flipped over its diagonal) PostScript matrix
+    // | a c e |
+    // | b d f |
+    // | 0 0 1 |
+    //
+    // Components
+    // | scale_x skew_x translate_x |
+    // | skew_y scale_y translate_y |
+    // | skew_z skew_z scale_z |
+    //
+    // rotate (z)
+    // | cos(θ) -sin(θ) translate_x |
+    // | sin(θ) cos(θ) translate_y |
+    // | skew_z skew_z scale_z |
+    //
+    // The order of operations is important, as it affects the final transformation matrix.
+    //
+    // Order of operations:
+    // 1. Scale
+    // 2. Rotate
+    // 3. Translate
+    let transform: [f64; 6] = {
+        // Convert UiGlobalTransform to Mat4
+        let mat2 = ui_transform.matrix2;
+        let translation = ui_transform.translation;
+        let model_matrix = Mat4::from_cols_array_2d(&[
+            [mat2.x_axis.x, mat2.x_axis.y, 0.0, 0.0],
+            [mat2.y_axis.x, mat2.y_axis.y, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [translation.x, translation.y, 0.0, 1.0],
+        ]);
+
+        // Apply node centering transformation
+        // Get node center
+        let local_center_matrix = {
+            let Vec2 {
+                x: width,
+                y: height,
+            } = ui_node.size();
+            Mat4::from_translation(Vec3::new(width / 2.0, height / 2.0, 0.0)).inverse()
+        };
+
+        // Transform chain: ui_transform (already in px) → pixel_scale
+        let raw_transform = model_matrix * local_center_matrix * pixel_scale_matrix;
+        let transform = raw_transform.to_cols_array();
+        [
+            transform[0] as f64,  // a // scale_x
+            transform[1] as f64,  // b // skew_y
+            transform[4] as f64,  // c // skew_x
+            transform[5] as f64,  // d // scale_y
+            transform[12] as f64, // e // translate_x
+            transform[13] as f64, // f // translate_y
+        ]
+    };
+
+    Affine::new(transform)
+}
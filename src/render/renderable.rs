@@ -0,0 +1,486 @@
+//! An extension point for renderable component types defined outside this crate.
+
+use std::{marker::PhantomData, sync::Arc};
+
+use bevy::{
+    camera::visibility::RenderLayers,
+    ecs::entity::{EntityHashMap, EntityHashSet},
+    prelude::*,
+    render::{
+        Extract, Render, RenderApp, RenderSystems, renderer::RenderDevice,
+        sync_world::TemporaryRenderEntity,
+    },
+};
+use vello::{
+    Scene,
+    kurbo::{Affine, Rect},
+};
+
+use super::{
+    VelloBlendMode, VelloFrameChanges, VelloUiRenderItem, VelloWorldRenderItem, WorldSortKey,
+    encode::push_item_layer,
+    extract::{
+        ExtractedVelloView, UiClipShapes, VelloExtractStep, VelloHierarchy, VelloUiClips,
+        VelloViewLayers,
+    },
+    prepare::{ui_affine, ui_node_views, world_affine},
+    systems::to_kurbo_clip,
+};
+
+/// A component type that draws itself with Vello, like [`VelloScene2d`](crate::prelude::VelloScene2d),
+/// SVGs or Lotties.
+///
+/// Register the type with [`VelloRenderableAppExt::register_vello_renderable`]. Entities with a
+/// [`Node`] are drawn as UI, sorted by stack index and clipped by their [`CalculatedClip`]. Other
+/// entities are drawn as world items, sorted by z or [`VelloZIndex`](super::VelloZIndex) with the
+/// built-in types and split into canvas z layers. Both respect visibility and [`RenderLayers`].
+///
+/// Items are [extracted](Self::extract) into the render world when their component changes,
+/// [prepared](Self::prepare) there once, and then encoded every frame they are drawn until the
+/// component changes again.
+pub trait VelloRenderable: Send + Sync + 'static {
+    /// Copies what the render world draws from the component. Defaults to a clone of the
+    /// component, so keep it cheap to clone, e.g. by sharing large data behind an [`Arc`].
+    fn extract(&self) -> Self
+    where
+        Self: Clone,
+    {
+        self.clone()
+    }
+
+    /// Prepares an extracted item in the render world before it is first encoded, e.g. to build
+    /// paths once instead of on every encode. Does nothing by default.
+    fn prepare(&mut self)
+    where
+        Self: Sized,
+    {
+    }
+
+    /// Encodes the item into `scene`. The `affine` maps item space to the pixels of the view being
+    /// rendered. Item space is y-down, around the entity's origin for world items, and from the UI
+    /// node's top-left corner in logical pixels for UI items.
+    fn encode(&self, scene: &mut Scene, affine: Affine);
+
+    /// The opacity of the item. Items below `1.0` are drawn through a layer clipped to
+    /// [`bounds`](Self::bounds).
+    fn alpha(&self) -> f32 {
+        1.0
+    }
+
+    /// The bounds of the item in item space, used to clip its opacity layer. Defaults to
+    /// unbounded.
+    fn bounds(&self) -> Option<Rect> {
+        None
+    }
+}
+
+/// Registers [`VelloRenderable`] types.
+pub trait VelloRenderableAppExt {
+    /// Draws every entity with a `T` component with Vello.
+    fn register_vello_renderable<T: VelloRenderable + Component + Clone>(&mut self) -> &mut Self;
+}
+
+impl VelloRenderableAppExt for App {
+    fn register_vello_renderable<T: VelloRenderable + Component + Clone>(&mut self) -> &mut Self {
+        self.add_plugins(VelloRenderablePlugin::<T>(PhantomData));
+        self
+    }
+}
+
+struct VelloRenderablePlugin<T>(PhantomData<T>);
+
+impl<T: VelloRenderable + Component + Clone> Plugin for VelloRenderablePlugin<T> {
+    fn build(&self, app: &mut App) {
+        #[cfg(feature = "picking")]
+        app.add_plugins((
+            crate::picking::WorldPickingPlugin::<T>::default(),
+            crate::picking::UiPickingPlugin::<T>::default(),
+            crate::picking::RenderableShapesPlugin::<T>::default(),
+        ));
+
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+        render_app
+            .init_resource::<ExtractedRenderables<T>>()
+            .add_systems(
+                ExtractSchedule,
+                extract_renderables::<T>.in_set(VelloExtractStep::ExtractAssets),
+            )
+            .add_systems(
+                Render,
+                (
+                    prepare_renderables::<T>.in_set(RenderSystems::PrepareAssets),
+                    // Drained by sorting, which only runs with a render device
+                    queue_renderables::<T>
+                        .in_set(RenderSystems::Queue)
+                        .run_if(resource_exists::<RenderDevice>),
+                ),
+            );
+    }
+
+    fn is_unique(&self) -> bool {
+        // Registering a type twice would draw it twice
+        true
+    }
+}
+
+/// An item a [`VelloRenderable`] draws, ready to be encoded.
+pub(crate) type ErasedRenderable = Arc<dyn VelloRenderable>;
+
+/// Items of registered [`VelloRenderable`] types, per view, merged with the built-in items when
+/// sorting.
+#[derive(Resource, Default)]
+pub(crate) struct VelloCustomRenderQueue {
+    /// World items with their sort key, group and clip.
+    pub world: EntityHashMap<
+        Vec<(
+            WorldSortKey,
+            Option<Entity>,
+            Option<Entity>,
+            VelloWorldRenderItem,
+        )>,
+    >,
+    /// UI items with their stack index and group.
+    pub ui: EntityHashMap<Vec<(u32, Option<Entity>, VelloUiRenderItem)>>,
+}
+
+/// The items of a [`VelloRenderable`] type in the render world, by main world entity.
+#[derive(Resource)]
+struct ExtractedRenderables<T> {
+    /// Items ready to be encoded, reused until their component changes.
+    prepared: EntityHashMap<ErasedRenderable>,
+    /// Items extracted this frame, prepared before they are queued.
+    unprepared: Vec<(Entity, T)>,
+}
+
+impl<T> Default for ExtractedRenderables<T> {
+    fn default() -> Self {
+        Self {
+            prepared: EntityHashMap::default(),
+            unprepared: Vec::new(),
+        }
+    }
+}
+
+impl<T: VelloRenderable + Component + Clone> ExtractedRenderables<T> {
+    /// Extracts the item of `entity` again if its component changed, or if no view could draw it
+    /// last frame.
+    fn extract(&mut self, entity: Entity, item: &Ref<T>) {
+        if item.is_changed() || !self.prepared.contains_key(&entity) {
+            self.unprepared.push((entity, item.extract()));
+        }
+    }
+}
+
+#[derive(Component)]
+struct ExtractedRenderable<T> {
+    /// The main world entity, which its item is stored by.
+    entity: Entity,
+    transform: GlobalTransform,
+    render_layers: RenderLayers,
+    blend_mode: VelloBlendMode,
+    opacity: f32,
+    group: Option<Entity>,
+    clip: Option<Entity>,
+    sort_key: WorldSortKey,
+    _type: PhantomData<T>,
+}
+
+#[derive(Component)]
+struct ExtractedUiRenderable<T> {
+    /// The main world entity, which its item is stored by.
+    entity: Entity,
+    ui_transform: UiGlobalTransform,
+    ui_node: ComputedNode,
+    ui_render_target: ComputedUiRenderTargetInfo,
+    target_camera: Option<Entity>,
+    render_layers: RenderLayers,
+    clip: Option<Rect>,
+    clip_shapes: UiClipShapes,
+    blend_mode: VelloBlendMode,
+    opacity: f32,
+    group: Option<Entity>,
+    _type: PhantomData<T>,
+}
+
+#[allow(clippy::type_complexity)]
+#[allow(clippy::too_many_arguments, reason = "Many system parameters")]
+fn extract_renderables<T: VelloRenderable + Component + Clone>(
+    mut commands: Commands,
+    views: VelloViewLayers,
+    hierarchy: VelloHierarchy,
+    ui_clips: VelloUiClips,
+    world_items: Extract<
+        Query<
+            (
+                Entity,
+                Ref<T>,
+                &GlobalTransform,
+                &ViewVisibility,
+                &InheritedVisibility,
+                Option<&RenderLayers>,
+                Option<&VelloBlendMode>,
+            ),
+            Without<Node>,
+        >,
+    >,
+    ui_items: Extract<
+        Query<(
+            Entity,
+            Ref<T>,
+            &ComputedNode,
+            &ComputedUiRenderTargetInfo,
+            &UiGlobalTransform,
+            &InheritedVisibility,
+            Option<&RenderLayers>,
+            Option<&ComputedUiTargetCamera>,
+            Option<&CalculatedClip>,
+            Option<&VelloBlendMode>,
+        )>,
+    >,
+    changed_items: Extract<
+        Query<
+            (),
+            (
+                With<T>,
+                Or<(
+                    Changed<T>,
+                    Changed<GlobalTransform>,
+                    Changed<UiGlobalTransform>,
+                    Changed<ComputedNode>,
+                    Changed<ComputedUiTargetCamera>,
+                    Changed<CalculatedClip>,
+                    Changed<ViewVisibility>,
+                    Changed<InheritedVisibility>,
+                    Changed<RenderLayers>,
+                    Changed<VelloBlendMode>,
+                )>,
+            ),
+        >,
+    >,
+    mut extracted: ResMut<ExtractedRenderables<T>>,
+    mut n_last_frame: Local<usize>,
+    mut frame_changes: ResMut<VelloFrameChanges>,
+) {
+    // Items drawn by any view, counting texture targets on the frames they skip
+    let mut drawable = EntityHashSet::default();
+
+    for (
+        entity,
+        item,
+        transform,
+        view_visibility,
+        inherited_visibility,
+        render_layers,
+        blend_mode,
+    ) in world_items.iter()
+    {
+        if !inherited_visibility.get() {
+            continue;
+        }
+        let render_layers = render_layers.unwrap_or_default();
+        // Kept up to date on frames a texture target drawing it skips
+        if views.may_draw_world_item(view_visibility, render_layers) {
+            extracted.extract(entity, &item);
+            drawable.insert(entity);
+        }
+        if views.draws_world_item(view_visibility, render_layers) {
+            commands.spawn((
+                ExtractedRenderable::<T> {
+                    entity,
+                    transform: *transform,
+                    render_layers: render_layers.clone(),
+                    blend_mode: blend_mode.copied().unwrap_or_default(),
+                    opacity: hierarchy.opacity(entity),
+                    group: hierarchy.group(entity),
+                    clip: hierarchy.clip(entity),
+                    sort_key: hierarchy.sort_key(entity, transform),
+                    _type: PhantomData,
+                },
+                TemporaryRenderEntity,
+            ));
+        }
+    }
+
+    for (
+        entity,
+        item,
+        ui_node,
+        ui_render_target,
+        ui_transform,
+        inherited_visibility,
+        render_layers,
+        target_camera,
+        calc_clip,
+        blend_mode,
+    ) in ui_items.iter()
+    {
+        if !inherited_visibility.get() {
+            continue;
+        }
+        let render_layers = render_layers.unwrap_or_default();
+        if views.draws_ui_item(render_layers) {
+            extracted.extract(entity, &item);
+            let (clip, clip_shapes) = ui_clips.get(entity, calc_clip);
+            commands.spawn((
+                ExtractedUiRenderable::<T> {
+                    entity,
+                    ui_transform: *ui_transform,
+                    ui_node: *ui_node,
+                    ui_render_target: *ui_render_target,
+                    target_camera: target_camera.and_then(ComputedUiTargetCamera::get),
+                    render_layers: render_layers.clone(),
+                    clip: to_kurbo_clip(clip),
+                    clip_shapes,
+                    blend_mode: blend_mode.copied().unwrap_or_default(),
+                    opacity: hierarchy.opacity(entity),
+                    group: hierarchy.group(entity),
+                    _type: PhantomData,
+                },
+                TemporaryRenderEntity,
+            ));
+            drawable.insert(entity);
+        }
+    }
+
+    // Forget the items no longer drawn
+    extracted
+        .prepared
+        .retain(|entity, _| drawable.contains(entity));
+
+    // Redraw if any item was added, removed or changed
+    if drawable.len() != *n_last_frame || !changed_items.is_empty() {
+        frame_changes.items_changed = true;
+    }
+    *n_last_frame = drawable.len();
+}
+
+/// Prepares the items extracted this frame.
+fn prepare_renderables<T: VelloRenderable + Component + Clone>(
+    mut extracted: ResMut<ExtractedRenderables<T>>,
+) {
+    let ExtractedRenderables {
+        prepared,
+        unprepared,
+    } = &mut *extracted;
+    for (entity, mut item) in unprepared.drain(..) {
+        item.prepare();
+        prepared.insert(entity, Arc::new(item));
+    }
+}
+
+fn queue_renderables<T: VelloRenderable + Component + Clone>(
+    views: Query<(Entity, &ExtractedVelloView)>,
+    world_items: Query<&ExtractedRenderable<T>>,
+    ui_items: Query<&ExtractedUiRenderable<T>>,
+    items: Res<ExtractedRenderables<T>>,
+    mut queue: ResMut<VelloCustomRenderQueue>,
+) {
+    for extracted in world_items.iter() {
+        let Some(item) = items.prepared.get(&extracted.entity) else {
+            continue;
+        };
+        for (view_entity, view) in views.iter() {
+            if !extracted.render_layers.intersects(&view.render_layers) {
+                continue;
+            }
+            queue.world.entry(view_entity).or_default().push((
+                extracted.sort_key,
+                extracted.group,
+                extracted.clip,
+                VelloWorldRenderItem::Custom {
+                    affine: world_affine(view, &extracted.transform),
+                    blend_mode: extracted.blend_mode,
+                    opacity: extracted.opacity,
+                    item: item.clone(),
+                },
+            ));
+        }
+    }
+
+    let ui_views: Vec<_> = views
+        .iter()
+        .filter_map(|(view_entity, view)| {
+            view.camera
+                .map(|camera| (view_entity, camera, view.render_layers.clone()))
+        })
+        .collect();
+    for extracted in ui_items.iter() {
+        let Some(item) = items.prepared.get(&extracted.entity) else {
+            continue;
+        };
+        let affine = ui_affine(
+            &extracted.ui_transform,
+            &extracted.ui_node,
+            extracted.ui_render_target.scale_factor(),
+        );
+        for view_entity in
+            ui_node_views(&ui_views, extracted.target_camera, &extracted.render_layers)
+        {
+            queue.ui.entry(view_entity).or_default().push((
+                extracted.ui_node.stack_index,
+                extracted.group,
+                VelloUiRenderItem::Custom {
+                    affine,
+                    clip: extracted.clip,
+                    clip_shapes: extracted.clip_shapes.clone(),
+                    blend_mode: extracted.blend_mode,
+                    opacity: extracted.opacity,
+                    item: item.clone(),
+                },
+            ));
+        }
+    }
+}
+
+/// Encodes a [`VelloRenderable`] item, applying its alpha, the entity's opacity and blend mode.
+pub(crate) fn encode_renderable(
+    scene: &mut Scene,
+    item: &dyn VelloRenderable,
+    affine: Affine,
+    blend_mode: VelloBlendMode,
+    opacity: f32,
+) {
+    let alpha = item.alpha() * opacity;
+    if alpha <= 0.0 {
+        return;
+    }
+    let layer = push_item_layer(scene, blend_mode, alpha, affine, item.bounds());
+    item.encode(scene, affine);
+    if layer {
+        scene.pop_layer();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::test_utils::Square;
+
+    #[test]
+    fn renderable_opacity_wraps_the_item_in_a_layer() {
+        let encode = |alpha, opacity| {
+            let mut scene = vello::Scene::new();
+            encode_renderable(
+                &mut scene,
+                &Square { red: 1.0, alpha },
+                Affine::IDENTITY,
+                VelloBlendMode::default(),
+                opacity,
+            );
+            let encoding = scene.encoding();
+            (encoding.n_paths, encoding.n_clips, encoding.n_open_clips)
+        };
+
+        assert_eq!(encode(1.0, 1.0), (1, 0, 0));
+        let (n_paths, n_clips, n_open_clips) = encode(0.5, 1.0);
+        assert!(n_paths > 1 && n_clips > 0);
+        assert_eq!(n_open_clips, 0);
+        // The entity's opacity fades the item like its own alpha.
+        assert_eq!(encode(1.0, 0.5), encode(0.5, 1.0));
+        // Invisible items are skipped entirely.
+        assert_eq!(encode(0.0, 1.0), (0, 0, 0));
+        assert_eq!(encode(1.0, 0.0), (0, 0, 0));
+    }
+}
@@ -1,27 +1,49 @@
+use std::{
+    any::TypeId,
+    cmp::Reverse,
+    marker::PhantomData,
+    panic::{self, AssertUnwindSafe},
+    sync::{Arc, Mutex},
+};
+
 use bevy::{
     asset::RenderAssetUsages,
-    camera::visibility::NoFrustumCulling,
+    camera::visibility::{NoFrustumCulling, VisibleEntities},
+    ecs::entity::{EntityHashMap, EntityHashSet},
     image::ToExtents,
     mesh::Indices,
+    platform::collections::HashMap,
     prelude::*,
     render::{
+        MainWorld,
         render_asset::RenderAssets,
         render_resource::{
-            Extent3d, PrimitiveTopology, TextureDescriptor, TextureDimension, TextureFormat,
-            TextureUsages,
+            Extent3d, PrimitiveTopology, TextureDescriptor, TextureDimension, TextureUsages,
         },
         renderer::{RenderDevice, RenderQueue},
         texture::GpuImage,
     },
-    sprite_render::MeshMaterial2d,
+    sprite_render::{AlphaMode2d, MeshMaterial2d},
+    tasks::{block_on, poll_once},
     window::PrimaryWindow,
 };
-use vello::{RenderParams, Scene};
 
 use super::{
-    VelloCanvasMaterial, VelloCanvasSettings, VelloEntityCountData, VelloFrameProfileData,
-    VelloRenderQueue, VelloRenderSettings, VelloRenderer, VelloWorldRenderItem,
-    extract::VelloRenderTarget, prepare::PreparedAffine,
+    PreparedClip, VelloCanvasFormat, VelloCanvasMaterial, VelloCanvasSettings, VelloFrameChanges,
+    VelloFrameProfileData, VelloRecoveryPolicy, VelloRecoveryState, VelloRenderError,
+    VelloRenderQueue, VelloRenderSettings, VelloRenderer, VelloRendererMessages,
+    VelloRendererReady, VelloRendererTask, VelloTextureRefresh, VelloTextureTarget,
+    VelloViewSettings, VelloWorldRenderItem,
+    encode::ItemEncoder,
+    extract::{
+        ExtractedClipSource, ExtractedUiVelloGroup, ExtractedVelloClip, ExtractedVelloGroup,
+        ExtractedVelloView, VelloCanvas, VelloRenderTarget,
+    },
+    init_renderer, insert_renderer,
+    linear::VelloLinearConverter,
+    prepare::{PreparedAffines, ui_affine, world_affine},
+    renderable::VelloCustomRenderQueue,
+    ui_canvas::VelloUiCanvases,
 };
 #[cfg(feature = "lottie")]
 use crate::integrations::lottie::render::{ExtractedUiVelloLottie, ExtractedVelloLottie2d};
@@ -40,7 +62,7 @@ use crate::{
 /// Convert a Bevy UI clip rect to a Vello kurbo rect.
 ///
 /// `CalculatedClip` is already in physical pixels — Bevy resolves layout
-/// against `physical_size` — matching the coordinate space of `PreparedAffine`.
+/// against `physical_size` — matching the coordinate space of `PreparedAffines`.
 ///
 /// Per-axis overflow clipping (e.g. `Overflow::clip_y()`) produces rects with
 /// `f32::INFINITY` / `f32::NEG_INFINITY` on the unconstrained axis. Vello
@@ -71,7 +93,37 @@ pub(crate) fn to_kurbo_clip(clip: Option<Rect>) -> Option<vello::kurbo::Rect> {
     })
 }
 
-pub fn setup_image(images: &mut Assets<Image>, width: u32, height: u32) -> Handle<Image> {
+/// The canvas z layer of a world item at `z`, given the sorted layer boundaries.
+///
+/// Items exactly at a boundary belong to the layer above it.
+pub(crate) fn canvas_layer(boundaries: &[f32], z: f32) -> usize {
+    boundaries.partition_point(|boundary| *boundary <= z)
+}
+
+/// The z at which a canvas layer is sorted among Bevy's 2D items.
+///
+/// Each layer sits just below its upper boundary, so a Bevy item at a boundary draws above the
+/// Vello items below it and below the Vello items at or above it. The top layer sits above
+/// everything. A single canvas without boundaries stays at z = 0.
+pub(crate) fn canvas_layer_z(boundaries: &[f32], layer: usize) -> f32 {
+    match boundaries.get(layer) {
+        Some(upper) => upper.next_down(),
+        None if boundaries.is_empty() => 0.0,
+        None => f32::MAX,
+    }
+}
+
+pub fn setup_image(
+    images: &mut Assets<Image>,
+    width: u32,
+    height: u32,
+    format: VelloCanvasFormat,
+) -> Handle<Image> {
+    images.add(canvas_image(width, height, format))
+}
+
+/// Creates an image Vello can render into.
+pub fn canvas_image(width: u32, height: u32, format: VelloCanvasFormat) -> Image {
     let size = Extent3d {
         width,
         height,
@@ -84,12 +136,17 @@ pub fn setup_image(images: &mut Assets<Image>, width: u32, height: u32) -> Handl
             label: None,
             size,
             dimension: TextureDimension::D2,
-            format: TextureFormat::Rgba8Unorm,
+            format: format.texture_format(),
             mip_level_count: 1,
             sample_count: 1,
             usage: TextureUsages::TEXTURE_BINDING
                 | TextureUsages::COPY_DST
-                | TextureUsages::STORAGE_BINDING,
+                | match format {
+                    // Vello renders directly into 8-bit images
+                    VelloCanvasFormat::Rgba8Unorm => TextureUsages::STORAGE_BINDING,
+                    // Float images are drawn into by the linear conversion pass
+                    VelloCanvasFormat::Rgba16Float => TextureUsages::RENDER_ATTACHMENT,
+                },
             view_formats: &[],
         },
         ..default()
@@ -97,471 +154,646 @@ pub fn setup_image(images: &mut Assets<Image>, width: u32, height: u32) -> Handl
 
     // fill image.data with zeroes
     image.resize(size);
-    images.add(image)
+    image
 }
 
 #[allow(clippy::too_many_arguments, reason = "Many features gates")]
 pub fn sort_render_items(
-    view_world_scenes: Query<(&PreparedAffine, &ExtractedVelloScene2d)>,
-    view_ui_scenes: Query<(&PreparedAffine, &ExtractedUiVelloScene)>,
-    #[cfg(feature = "text")] view_world_text: Query<(&PreparedAffine, &ExtractedVelloText2d)>,
-    #[cfg(feature = "text")] view_ui_text: Query<(&PreparedAffine, &ExtractedUiVelloText)>,
-    #[cfg(feature = "svg")] view_world_svgs: Query<(&PreparedAffine, &ExtractedVelloSvg2d)>,
-    #[cfg(feature = "svg")] view_ui_svgs: Query<(&PreparedAffine, &ExtractedUiVelloSvg)>,
+    views: Query<(Entity, &ExtractedVelloView)>,
+    canvas_settings: Res<VelloCanvasSettings>,
+    view_world_scenes: Query<(&PreparedAffines, &ExtractedVelloScene2d)>,
+    view_ui_scenes: Query<(&PreparedAffines, &ExtractedUiVelloScene)>,
+    #[cfg(feature = "text")] view_world_text: Query<(&PreparedAffines, &ExtractedVelloText2d)>,
+    #[cfg(feature = "text")] view_ui_text: Query<(&PreparedAffines, &ExtractedUiVelloText)>,
+    #[cfg(feature = "svg")] view_world_svgs: Query<(&PreparedAffines, &ExtractedVelloSvg2d)>,
+    #[cfg(feature = "svg")] view_ui_svgs: Query<(&PreparedAffines, &ExtractedUiVelloSvg)>,
     #[cfg(feature = "lottie")] view_world_lotties: Query<(
-        &PreparedAffine,
+        &PreparedAffines,
         &ExtractedVelloLottie2d,
     )>,
-    #[cfg(feature = "lottie")] view_ui_lotties: Query<(&PreparedAffine, &ExtractedUiVelloLottie)>,
+    #[cfg(feature = "lottie")] view_ui_lotties: Query<(&PreparedAffines, &ExtractedUiVelloLottie)>,
+    world_groups: Query<&ExtractedVelloGroup>,
+    ui_groups: Query<&ExtractedUiVelloGroup>,
+    world_clips: Query<&ExtractedVelloClip>,
+    mut custom_render_queue: ResMut<VelloCustomRenderQueue>,
     mut final_render_queue: ResMut<VelloRenderQueue>,
-    frame_data: ResMut<VelloEntityCountData>,
 ) {
-    let mut n_world_items: usize = 0;
-    let mut n_ui_items: usize = 0;
+    let mut world_queues = GroupedQueues::new(
+        views.iter().map(|(view_entity, _)| view_entity),
+        world_groups.iter().map(|group| group.entity),
+    );
+    let mut ui_queues = GroupedQueues::new(
+        views.iter().map(|(view_entity, _)| view_entity),
+        ui_groups.iter().map(|group| group.entity),
+    );
+    let mut clips = ClipChains::new(&views, &world_clips);
 
     // Scenes
-    n_world_items += frame_data.n_world_scenes as usize;
-    n_ui_items += frame_data.n_ui_scenes as usize;
-    // Text
-    #[cfg(feature = "text")]
-    {
-        n_world_items += frame_data.n_world_texts as usize;
-        n_ui_items += frame_data.n_ui_texts as usize;
+    for (affines, scene) in view_world_scenes.iter() {
+        for (view_entity, &affine) in affines.iter() {
+            world_queues.push(
+                *view_entity,
+                scene.group,
+                scene.sort_key,
+                clips.apply(
+                    *view_entity,
+                    scene.clip,
+                    VelloWorldRenderItem::Scene {
+                        affine,
+                        item: scene.clone(),
+                    },
+                ),
+            );
+        }
+    }
+    for (affines, scene) in view_ui_scenes.iter() {
+        for (view_entity, &affine) in affines.iter() {
+            ui_queues.push(
+                *view_entity,
+                scene.group,
+                scene.ui_node.stack_index,
+                VelloUiRenderItem::Scene {
+                    affine,
+                    clip: to_kurbo_clip(scene.clip),
+                    item: scene.clone(),
+                },
+            );
+        }
     }
-    // Svg
+
     #[cfg(feature = "svg")]
     {
-        n_world_items += frame_data.n_world_svgs as usize;
-        n_ui_items += frame_data.n_ui_svgs as usize;
+        for (affines, svg) in view_world_svgs.iter() {
+            for (view_entity, &affine) in affines.iter() {
+                world_queues.push(
+                    *view_entity,
+                    svg.group,
+                    svg.sort_key,
+                    clips.apply(
+                        *view_entity,
+                        svg.clip,
+                        VelloWorldRenderItem::Svg {
+                            affine,
+                            item: svg.clone(),
+                        },
+                    ),
+                );
+            }
+        }
+        for (affines, svg) in view_ui_svgs.iter() {
+            for (view_entity, &affine) in affines.iter() {
+                ui_queues.push(
+                    *view_entity,
+                    svg.group,
+                    svg.ui_node.stack_index,
+                    VelloUiRenderItem::Svg {
+                        affine,
+                        clip: to_kurbo_clip(svg.clip),
+                        item: svg.clone(),
+                    },
+                );
+            }
+        }
     }
-    // Lottie
+
     #[cfg(feature = "lottie")]
     {
-        n_world_items += frame_data.n_world_lotties as usize;
-        n_ui_items += frame_data.n_ui_lotties as usize;
+        for (affines, lottie) in view_world_lotties.iter() {
+            for (view_entity, &affine) in affines.iter() {
+                world_queues.push(
+                    *view_entity,
+                    lottie.group,
+                    lottie.sort_key,
+                    clips.apply(
+                        *view_entity,
+                        lottie.clip,
+                        VelloWorldRenderItem::Lottie {
+                            affine,
+                            item: lottie.clone(),
+                        },
+                    ),
+                );
+            }
+        }
+        for (affines, lottie) in view_ui_lotties.iter() {
+            for (view_entity, &affine) in affines.iter() {
+                ui_queues.push(
+                    *view_entity,
+                    lottie.group,
+                    lottie.ui_node.stack_index,
+                    VelloUiRenderItem::Lottie {
+                        affine,
+                        clip: to_kurbo_clip(lottie.clip),
+                        item: lottie.clone(),
+                    },
+                );
+            }
+        }
     }
 
-    // Reserve space for the render queues to avoid reallocations
-    let mut world_render_queue: Vec<(f32, VelloWorldRenderItem)> =
-        Vec::with_capacity(n_world_items);
-    let mut ui_render_queue: Vec<(u32, VelloUiRenderItem)> = Vec::with_capacity(n_world_items);
+    #[cfg(feature = "text")]
+    {
+        for (affines, text) in view_world_text.iter() {
+            for (view_entity, &affine) in affines.iter() {
+                world_queues.push(
+                    *view_entity,
+                    text.group,
+                    text.sort_key,
+                    clips.apply(
+                        *view_entity,
+                        text.clip,
+                        VelloWorldRenderItem::Text {
+                            affine,
+                            item: text.clone(),
+                        },
+                    ),
+                );
+            }
+        }
+        for (affines, text) in view_ui_text.iter() {
+            for (view_entity, &affine) in affines.iter() {
+                ui_queues.push(
+                    *view_entity,
+                    text.group,
+                    text.ui_node.stack_index,
+                    VelloUiRenderItem::Text {
+                        affine,
+                        clip: to_kurbo_clip(text.clip),
+                        item: text.clone(),
+                    },
+                );
+            }
+        }
+    }
 
-    // Scenes
-    for (&affine, scene) in view_world_scenes.iter() {
-        world_render_queue.push((
-            scene.transform.translation().z,
-            VelloWorldRenderItem::Scene {
-                affine: *affine,
-                item: scene.clone(),
-            },
-        ));
-    }
-    for (&affine, scene) in view_ui_scenes.iter() {
-        ui_render_queue.push((
-            scene.ui_node.stack_index,
-            VelloUiRenderItem::Scene {
-                affine: *affine,
-                clip: to_kurbo_clip(scene.clip),
-                item: scene.clone(),
-            },
-        ));
+    // Items of registered renderable types
+    for (view_entity, items) in custom_render_queue.world.drain() {
+        for (sort_key, group, clip, item) in items {
+            world_queues.push(
+                view_entity,
+                group,
+                sort_key,
+                clips.apply(view_entity, clip, item),
+            );
+        }
+    }
+    for (view_entity, items) in custom_render_queue.ui.drain() {
+        for (stack_index, group, item) in items {
+            ui_queues.push(view_entity, group, stack_index, item);
+        }
     }
 
-    #[cfg(feature = "svg")]
-    {
-        for (&affine, svg) in view_world_svgs.iter() {
-            world_render_queue.push((
-                svg.transform.translation().z,
-                VelloWorldRenderItem::Svg {
-                    affine: *affine,
-                    item: svg.clone(),
+    // Fold the items of each group into a single item of its parent, nested groups first
+    let mut world_groups: Vec<_> = world_groups.iter().collect();
+    world_groups.sort_by_key(|group| Reverse(group.depth));
+    for group in world_groups {
+        for (view_entity, view) in views.iter() {
+            world_queues.fold(
+                view_entity,
+                group.entity,
+                group.parent,
+                group.sort_key,
+                |items| VelloWorldRenderItem::Group {
+                    affine: world_affine(view, &group.transform),
+                    item: group.clone(),
+                    items,
                 },
-            ));
-        }
-        for (&affine, svg) in view_ui_svgs.iter() {
-            ui_render_queue.push((
-                svg.ui_node.stack_index,
-                VelloUiRenderItem::Svg {
-                    affine: *affine,
-                    clip: to_kurbo_clip(svg.clip),
-                    item: svg.clone(),
+            );
+        }
+    }
+    let mut ui_groups: Vec<_> = ui_groups.iter().collect();
+    ui_groups.sort_by_key(|group| Reverse(group.depth));
+    for group in ui_groups {
+        let affine = ui_affine(
+            &group.ui_transform,
+            &group.ui_node,
+            group.ui_render_target.scale_factor(),
+        );
+        for (view_entity, _) in views.iter() {
+            ui_queues.fold(
+                view_entity,
+                group.entity,
+                group.parent,
+                group.ui_node.stack_index,
+                |items| VelloUiRenderItem::Group {
+                    affine,
+                    clip: to_kurbo_clip(group.clip),
+                    item: group.clone(),
+                    items,
                 },
-            ));
+            );
         }
     }
 
-    #[cfg(feature = "lottie")]
-    {
-        for (&affine, lottie) in view_world_lotties.iter() {
-            world_render_queue.push((
-                lottie.transform.translation().z,
-                VelloWorldRenderItem::Lottie {
-                    affine: *affine,
-                    item: lottie.clone(),
-                },
-            ));
-        }
-        for (&affine, lottie) in view_ui_lotties.iter() {
-            ui_render_queue.push((
-                lottie.ui_node.stack_index,
-                VelloUiRenderItem::Lottie {
-                    affine: *affine,
-                    clip: to_kurbo_clip(lottie.clip),
-                    item: lottie.clone(),
-                },
-            ));
+    // Render queues are rebuilt every frame, dropping views that no longer exist
+    final_render_queue.clear();
+    for (view_entity, world_render_queue) in world_queues.into_sorted() {
+        // Split into canvas z layers. Views with fewer targets, e.g. texture targets, draw all
+        // remaining items into their top layer.
+        let n_layers = views
+            .get(view_entity)
+            .map_or(1, |(_, view)| view.targets.len().max(1));
+        let mut layers: Vec<Vec<VelloWorldRenderItem>> =
+            (0..n_layers).map(|_| Vec::new()).collect();
+        for (sort_key, render_item) in world_render_queue {
+            let layer = canvas_layer(&canvas_settings.z_layers, sort_key.z).min(n_layers - 1);
+            layers[layer].push(render_item);
         }
+        final_render_queue.entry(view_entity).or_default().world = layers;
+    }
+    for (view_entity, ui_render_queue) in ui_queues.into_sorted() {
+        let view_render_queue = final_render_queue.entry(view_entity).or_default();
+        (view_render_queue.ui_stack_indices, view_render_queue.ui) =
+            ui_render_queue.into_iter().unzip();
     }
+}
 
-    #[cfg(feature = "text")]
-    {
-        for (&affine, text) in view_world_text.iter() {
-            world_render_queue.push((
-                text.transform.translation().z,
-                VelloWorldRenderItem::Text {
-                    affine: *affine,
-                    item: text.clone(),
-                },
-            ));
-        }
-        for (&affine, text) in view_ui_text.iter() {
-            ui_render_queue.push((
-                text.ui_node.stack_index,
-                VelloUiRenderItem::Text {
-                    affine: *affine,
-                    clip: to_kurbo_clip(text.clip),
-                    item: text.clone(),
-                },
-            ));
-        }
-    }
-
-    // Sort by render mode with screen space on top, then by z-index
-    world_render_queue.sort_unstable_by(|(a_z_index, _), (b_z_index, _)| {
-        a_z_index
-            .partial_cmp(b_z_index)
-            .unwrap_or(std::cmp::Ordering::Equal)
-    });
-    // Stable sort preserves insertion order for same-stack-index items,
-    // preventing scene/text render order from flipping between frames.
-    ui_render_queue.sort_by(|(a_stack_index, _), (b_stack_index, _)| {
-        a_stack_index
-            .partial_cmp(b_stack_index)
-            .unwrap_or(std::cmp::Ordering::Equal)
-    });
-
-    // Render queue is drained on render
-    final_render_queue.world.clear();
-    // Reserve space for the final render queue to avoid reallocations
-    final_render_queue.world.reserve(n_world_items);
-    final_render_queue
-        .world
-        .extend(world_render_queue.into_iter().map(|(_, r)| r));
-
-    // Same thing for UI
-    final_render_queue.ui.clear();
-    final_render_queue.ui.reserve(n_ui_items);
-    final_render_queue
-        .ui
-        .extend(ui_render_queue.into_iter().map(|(_, r)| r));
+/// The clip chains of world items, prepared once per view and clip.
+struct ClipChains<'a> {
+    views: EntityHashMap<&'a ExtractedVelloView>,
+    clips: EntityHashMap<&'a ExtractedVelloClip>,
+    prepared: HashMap<(Entity, Entity), Arc<[PreparedClip]>>,
+}
+
+impl<'a> ClipChains<'a> {
+    fn new(
+        views: &'a Query<(Entity, &ExtractedVelloView)>,
+        clips: &'a Query<&ExtractedVelloClip>,
+    ) -> Self {
+        Self {
+            views: views.iter().collect(),
+            clips: clips.iter().map(|clip| (clip.entity, clip)).collect(),
+            prepared: HashMap::default(),
+        }
+    }
+
+    /// Wraps an item of `view` in the clips it is in, starting from `clip`.
+    fn apply(
+        &mut self,
+        view: Entity,
+        clip: Option<Entity>,
+        item: VelloWorldRenderItem,
+    ) -> VelloWorldRenderItem {
+        let (Some(clip), Some(extracted_view)) = (clip, self.views.get(&view)) else {
+            return item;
+        };
+        let clips = &self.clips;
+        let chain = self.prepared.entry((view, clip)).or_insert_with(|| {
+            let mut chain: Vec<_> =
+                std::iter::successors(clips.get(&clip), |clip| clips.get(&clip.parent?))
+                    .map(|clip| prepare_clip(extracted_view, clip))
+                    .collect();
+            chain.reverse();
+            chain.into()
+        });
+        if chain.is_empty() {
+            return item;
+        }
+        VelloWorldRenderItem::Clipped {
+            clips: chain.clone(),
+            item: Box::new(item),
+        }
+    }
+}
+
+/// A clip in the pixel space of `view`.
+fn prepare_clip(view: &ExtractedVelloView, clip: &ExtractedVelloClip) -> PreparedClip {
+    match &clip.source {
+        ExtractedClipSource::Shape(shape) => PreparedClip::Shape {
+            affine: world_affine(view, &clip.transform),
+            shape: shape.clone(),
+        },
+        ExtractedClipSource::Mask {
+            scene,
+            transform,
+            offset,
+            mode,
+        } => PreparedClip::Mask {
+            affine: world_affine(view, transform) * *offset,
+            scene: scene.clone(),
+            mode: *mode,
+        },
+    }
+}
+
+/// The render items of every view, with the items of each [`VelloGroup`](super::VelloGroup) kept
+/// apart until they are folded into a single item of the group.
+pub(crate) struct GroupedQueues<K, T> {
+    /// Items drawn outside any group, per view.
+    top: EntityHashMap<Vec<(K, T)>>,
+    /// Items of each group, per view and main world group entity.
+    grouped: HashMap<(Entity, Entity), Vec<(K, T)>>,
+    /// The groups items may be drawn in. Items of any other group are drawn outside it.
+    groups: EntityHashSet,
+}
+
+impl<K: PartialOrd, T> GroupedQueues<K, T> {
+    pub fn new(
+        views: impl IntoIterator<Item = Entity>,
+        groups: impl IntoIterator<Item = Entity>,
+    ) -> Self {
+        Self {
+            top: views.into_iter().map(|view| (view, Vec::new())).collect(),
+            grouped: HashMap::default(),
+            groups: groups.into_iter().collect(),
+        }
+    }
+
+    /// Queues an item of `view`, in `group` if it has one. Items of unknown views are dropped.
+    pub fn push(&mut self, view: Entity, group: Option<Entity>, key: K, item: T) {
+        let Some(queue) = self.top.get_mut(&view) else {
+            return;
+        };
+        match group.filter(|group| self.groups.contains(group)) {
+            Some(group) => self
+                .grouped
+                .entry((view, group))
+                .or_default()
+                .push((key, item)),
+            None => queue.push((key, item)),
+        }
+    }
+
+    /// Sorts the items of `group` in `view`, if it has any, and queues them as a single item at
+    /// `key` in `parent`.
+    pub fn fold(
+        &mut self,
+        view: Entity,
+        group: Entity,
+        parent: Option<Entity>,
+        key: K,
+        into_item: impl FnOnce(Vec<T>) -> T,
+    ) {
+        let Some(mut items) = self.grouped.remove(&(view, group)) else {
+            return;
+        };
+        sort_items(&mut items);
+        let item = into_item(items.into_iter().map(|(_, item)| item).collect());
+        self.push(view, parent, key, item);
+    }
+
+    /// The sorted items drawn outside any group, per view.
+    pub fn into_sorted(self) -> impl Iterator<Item = (Entity, Vec<(K, T)>)> {
+        self.top.into_iter().map(|(view, mut items)| {
+            sort_items(&mut items);
+            (view, items)
+        })
+    }
+}
+
+/// Sorts items by their world sort key or stack index. The sort is stable, preserving insertion
+/// order for UI items with the same stack index, so their render order doesn't flip between frames.
+fn sort_items<K: PartialOrd, T>(items: &mut [(K, T)]) {
+    items.sort_by(|(a, _), (b, _)| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+}
+
+/// Why a target failed to render.
+enum RenderFailure {
+    /// The renderer panicked, while rendering this target or an earlier one.
+    Panicked,
+    Error(vello::Error),
+}
+
+/// Renders `scene` into `texture_view`. Panics in the renderer are caught and reported like other
+/// failures instead of unwinding through the render app. They poison the renderer, so it is
+/// re-created as allowed by the [`VelloRecoveryPolicy`].
+fn render_to_texture(
+    renderer: &Mutex<vello::Renderer>,
+    device: &vello::wgpu::Device,
+    queue: &vello::wgpu::Queue,
+    scene: &vello::Scene,
+    texture_view: &vello::wgpu::TextureView,
+    params: &vello::RenderParams,
+) -> Result<(), RenderFailure> {
+    let Ok(mut renderer) = renderer.lock() else {
+        return Err(RenderFailure::Panicked);
+    };
+    // The lock is moved in, so unwinding drops it and poisons the renderer
+    panic::catch_unwind(AssertUnwindSafe(move || {
+        renderer.render_to_texture(device, queue, scene, texture_view, params)
+    }))
+    .map_err(|_| RenderFailure::Panicked)?
+    .map_err(RenderFailure::Error)
 }
 
 /// Transforms all the vectors extracted from the game world and places them in
-/// a scene, and renders the scene to a texture with WGPU
+/// a scene per view, and renders each scene to its view's target texture with WGPU
 #[allow(clippy::complexity)]
 pub fn render_frame(
-    render_target: Single<&VelloRenderTarget>,
+    views: Query<(Entity, &ExtractedVelloView)>,
     #[cfg(feature = "text")] font_render_assets: Res<RenderAssets<VelloFont>>,
     gpu_images: Res<RenderAssets<GpuImage>>,
     device: Res<RenderDevice>,
     queue: Res<RenderQueue>,
     renderer: Res<VelloRenderer>,
-    #[cfg(feature = "lottie")] mut velato_renderer: ResMut<super::VelatoRenderer>,
+    mut linear_converter: ResMut<VelloLinearConverter>,
+    recovery_policy: Res<VelloRecoveryPolicy>,
+    mut recovery_state: ResMut<VelloRecoveryState>,
+    mut renderer_messages: ResMut<VelloRendererMessages>,
+    mut commands: Commands,
     render_settings: Res<VelloRenderSettings>,
     render_queue: Res<VelloRenderQueue>,
+    mut frame_changes: ResMut<VelloFrameChanges>,
     mut frame_profile: ResMut<VelloFrameProfileData>,
+    mut ui_canvases: ResMut<VelloUiCanvases>,
 ) {
-    let VelloRenderTarget(render_target_image) = *render_target;
-    let gpu_image = gpu_images.get(render_target_image).unwrap();
-
-    let mut scene_buffer = Scene::new();
-
-    // World Renderables
-    for render_item in render_queue.world.iter() {
-        match render_item {
-            VelloWorldRenderItem::Scene {
-                affine,
-                item: ExtractedVelloScene2d { scene, .. },
-            } => {
-                scene_buffer.append(scene, Some(*affine));
+    // Profile data is accumulated over all views
+    *frame_profile = VelloFrameProfileData {
+        n_skipped_frames: frame_profile.n_skipped_frames,
+        ..default()
+    };
+
+    // Targets are reused until something they show changes
+    if frame_changes.items_changed || render_settings.is_changed() || renderer.is_changed() {
+        frame_changes.up_to_date.clear();
+        for canvas in ui_canvases.values_mut().flatten() {
+            canvas.up_to_date = false;
+        }
+    }
+    let (mut n_rendered, mut n_skipped, mut n_failed) = (0, 0, 0);
+    let encoder = ItemEncoder {
+        #[cfg(feature = "text")]
+        fonts: &font_render_assets,
+        _lifetime: PhantomData,
+    };
+
+    for (view_entity, view) in views.iter() {
+        let Some(view_render_queue) = render_queue.get(&view_entity) else {
+            continue;
+        };
+        if view.changed {
+            for target in view.targets.iter() {
+                frame_changes.up_to_date.remove(&target.id());
             }
-            #[cfg(feature = "lottie")]
-            VelloWorldRenderItem::Lottie {
-                affine,
-                item:
-                    ExtractedVelloLottie2d {
-                        asset,
-                        alpha,
-                        theme,
-                        playhead,
-                        ..
-                    },
-            } => {
-                if *alpha <= 0.0 {
-                    continue;
-                }
-                if *alpha < 1.0 {
-                    scene_buffer.push_layer(
-                        vello::peniko::Fill::NonZero,
-                        vello::peniko::Mix::Normal,
-                        *alpha,
-                        *affine,
-                        &vello::kurbo::Rect::new(
-                            0.0,
-                            0.0,
-                            asset.composition.width as f64,
-                            asset.composition.height as f64,
-                        ),
-                    );
-                }
-                let recolored = theme.as_ref().map(|cs| cs.recolor(&asset.composition));
-                let animation = recolored.as_ref().unwrap_or(&asset.composition);
-                velato_renderer.append(
-                    animation,
-                    *playhead as f64,
-                    *affine,
-                    1.0,
-                    &mut scene_buffer,
-                );
-                if *alpha < 1.0 {
-                    scene_buffer.pop_layer();
-                }
+        }
+        // Each canvas z layer is rendered into its own target
+        let top_layer = view.targets.len().saturating_sub(1);
+        for (layer, target) in view.targets.iter().enumerate() {
+            let Some(gpu_image) = gpu_images.get(target) else {
+                continue;
+            };
+            if frame_changes.up_to_date.contains(&target.id()) {
+                n_skipped += 1;
+                continue;
             }
-            #[cfg(feature = "svg")]
-            VelloWorldRenderItem::Svg {
-                affine,
-                item: ExtractedVelloSvg2d { asset, alpha, .. },
-            } => {
-                if *alpha <= 0.0 {
+
+            // UI items are drawn above all world items, unless they have canvases of their own
+            let world_items = view_render_queue
+                .world
+                .get(layer)
+                .map_or(&[][..], Vec::as_slice);
+            let ui_items = if layer == top_layer && !view_render_queue.ui_interleaved {
+                view_render_queue.ui.as_slice()
+            } else {
+                &[]
+            };
+            let scene_buffer = encoder.encode(world_items, ui_items, view.render_scale as f64);
+            frame_profile.add_scene(&scene_buffer);
+
+            // Vello can only render into 8-bit targets, float targets are converted afterwards
+            let convert = VelloLinearConverter::needs_conversion(gpu_image.texture_format);
+            let render_view = if convert {
+                linear_converter.intermediate(device.wgpu_device(), target.id(), gpu_image.size)
+            } else {
+                &gpu_image.texture_view
+            };
+            let render_result = render_to_texture(
+                &renderer,
+                device.wgpu_device(),
+                &queue,
+                &scene_buffer,
+                render_view,
+                // Only the lowest canvas z layer is cleared to the base color
+                &view.settings.render_params(
+                    layer == 0,
+                    gpu_image.size.width,
+                    gpu_image.size.height,
+                    &render_settings,
+                ),
+            );
+            // The target keeps its last good frame, and is retried next frame
+            match render_result {
+                Ok(()) => {}
+                Err(RenderFailure::Panicked) => {
+                    tracing::error!("Failed to render Vello target: the renderer panicked");
+                    renderer_messages.errors.push(VelloRenderError::Poisoned {
+                        camera: view.camera,
+                        target: target.id(),
+                    });
+                    n_failed += 1;
                     continue;
                 }
-                if *alpha < 1.0 {
-                    scene_buffer.push_layer(
-                        vello::peniko::Fill::NonZero,
-                        vello::peniko::Mix::Normal,
-                        *alpha,
-                        *affine,
-                        &vello::kurbo::Rect::new(0.0, 0.0, asset.width as f64, asset.height as f64),
-                    );
-                }
-                scene_buffer.append(&asset.scene, Some(*affine));
-                if *alpha < 1.0 {
-                    scene_buffer.pop_layer();
+                Err(RenderFailure::Error(e)) => {
+                    tracing::error!("Failed to render Vello target: {e}");
+                    renderer_messages.errors.push(VelloRenderError::Render {
+                        camera: view.camera,
+                        target: target.id(),
+                        error: Arc::new(e),
+                    });
+                    n_failed += 1;
+                    continue;
                 }
             }
-            #[cfg(feature = "text")]
-            VelloWorldRenderItem::Text {
-                affine,
-                item:
-                    ExtractedVelloText2d {
-                        text, text_anchor, ..
-                    },
-            } => {
-                if let Some(font) = font_render_assets.get(text.style.font.id()) {
-                    font.render(
-                        &mut scene_buffer,
-                        *affine,
-                        &text.value,
-                        &text.style,
-                        text.text_align,
-                        text.max_advance,
-                        *text_anchor,
-                        None,
-                        None,
-                    );
-                }
+            if convert {
+                linear_converter.convert(
+                    device.wgpu_device(),
+                    &queue,
+                    target.id(),
+                    &gpu_image.texture_view,
+                    gpu_image.texture_format,
+                    view.settings.intensity,
+                );
             }
+            frame_changes.up_to_date.insert(target.id());
+            n_rendered += 1;
         }
-    }
 
-    // Ui Renderables
-    for render_item in render_queue.ui.iter() {
-        // Skip fully transparent items before pushing any layers, so that
-        // clip push/pop balance is never a concern for early returns.
-        let skip = match render_item {
-            #[cfg(feature = "lottie")]
-            VelloUiRenderItem::Lottie { item, .. } => item.alpha <= 0.0,
-            #[cfg(feature = "svg")]
-            VelloUiRenderItem::Svg { item, .. } => item.alpha <= 0.0,
-            _ => false,
-        };
-        if skip {
+        // UI interleaved with native UI is rendered into a canvas per stacking range
+        let Some(view_ui_canvases) = view.camera.and_then(|camera| ui_canvases.get_mut(&camera))
+        else {
             continue;
-        }
-
-        // Extract the clip rect (pre-scaled to physical pixels in sort_render_items)
-        let clip = match render_item {
-            VelloUiRenderItem::Scene { clip, .. } => clip,
-            #[cfg(feature = "lottie")]
-            VelloUiRenderItem::Lottie { clip, .. } => clip,
-            #[cfg(feature = "svg")]
-            VelloUiRenderItem::Svg { clip, .. } => clip,
-            #[cfg(feature = "text")]
-            VelloUiRenderItem::Text { clip, .. } => clip,
         };
+        for canvas in view_ui_canvases.iter_mut() {
+            if canvas.up_to_date && !view.changed {
+                n_skipped += 1;
+                continue;
+            }
+            let Some(ui_items) = view_render_queue.ui.get(canvas.items.clone()) else {
+                continue;
+            };
+            let scene_buffer = encoder.encode(&[], ui_items, view.render_scale as f64);
+            frame_profile.add_scene(&scene_buffer);
 
-        if let Some(clip_rect) = clip {
-            scene_buffer.push_clip_layer(
-                vello::peniko::Fill::NonZero,
-                vello::kurbo::Affine::IDENTITY,
-                clip_rect,
+            let top_target = view
+                .targets
+                .last()
+                .map_or_else(AssetId::default, Handle::id);
+            let render_result = render_to_texture(
+                &renderer,
+                device.wgpu_device(),
+                &queue,
+                &scene_buffer,
+                &canvas.texture_view,
+                &view
+                    .settings
+                    .render_params(false, canvas.size.x, canvas.size.y, &render_settings),
             );
-        }
-
-        match render_item {
-            VelloUiRenderItem::Scene {
-                affine,
-                item: ExtractedUiVelloScene { scene, .. },
-                ..
-            } => {
-                scene_buffer.append(scene, Some(*affine));
-            }
-            #[cfg(feature = "lottie")]
-            VelloUiRenderItem::Lottie {
-                affine,
-                item:
-                    ExtractedUiVelloLottie {
-                        asset,
-                        alpha,
-                        theme,
-                        playhead,
-                        ..
-                    },
-                ..
-            } => {
-                if *alpha < 1.0 {
-                    scene_buffer.push_layer(
-                        vello::peniko::Fill::NonZero,
-                        vello::peniko::Mix::Normal,
-                        *alpha,
-                        *affine,
-                        &vello::kurbo::Rect::new(
-                            0.0,
-                            0.0,
-                            asset.composition.width as f64,
-                            asset.composition.height as f64,
-                        ),
-                    );
-                }
-                let recolored = theme.as_ref().map(|cs| cs.recolor(&asset.composition));
-                let animation = recolored.as_ref().unwrap_or(&asset.composition);
-                velato_renderer.append(
-                    animation,
-                    *playhead as f64,
-                    *affine,
-                    1.0,
-                    &mut scene_buffer,
-                );
-                if *alpha < 1.0 {
-                    scene_buffer.pop_layer();
-                }
-            }
-            #[cfg(feature = "svg")]
-            VelloUiRenderItem::Svg {
-                affine,
-                item: ExtractedUiVelloSvg { asset, alpha, .. },
-                ..
-            } => {
-                if *alpha < 1.0 {
-                    scene_buffer.push_layer(
-                        vello::peniko::Fill::NonZero,
-                        vello::peniko::Mix::Normal,
-                        *alpha,
-                        *affine,
-                        &vello::kurbo::Rect::new(0.0, 0.0, asset.width as f64, asset.height as f64),
-                    );
-                }
-                scene_buffer.append(&asset.scene, Some(*affine));
-                if *alpha < 1.0 {
-                    scene_buffer.pop_layer();
+            match render_result {
+                Ok(()) => {}
+                Err(RenderFailure::Panicked) => {
+                    tracing::error!("Failed to render Vello UI canvas: the renderer panicked");
+                    renderer_messages.errors.push(VelloRenderError::Poisoned {
+                        camera: view.camera,
+                        target: top_target,
+                    });
+                    n_failed += 1;
+                    continue;
                 }
-            }
-            #[cfg(feature = "text")]
-            VelloUiRenderItem::Text {
-                affine,
-                clip,
-                item:
-                    ExtractedUiVelloText {
-                        text,
-                        text_anchor,
-                        ui_node,
-                        ui_render_target,
-                        ..
-                    },
-            } => {
-                if let Some(font) = font_render_assets.get(text.style.font.id()) {
-                    let logical_size = ui_node.size() / ui_render_target.scale_factor();
-                    font.render(
-                        &mut scene_buffer,
-                        *affine,
-                        &text.value,
-                        &text.style,
-                        text.text_align,
-                        text.max_advance,
-                        *text_anchor,
-                        Some(logical_size),
-                        *clip,
-                    );
+                Err(RenderFailure::Error(e)) => {
+                    tracing::error!("Failed to render Vello UI canvas: {e}");
+                    renderer_messages.errors.push(VelloRenderError::Render {
+                        camera: view.camera,
+                        target: top_target,
+                        error: Arc::new(e),
+                    });
+                    n_failed += 1;
+                    continue;
                 }
             }
+            canvas.up_to_date = true;
+            n_rendered += 1;
         }
+    }
 
-        if clip.is_some() {
-            scene_buffer.pop_layer();
+    if n_rendered == 0 && n_skipped > 0 {
+        frame_profile.n_skipped_frames += 1;
+    }
+
+    if n_failed > 0 {
+        if recovery_state.reinit_attempts < recovery_policy.max_reinit_attempts {
+            recovery_state.reinit_attempts += 1;
+            tracing::warn!(
+                "Re-initializing vello after a failed frame, attempt {} of {}",
+                recovery_state.reinit_attempts,
+                recovery_policy.max_reinit_attempts
+            );
+            commands.queue(init_renderer);
         }
+    } else if n_rendered > 0 {
+        recovery_state.reinit_attempts = 0;
     }
 
-    frame_profile.n_paths = scene_buffer.encoding().n_paths;
-    frame_profile.n_path_segs = scene_buffer.encoding().n_path_segments;
-    frame_profile.n_clips = scene_buffer.encoding().n_clips;
-    frame_profile.n_open_clips = scene_buffer.encoding().n_open_clips;
-    #[cfg(feature = "text")]
-    {
-        frame_profile.n_glyphs = scene_buffer.encoding().resources.glyphs.len() as u32;
-        frame_profile.n_glyph_runs = scene_buffer.encoding().resources.glyph_runs.len() as u32;
-    }
-
-    renderer
-        .lock()
-        .unwrap()
-        .render_to_texture(
-            device.wgpu_device(),
-            &queue,
-            &scene_buffer,
-            &gpu_image.texture_view,
-            &RenderParams {
-                base_color: vello::peniko::Color::TRANSPARENT,
-                width: gpu_image.size.width,
-                height: gpu_image.size.height,
-                antialiasing_method: render_settings.antialiasing,
-            },
-        )
-        .unwrap();
+    linear_converter.retain_targets(|target| gpu_images.get(target).is_some());
 }
 
 // Returns the width and height of the available viewport space;
 // camera viewport size if present, otherwise default to window size
-pub fn get_viewport_size(
-    camera_query: Query<&Camera, With<VelloView>>,
-    window: Option<Single<&Window, With<PrimaryWindow>>>,
-) -> (u32, u32) {
-    if let Ok(camera) = camera_query.single()
-        && let Some(viewport) = &camera.viewport
-    {
-        return (viewport.physical_size.x, viewport.physical_size.y);
+pub fn get_viewport_size(camera: &Camera, window: Option<&Window>) -> (u32, u32) {
+    if let Some(size) = camera.physical_viewport_size() {
+        return (size.x, size.y);
     }
 
-    if let Some(window) = window.as_deref() {
+    if let Some(window) = window {
         (
             window.resolution.physical_width(),
             window.resolution.physical_height(),
@@ -574,44 +806,85 @@ pub fn get_viewport_size(
     }
 }
 
+// Returns the width and height of a view's canvas: the viewport size, scaled by the render scale
+pub fn get_canvas_size(
+    camera: &Camera,
+    settings: Option<&VelloViewSettings>,
+    window: Option<&Window>,
+) -> (u32, u32) {
+    let (width, height) = get_viewport_size(camera, window);
+    let render_scale = settings.map_or(1.0, VelloViewSettings::effective_render_scale);
+    scaled_canvas_size(width, height, render_scale)
+}
+
+pub(crate) fn scaled_canvas_size(width: u32, height: u32, render_scale: f32) -> (u32, u32) {
+    if width == 0 || height == 0 {
+        return (0, 0);
+    }
+    let scale = |length: u32| ((length as f32 * render_scale).round() as u32).max(1);
+    (scale(width), scale(height))
+}
+
 pub fn resize_rendertargets(
-    mut query: Query<(&mut VelloRenderTarget, &MeshMaterial2d<VelloCanvasMaterial>)>,
+    mut views: Query<
+        (
+            Entity,
+            &Camera,
+            &mut VelloRenderTarget,
+            Option<&VelloViewSettings>,
+        ),
+        With<VelloView>,
+    >,
+    canvases: Query<(&VelloCanvas, &MeshMaterial2d<VelloCanvasMaterial>)>,
     mut images: ResMut<Assets<Image>>,
     mut target_materials: ResMut<Assets<VelloCanvasMaterial>>,
     window: Option<Single<&Window, With<PrimaryWindow>>>,
-    camera_query: Query<&Camera, With<VelloView>>,
 ) {
-    let (width, height) = get_viewport_size(camera_query, window);
+    for (view_entity, camera, mut target, settings) in views.iter_mut() {
+        let (width, height) = get_canvas_size(camera, settings, window.as_deref().copied());
+        let format = settings.map_or_else(VelloCanvasFormat::default, |settings| {
+            settings.canvas_format
+        });
 
-    let size = Extent3d {
-        width,
-        height,
-        ..default()
-    };
-    if size.width == 0 || size.height == 0 {
-        return;
-    }
-    for (mut target, target_mat_handle) in query.iter_mut() {
-        if let Some(image) = images.get(target.0.id())
-            && image.size().to_extents() == size
-        {
+        let size = Extent3d {
+            width,
+            height,
+            ..default()
+        };
+        if size.width == 0 || size.height == 0 {
             continue;
         }
 
-        let image = setup_image(&mut images, width, height);
-        if let Some(mat) = target_materials.get_mut(target_mat_handle.id()) {
-            target.0 = image.clone();
-            mat.texture = image;
+        for (layer, layer_image) in target.0.iter_mut().enumerate() {
+            if let Some(image) = images.get(layer_image.id())
+                && image.size().to_extents() == size
+                && image.texture_descriptor.format == format.texture_format()
+            {
+                continue;
+            }
+
+            let image = setup_image(&mut images, width, height, format);
+            for (_, target_mat_handle) in canvases
+                .iter()
+                .filter(|(canvas, _)| canvas.view == view_entity && canvas.layer == layer)
+            {
+                if let Some(mat) = target_materials.get_mut(target_mat_handle.id()) {
+                    mat.texture = image.clone();
+                    mat.format = format;
+                }
+            }
+            *layer_image = image;
+            tracing::debug!(
+                size = format!(
+                    "Resized Vello render image to {:?}",
+                    (size.width, size.height)
+                )
+            );
         }
-        tracing::debug!(
-            size = format!(
-                "Resized Vello render image to {:?}",
-                (size.width, size.height)
-            )
-        );
     }
 }
 
+/// Spawns the canvases of every new [`VelloView`], one per canvas z layer.
 #[allow(clippy::complexity)]
 pub fn setup_rendertarget(
     mut commands: Commands,
@@ -621,46 +894,129 @@ pub fn setup_rendertarget(
     window: Option<Single<&Window, With<PrimaryWindow>>>,
     mut render_target_mesh_handle: Local<Option<Handle<Mesh>>>,
     settings: Res<VelloCanvasSettings>,
-    camera_query: Query<&Camera, With<VelloView>>,
+    views: Query<
+        (Entity, &Camera, Option<&VelloViewSettings>),
+        (With<VelloView>, Without<VelloRenderTarget>),
+    >,
 ) {
-    let (width, height) = get_viewport_size(camera_query, window);
+    for (view_entity, camera, view_settings) in views.iter() {
+        let (width, height) = get_canvas_size(camera, view_settings, window.as_deref().copied());
+        if width == 0 || height == 0 {
+            // Try again once the view has a size.
+            continue;
+        }
 
-    let mesh_handle = render_target_mesh_handle.get_or_insert_with(|| {
-        let mut rendertarget_quad = Mesh::new(
-            PrimitiveTopology::TriangleList,
-            RenderAssetUsages::default(),
-        );
+        let mesh_handle = render_target_mesh_handle.get_or_insert_with(|| {
+            let mut rendertarget_quad = Mesh::new(
+                PrimitiveTopology::TriangleList,
+                RenderAssetUsages::default(),
+            );
 
-        // Rectangle of the screen
-        let verts = vec![
-            [-1.0, -1.0, 0.0],
-            [1.0, -1.0, 0.0],
-            [1.0, 1.0, 0.0],
-            [-1.0, 1.0, 0.0],
-        ];
-        rendertarget_quad.insert_attribute(Mesh::ATTRIBUTE_POSITION, verts);
-
-        let uv_pos = vec![[-1.0, -1.0], [1.0, -1.0], [1.0, 1.0], [1.0, 1.0]];
-        rendertarget_quad.insert_attribute(Mesh::ATTRIBUTE_UV_0, uv_pos);
-
-        let indices = vec![0, 1, 2, 0, 2, 3];
-        rendertarget_quad.insert_indices(Indices::U32(indices));
-
-        meshes.add(rendertarget_quad)
-    });
-    let texture_image = setup_image(&mut images, width, height);
-
-    commands
-        .spawn((
-            Name::new("Vello Canvas"),
-            VelloRenderTarget(texture_image.clone()),
-            Mesh2d(mesh_handle.clone()),
-            MeshMaterial2d(custom_materials.add(VelloCanvasMaterial {
-                texture: texture_image,
-            })),
-        ))
-        .insert(NoFrustumCulling)
-        .insert(settings.render_layers.clone());
+            // Rectangle of the screen
+            let verts = vec![
+                [-1.0, -1.0, 0.0],
+                [1.0, -1.0, 0.0],
+                [1.0, 1.0, 0.0],
+                [-1.0, 1.0, 0.0],
+            ];
+            rendertarget_quad.insert_attribute(Mesh::ATTRIBUTE_POSITION, verts);
+
+            let uv_pos = vec![[-1.0, -1.0], [1.0, -1.0], [1.0, 1.0], [1.0, 1.0]];
+            rendertarget_quad.insert_attribute(Mesh::ATTRIBUTE_UV_0, uv_pos);
+
+            let indices = vec![0, 1, 2, 0, 2, 3];
+            rendertarget_quad.insert_indices(Indices::U32(indices));
+
+            meshes.add(rendertarget_quad)
+        });
+        // Canvases split into z layers are sorted with Bevy's transparent 2D items
+        let alpha_mode = if settings.z_layers.is_empty() {
+            AlphaMode2d::Opaque
+        } else {
+            AlphaMode2d::Blend
+        };
+        let format = view_settings.map_or_else(VelloCanvasFormat::default, |settings| {
+            settings.canvas_format
+        });
+        let mut texture_images = Vec::with_capacity(settings.z_layers.len() + 1);
+        for layer in 0..=settings.z_layers.len() {
+            let texture_image = setup_image(&mut images, width, height, format);
+            commands
+                .spawn((
+                    Name::new("Vello Canvas"),
+                    VelloCanvas {
+                        view: view_entity,
+                        layer,
+                    },
+                    Mesh2d(mesh_handle.clone()),
+                    MeshMaterial2d(custom_materials.add(VelloCanvasMaterial {
+                        texture: texture_image.clone(),
+                        alpha_mode,
+                        format,
+                    })),
+                    Transform::from_xyz(0.0, 0.0, canvas_layer_z(&settings.z_layers, layer)),
+                ))
+                .insert(NoFrustumCulling)
+                .insert(settings.render_layers.clone());
+            texture_images.push(texture_image);
+        }
+
+        commands
+            .entity(view_entity)
+            .insert(VelloRenderTarget(texture_images));
+    }
+}
+
+/// Despawns the canvases of cameras that are no longer a [`VelloView`].
+pub fn cleanup_rendertargets(
+    mut commands: Commands,
+    mut removed_views: RemovedComponents<VelloView>,
+    views: Query<(), With<VelloView>>,
+    canvases: Query<(Entity, &VelloCanvas)>,
+) {
+    for view_entity in removed_views.read() {
+        if let Ok(mut view) = commands.get_entity(view_entity) {
+            view.try_remove::<VelloRenderTarget>();
+        }
+    }
+    for (canvas_entity, canvas) in canvases.iter() {
+        if !views.contains(canvas.view) {
+            commands.entity(canvas_entity).despawn();
+        }
+    }
+}
+
+/// Ensures each canvas is only drawn by the camera it belongs to, even when several cameras see
+/// the canvas render layers.
+pub fn restrict_canvas_visibility(
+    mut views: Query<(Entity, &mut VisibleEntities), With<Camera>>,
+    canvases: Query<&VelloCanvas>,
+) {
+    for (view_entity, mut visible_entities) in views.iter_mut() {
+        visible_entities
+            .get_mut(TypeId::of::<Mesh2d>())
+            .retain(|entity| {
+                canvases
+                    .get(*entity)
+                    .ok()
+                    .is_none_or(|canvas| canvas.view == view_entity)
+            });
+    }
+}
+
+/// Decides which texture targets are rendered this frame.
+pub fn update_texture_targets(time: Res<Time>, mut targets: Query<&mut VelloTextureTarget>) {
+    for mut target in targets.iter_mut() {
+        let target = target.bypass_change_detection();
+        let refresh_due = match &mut target.refresh {
+            VelloTextureRefresh::EveryFrame => true,
+            VelloTextureRefresh::Timer(timer) => timer.tick(time.delta()).just_finished(),
+            VelloTextureRefresh::OnDemand => false,
+        };
+        target.render_this_frame = target.requested || refresh_due;
+        target.redraw_this_frame = target.requested;
+        target.requested = false;
+    }
 }
 
 /// Reinitialize for renderer settings changes.
@@ -671,7 +1027,219 @@ pub fn render_settings_change_detection(
     if render_settings.is_changed() && !render_settings.is_added() {
         // Replace renderer
         tracing::info!("Render settings changed, re-initializing vello...");
-        commands.remove_resource::<VelloRenderer>();
-        commands.init_resource::<VelloRenderer>();
+        commands.queue(init_renderer);
+    }
+}
+
+/// Inserts the renderer once its background initialization finished.
+pub fn poll_renderer_task(world: &mut World) {
+    let Some(mut task) = world.get_resource_mut::<VelloRendererTask>() else {
+        return;
+    };
+    let Some(init) = block_on(poll_once(&mut task.0)) else {
+        return;
+    };
+    world.remove_resource::<VelloRendererTask>();
+    insert_renderer(world, init);
+}
+
+/// Sends renderer availability and failures to the main world.
+pub fn send_renderer_messages(
+    mut messages: ResMut<VelloRendererMessages>,
+    mut main_world: ResMut<MainWorld>,
+) {
+    if std::mem::take(&mut messages.ready) {
+        main_world.write_message(VelloRendererReady);
+    }
+    for error in messages.errors.drain(..) {
+        main_world.write_message(error);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::test_utils;
+    use bevy::{camera::visibility::VisibleEntities, ecs::system::RunSystemOnce};
+    use std::any::TypeId;
+    use std::time::Duration;
+
+    /// Items exactly at a boundary belong to the layer above it, so a Bevy item at the boundary
+    /// z sits between the two layers.
+    #[test]
+    fn canvas_layer_splits_at_boundaries() {
+        let boundaries = [0.0, 5.0];
+        assert_eq!(canvas_layer(&boundaries, -1.0), 0);
+        assert_eq!(canvas_layer(&boundaries, 0.0), 1);
+        assert_eq!(canvas_layer(&boundaries, 3.0), 1);
+        assert_eq!(canvas_layer(&boundaries, 5.0), 2);
+        assert_eq!(canvas_layer(&boundaries, 7.0), 2);
+        assert_eq!(canvas_layer(&[], 7.0), 0);
+    }
+
+    /// A sprite at z=5 must draw above the canvas holding z=3 and below the one holding z=7.
+    #[test]
+    fn canvas_layer_z_sorts_around_boundaries() {
+        let boundaries = [5.0];
+        let below = canvas_layer_z(&boundaries, canvas_layer(&boundaries, 3.0));
+        let above = canvas_layer_z(&boundaries, canvas_layer(&boundaries, 7.0));
+        assert!(below < 5.0);
+        assert!(above > 5.0);
+        // A single canvas keeps its previous position.
+        assert_eq!(canvas_layer_z(&[], 0), 0.0);
+    }
+
+    #[test]
+    fn canvas_size_follows_render_scale() {
+        assert_eq!(scaled_canvas_size(1280, 720, 1.0), (1280, 720));
+        assert_eq!(scaled_canvas_size(1280, 720, 0.5), (640, 360));
+        assert_eq!(scaled_canvas_size(1280, 720, 2.0), (2560, 1440));
+        // Tiny scales still allocate a texture, but empty views stay empty.
+        assert_eq!(scaled_canvas_size(100, 100, 0.001), (1, 1));
+        assert_eq!(scaled_canvas_size(0, 100, 2.0), (0, 0));
+    }
+
+    #[test]
+    fn grouped_items_sort_as_a_unit() {
+        let (view, group, nested) = (
+            Entity::from_raw_u32(1).unwrap(),
+            Entity::from_raw_u32(2).unwrap(),
+            Entity::from_raw_u32(3).unwrap(),
+        );
+        let mut queues = GroupedQueues::new([view], [group, nested]);
+        queues.push(view, None, 0.0, "a".to_string());
+        queues.push(view, None, 2.0, "d".to_string());
+        queues.push(view, Some(group), 5.0, "c".to_string());
+        queues.push(view, Some(nested), 1.0, "b2".to_string());
+        queues.push(view, Some(nested), -1.0, "b1".to_string());
+        // Items of groups that weren't extracted are drawn outside any group
+        queues.push(view, Some(Entity::PLACEHOLDER), 3.0, "e".to_string());
+
+        let fold = |items: Vec<String>| format!("[{}]", items.join(" "));
+        queues.fold(view, nested, Some(group), -10.0, fold);
+        queues.fold(view, group, None, 1.0, fold);
+
+        let sorted: Vec<_> = queues
+            .into_sorted()
+            .flat_map(|(_, items)| items)
+            .map(|(_, item)| item)
+            .collect();
+        assert_eq!(sorted, ["a", "[[b1 b2] c]", "d", "e"]);
+    }
+
+    #[test]
+    fn each_view_gets_its_own_canvas() {
+        let mut world = World::new();
+        world.init_resource::<Assets<Mesh>>();
+        world.init_resource::<Assets<Image>>();
+        world.init_resource::<Assets<VelloCanvasMaterial>>();
+        world.init_resource::<VelloCanvasSettings>();
+        let view = |size: UVec2| (VelloView, test_utils::camera(size));
+        let (main_size, minimap_size) = (UVec2::new(100, 50), UVec2::new(40, 30));
+        let main = world.spawn(view(main_size)).id();
+        let minimap = world.spawn(view(minimap_size)).id();
+        world.run_system_once(setup_rendertarget).unwrap();
+        // Views that have canvases are not set up again
+        world.run_system_once(setup_rendertarget).unwrap();
+
+        let canvases: Vec<(Entity, VelloCanvas)> = world
+            .query::<(Entity, &VelloCanvas)>()
+            .iter(&world)
+            .map(|(entity, canvas)| (entity, *canvas))
+            .collect();
+        assert_eq!(canvases.len(), 2);
+        for (view, size) in [(main, main_size), (minimap, minimap_size)] {
+            assert_eq!(
+                canvases
+                    .iter()
+                    .filter(|(_, canvas)| canvas.view == view)
+                    .count(),
+                1
+            );
+            let target = world.get::<VelloRenderTarget>(view).unwrap();
+            let image = world.resource::<Assets<Image>>().get(&target.0[0]).unwrap();
+            assert_eq!(image.size(), size);
+        }
+
+        // Each camera only draws its own canvas
+        for view in [main, minimap] {
+            let mut visible_entities = VisibleEntities::default();
+            visible_entities
+                .get_mut(TypeId::of::<Mesh2d>())
+                .extend(canvases.iter().map(|(entity, _)| *entity));
+            world.entity_mut(view).insert(visible_entities);
+        }
+        world.run_system_once(restrict_canvas_visibility).unwrap();
+        for view in [main, minimap] {
+            let visible = world
+                .get::<VisibleEntities>(view)
+                .unwrap()
+                .get(TypeId::of::<Mesh2d>());
+            assert_eq!(visible.len(), 1);
+            assert_eq!(world.get::<VelloCanvas>(visible[0]).unwrap().view, view);
+        }
+
+        // Canvases are despawned with their view
+        world.entity_mut(minimap).remove::<VelloView>();
+        world.run_system_once(cleanup_rendertargets).unwrap();
+        let views: Vec<Entity> = world
+            .query::<&VelloCanvas>()
+            .iter(&world)
+            .map(|canvas| canvas.view)
+            .collect();
+        assert_eq!(views, [main]);
+        assert!(world.get::<VelloRenderTarget>(minimap).is_none());
+    }
+
+    #[test]
+    fn texture_targets_render_on_their_schedule() {
+        let mut world = World::new();
+        world.init_resource::<Time>();
+        let every_frame = world.spawn(VelloTextureTarget::new(Handle::default())).id();
+        let timer = world
+            .spawn(VelloTextureTarget::new(Handle::default()).with_refresh(
+                VelloTextureRefresh::Timer(Timer::from_seconds(1.0, TimerMode::Repeating)),
+            ))
+            .id();
+        let on_demand = world
+            .spawn(
+                VelloTextureTarget::new(Handle::default())
+                    .with_refresh(VelloTextureRefresh::OnDemand),
+            )
+            .id();
+        // Whether each target renders, and redraws, a frame `delta` seconds after the last
+        let frame = |world: &mut World, delta: f32| {
+            world
+                .resource_mut::<Time>()
+                .advance_by(Duration::from_secs_f32(delta));
+            world.run_system_once(update_texture_targets).unwrap();
+            [every_frame, timer, on_demand].map(|entity| {
+                let target = world.get::<VelloTextureTarget>(entity).unwrap();
+                (target.render_this_frame, target.redraw_this_frame)
+            })
+        };
+
+        // Every target renders its first frame
+        assert_eq!(frame(&mut world, 0.0), [(true, true); 3]);
+        assert_eq!(
+            frame(&mut world, 0.5),
+            [(true, false), (false, false), (false, false)]
+        );
+        assert_eq!(
+            frame(&mut world, 0.5),
+            [(true, false), (true, false), (false, false)]
+        );
+        world
+            .get_mut::<VelloTextureTarget>(on_demand)
+            .unwrap()
+            .request_render();
+        assert_eq!(
+            frame(&mut world, 0.5),
+            [(true, false), (false, false), (true, true)]
+        );
+        assert_eq!(
+            frame(&mut world, 0.5),
+            [(true, false), (true, false), (false, false)]
+        );
     }
 }
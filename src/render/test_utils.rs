@@ -0,0 +1,69 @@
+//! Fixtures shared by the render tests.
+
+use super::{
+    VelloCanvasSettings, VelloEntityCountData, VelloFrameChanges, VelloRenderable,
+    encode::ItemEncoder,
+};
+#[cfg(feature = "text")]
+use bevy::render::render_asset::RenderAssets;
+use bevy::{camera::RenderTargetInfo, prelude::*, render::MainWorld};
+use vello::kurbo::Affine;
+
+/// A 10x10 square. Its red channel tells squares apart in the draw data.
+pub(crate) struct Square {
+    pub red: f32,
+    pub alpha: f32,
+}
+
+impl Square {
+    pub fn new(red: f32) -> Self {
+        Self { red, alpha: 1.0 }
+    }
+}
+
+impl VelloRenderable for Square {
+    fn encode(&self, scene: &mut vello::Scene, affine: Affine) {
+        scene.fill(
+            vello::peniko::Fill::NonZero,
+            affine,
+            vello::peniko::Color::new([self.red, 1.0, 1.0, 1.0]),
+            None,
+            &vello::kurbo::Rect::new(0.0, 0.0, 10.0, 10.0),
+        );
+    }
+
+    fn alpha(&self) -> f32 {
+        self.alpha
+    }
+}
+
+/// A camera whose render target of `size` has already been computed.
+pub(crate) fn camera(size: UVec2) -> Camera {
+    let mut camera = Camera::default();
+    camera.computed.target_info = Some(RenderTargetInfo {
+        physical_size: size,
+        scale_factor: 1.0,
+    });
+    camera
+}
+
+/// A render world extracting from `main_world`, with the resources the extractors use.
+pub(crate) fn render_world(main_world: MainWorld) -> World {
+    let mut render_world = World::new();
+    render_world.insert_resource(main_world);
+    render_world.init_resource::<VelloFrameChanges>();
+    render_world.init_resource::<VelloEntityCountData>();
+    render_world.init_resource::<VelloCanvasSettings>();
+    render_world
+}
+
+/// Runs `f` with an item encoder.
+pub(crate) fn with_encoder<R>(f: impl FnOnce(ItemEncoder<'_>) -> R) -> R {
+    #[cfg(feature = "text")]
+    let fonts = RenderAssets::default();
+    f(ItemEncoder {
+        #[cfg(feature = "text")]
+        fonts: &fonts,
+        _lifetime: std::marker::PhantomData,
+    })
+}
@@ -0,0 +1,326 @@
+//! Vello UI drawn in Bevy's UI pass, interleaved with native UI nodes.
+//!
+//! The sorted UI items of a view are split into stacking ranges, wherever a native UI item is
+//! drawn between two of them. Each range is rendered into its own canvas, which is drawn over the
+//! viewport in the transparent UI phase, at the stack index of its first item.
+
+use std::ops::Range;
+
+use bevy::{
+    ecs::{
+        entity::{EntityHashMap, EntityHashSet},
+        system::{SystemParamItem, lifetimeless::Read},
+    },
+    image::BevyDefault,
+    math::FloatOrd,
+    prelude::*,
+    render::{
+        render_phase::{
+            DrawFunctions, PhaseItem, PhaseItemExtraIndex, RenderCommand, RenderCommandResult,
+            SetItemPipeline, TrackedRenderPass, ViewSortedRenderPhases,
+        },
+        render_resource::{
+            BindGroup, BindGroupEntries, BindGroupLayoutDescriptor, BindGroupLayoutEntries,
+            BlendState, ColorTargetState, ColorWrites, Extent3d, FilterMode, FragmentState,
+            PipelineCache, RenderPipelineDescriptor, Sampler, SamplerBindingType,
+            SamplerDescriptor, ShaderStages, SpecializedRenderPipeline, SpecializedRenderPipelines,
+            TextureDescriptor, TextureDimension, TextureFormat, TextureSampleType, TextureUsages,
+            TextureView, VertexState,
+            binding_types::{sampler, texture_2d},
+        },
+        renderer::RenderDevice,
+        sync_world::TemporaryRenderEntity,
+        view::{ExtractedView, ViewTarget},
+    },
+    ui_render::{TransparentUi, UiViewTarget},
+};
+
+use super::{
+    UI_CANVAS_SHADER_HANDLE, VelloRenderQueue, extract::ExtractedVelloView,
+    systems::scaled_canvas_size,
+};
+
+/// The offset of Vello content from its node's stack index in the transparent UI phase, above the
+/// node's images and below its text, like Bevy's
+/// [`stack_z_offsets`](bevy::ui_render::stack_z_offsets).
+pub(crate) const VELLO_UI_Z_OFFSET: f32 = 0.045;
+
+/// The UI canvases of every camera rendering interleaved UI, keyed by the main world camera.
+#[derive(Resource, Default, Deref, DerefMut)]
+pub(crate) struct VelloUiCanvases(EntityHashMap<Vec<VelloUiCanvas>>);
+
+/// A texture holding the UI items of one stacking range.
+pub(crate) struct VelloUiCanvas {
+    /// The range of the view's sorted UI items drawn into this canvas.
+    pub items: Range<usize>,
+    pub texture_view: TextureView,
+    pub size: UVec2,
+    bind_group: BindGroup,
+    /// Whether the canvas already shows its items, and can be reused as is.
+    pub up_to_date: bool,
+}
+
+impl VelloUiCanvas {
+    fn new(
+        device: &RenderDevice,
+        pipeline_cache: &PipelineCache,
+        pipeline: &VelloUiCanvasPipeline,
+        size: UVec2,
+    ) -> Self {
+        let texture = device.create_texture(&TextureDescriptor {
+            label: Some("vello_ui_canvas"),
+            size: Extent3d {
+                width: size.x,
+                height: size.y,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::Rgba8Unorm,
+            usage: TextureUsages::STORAGE_BINDING | TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let texture_view = texture.create_view(&default());
+        let bind_group = device.create_bind_group(
+            "vello_ui_canvas_bind_group",
+            &pipeline_cache.get_bind_group_layout(&pipeline.layout),
+            &BindGroupEntries::sequential((&texture_view, &pipeline.sampler)),
+        );
+        Self {
+            items: 0..0,
+            texture_view,
+            size,
+            bind_group,
+            up_to_date: false,
+        }
+    }
+}
+
+/// Splits the sort keys of a view's sorted UI items into ranges that no native UI item is drawn
+/// between, given the sorted keys of the native items. Native items sorted at the same key as a
+/// Vello item are drawn below it.
+pub(crate) fn ui_canvas_ranges(vello_keys: &[f32], native_keys: &[f32]) -> Vec<Range<usize>> {
+    let natives_below = |key: f32| native_keys.partition_point(|native| *native <= key);
+    let mut ranges = Vec::new();
+    let mut start = 0;
+    for index in 1..vello_keys.len() {
+        if natives_below(vello_keys[index]) > natives_below(vello_keys[index - 1]) {
+            ranges.push(start..index);
+            start = index;
+        }
+    }
+    if start < vello_keys.len() {
+        ranges.push(start..vello_keys.len());
+    }
+    ranges
+}
+
+/// Splits the UI items of every view into stacking ranges, and queues their canvases into the
+/// view's transparent UI phase. Runs after all native UI items are queued, before the phase is
+/// sorted.
+#[allow(clippy::too_many_arguments, reason = "Many system parameters")]
+pub(crate) fn queue_ui_canvases(
+    mut commands: Commands,
+    views: Query<(Entity, &ExtractedVelloView)>,
+    ui_views: Query<&ExtractedView, With<UiViewTarget>>,
+    mut render_queue: ResMut<VelloRenderQueue>,
+    mut canvases: ResMut<VelloUiCanvases>,
+    mut phases: ResMut<ViewSortedRenderPhases<TransparentUi>>,
+    pipeline: Res<VelloUiCanvasPipeline>,
+    mut pipelines: ResMut<SpecializedRenderPipelines<VelloUiCanvasPipeline>>,
+    pipeline_cache: Res<PipelineCache>,
+    draw_functions: Res<DrawFunctions<TransparentUi>>,
+    device: Res<RenderDevice>,
+) {
+    let draw_function = draw_functions.read().id::<DrawVelloUiCanvas>();
+    let ui_views: EntityHashMap<&ExtractedView> = ui_views
+        .iter()
+        .map(|view| (view.retained_view_entity.main_entity.id(), view))
+        .collect();
+
+    let mut live_cameras = EntityHashSet::default();
+    for (view_entity, view) in views.iter() {
+        let (Some(camera), Some(view_queue)) = (view.camera, render_queue.get_mut(&view_entity))
+        else {
+            continue;
+        };
+        let Some(ui_view) = ui_views.get(&camera) else {
+            continue;
+        };
+        let Some(phase) = phases.get_mut(&ui_view.retained_view_entity) else {
+            continue;
+        };
+        let (width, height) = scaled_canvas_size(view.size.x, view.size.y, view.render_scale);
+        if view_queue.ui.is_empty() || width == 0 || height == 0 {
+            continue;
+        }
+        let size = UVec2::new(width, height);
+
+        let vello_keys: Vec<f32> = view_queue
+            .ui_stack_indices
+            .iter()
+            .map(|stack_index| *stack_index as f32 + VELLO_UI_Z_OFFSET)
+            .collect();
+        let mut native_keys: Vec<f32> = phase.items.iter().map(|item| item.sort_key.0).collect();
+        native_keys.sort_by(f32::total_cmp);
+        let ranges = ui_canvas_ranges(&vello_keys, &native_keys);
+
+        let pipeline_id = pipelines.specialize(&pipeline_cache, &pipeline, ui_view.hdr);
+        let view_canvases = canvases.entry(camera).or_default();
+        view_canvases.truncate(ranges.len());
+        for (index, items) in ranges.into_iter().enumerate() {
+            if view_canvases
+                .get(index)
+                .is_none_or(|canvas| canvas.size != size)
+            {
+                let canvas = VelloUiCanvas::new(&device, &pipeline_cache, &pipeline, size);
+                if index < view_canvases.len() {
+                    view_canvases[index] = canvas;
+                } else {
+                    view_canvases.push(canvas);
+                }
+            }
+            let canvas = &mut view_canvases[index];
+            if canvas.items != items {
+                canvas.items = items.clone();
+                canvas.up_to_date = false;
+            }
+
+            let entity = commands
+                .spawn((
+                    VelloUiCanvasBindGroup(canvas.bind_group.clone()),
+                    TemporaryRenderEntity,
+                ))
+                .id();
+            phase.add(TransparentUi {
+                sort_key: FloatOrd(vello_keys[items.start]),
+                entity: (entity, camera.into()),
+                pipeline: pipeline_id,
+                draw_function,
+                batch_range: 0..1,
+                extra_index: PhaseItemExtraIndex::None,
+                index,
+                indexed: false,
+            });
+        }
+        view_queue.ui_interleaved = true;
+        live_cameras.insert(camera);
+    }
+
+    // Canvases of cameras without UI items this frame are released
+    canvases.retain(|camera, _| live_cameras.contains(camera));
+}
+
+/// Draws a UI canvas over its viewport.
+#[derive(Resource)]
+pub(crate) struct VelloUiCanvasPipeline {
+    layout: BindGroupLayoutDescriptor,
+    sampler: Sampler,
+}
+
+pub(crate) fn init_ui_canvas_pipeline(mut commands: Commands, device: Res<RenderDevice>) {
+    let layout = BindGroupLayoutDescriptor::new(
+        "vello_ui_canvas_layout",
+        &BindGroupLayoutEntries::sequential(
+            ShaderStages::FRAGMENT,
+            (
+                texture_2d(TextureSampleType::Float { filterable: true }),
+                sampler(SamplerBindingType::Filtering),
+            ),
+        ),
+    );
+    // Canvases scaled by the render scale are filtered
+    let sampler = device.create_sampler(&SamplerDescriptor {
+        label: Some("vello_ui_canvas_sampler"),
+        mag_filter: FilterMode::Linear,
+        min_filter: FilterMode::Linear,
+        ..default()
+    });
+    commands.insert_resource(VelloUiCanvasPipeline { layout, sampler });
+}
+
+impl SpecializedRenderPipeline for VelloUiCanvasPipeline {
+    /// Whether the view is HDR.
+    type Key = bool;
+
+    fn specialize(&self, hdr: Self::Key) -> RenderPipelineDescriptor {
+        RenderPipelineDescriptor {
+            label: Some("vello_ui_canvas_pipeline".into()),
+            layout: vec![self.layout.clone()],
+            vertex: VertexState {
+                shader: UI_CANVAS_SHADER_HANDLE,
+                ..default()
+            },
+            fragment: Some(FragmentState {
+                shader: UI_CANVAS_SHADER_HANDLE,
+                targets: vec![Some(ColorTargetState {
+                    format: if hdr {
+                        ViewTarget::TEXTURE_FORMAT_HDR
+                    } else {
+                        TextureFormat::bevy_default()
+                    },
+                    blend: Some(BlendState::ALPHA_BLENDING),
+                    write_mask: ColorWrites::ALL,
+                })],
+                ..default()
+            }),
+            ..default()
+        }
+    }
+}
+
+/// The bind group of the canvas a phase item draws.
+#[derive(Component)]
+pub(crate) struct VelloUiCanvasBindGroup(BindGroup);
+
+pub(crate) type DrawVelloUiCanvas = (SetItemPipeline, DrawUiCanvas);
+
+pub(crate) struct DrawUiCanvas;
+
+impl<P: PhaseItem> RenderCommand<P> for DrawUiCanvas {
+    type Param = ();
+    type ViewQuery = ();
+    type ItemQuery = Read<VelloUiCanvasBindGroup>;
+
+    fn render<'w>(
+        _item: &P,
+        _view: (),
+        bind_group: Option<&'w VelloUiCanvasBindGroup>,
+        _param: SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        let Some(bind_group) = bind_group else {
+            return RenderCommandResult::Skip;
+        };
+        pass.set_bind_group(0, &bind_group.0, &[]);
+        pass.draw(0..3, 0..1);
+        RenderCommandResult::Success
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ui_canvases_split_around_native_ui() {
+        let vello_keys: Vec<f32> = [1, 2, 2, 5, 6, 9]
+            .map(|stack_index| stack_index as f32 + VELLO_UI_Z_OFFSET)
+            .to_vec();
+
+        // Without native UI between them, all items share one canvas.
+        assert_eq!(ui_canvas_ranges(&vello_keys, &[]), vec![0..6]);
+        assert_eq!(ui_canvas_ranges(&vello_keys, &[0.0, 10.0]), vec![0..6]);
+
+        // A background below node 5 and text above node 6 split the items around them.
+        assert_eq!(
+            ui_canvas_ranges(&vello_keys, &[0.0, 5.0, 6.06, 12.0]),
+            vec![0..3, 3..5, 5..6]
+        );
+
+        // Items at the same stack index are never split apart.
+        assert_eq!(ui_canvas_ranges(&vello_keys, &[2.0]), vec![0..1, 1..6]);
+        assert!(ui_canvas_ranges(&[], &[1.0]).is_empty());
+    }
+}